@@ -0,0 +1,480 @@
+use core::fmt::Display;
+
+use crate::token;
+
+/// Index of a source file in a `module::Modules` set, single file programs are file 0.
+pub type FileId = usize;
+
+/// Source range of a node, from its first character to the character after its last one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub file: FileId,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, end_line: usize, end_column: usize) -> Self {
+        Span { file: 0, line, column, end_line, end_column }
+    }
+
+    pub fn in_file(self, file: FileId) -> Span {
+        Span { file, ..self }
+    }
+
+    /// Span covering both `self` and `other`, assuming `other` ends after `self` starts.
+    pub fn to(self, other: Span) -> Span {
+        Span { end_line: other.end_line, end_column: other.end_column, ..self }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Integer,
+    Rune,
+    String,
+    Boolean,
+    Void,
+    /// User-defined `complex` type, by name
+    Complex(String),
+    /// Reference to a `repo`, `user`, `deployment`, `manifest` or `ciConfig` declaration
+    Domain(DomainKind),
+    /// Result of calling a `command`: its exit status and captured output
+    Command,
+    /// Text holding secret values, masked wherever it could be shown
+    Secret,
+    /// `list<element>`
+    List(Box<Type>),
+    /// `map<key, value>`
+    Map(Box<Type>, Box<Type>),
+}
+
+impl Type {
+    pub fn from_keyword(keyword: &str) -> Option<Type> {
+        match keyword {
+            token::INTEGER => Some(Type::Integer),
+            token::RUNE => Some(Type::Rune),
+            token::STRING => Some(Type::String),
+            token::BOOLEAN => Some(Type::Boolean),
+            token::VOID => Some(Type::Void),
+            token::COMMAND => Some(Type::Command),
+            token::SECRET => Some(Type::Secret),
+            _ => DomainKind::from_keyword(keyword).map(Type::Domain),
+        }
+    }
+
+    /// Keyword or name of the type, without the element types of lists and maps.
+    pub fn name(&self) -> &str {
+        match self {
+            Type::Integer => token::INTEGER,
+            Type::Rune => token::RUNE,
+            Type::String => token::STRING,
+            Type::Boolean => token::BOOLEAN,
+            Type::Void => token::VOID,
+            Type::Complex(name) => name,
+            Type::Domain(kind) => kind.keyword(),
+            Type::Command => token::COMMAND,
+            Type::Secret => token::SECRET,
+            Type::List(_) => token::LIST,
+            Type::Map(_, _) => token::MAP,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Type::List(element) => write!(f, "{}<{}>", token::LIST, element),
+            Type::Map(key, value) => write!(f, "{}<{}, {}>", token::MAP, key, value),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DomainKind {
+    User,
+    Repo,
+    CiConfig,
+    Deployment,
+    Manifest,
+    /// Where to load a secret from, declarations of this kind are read as `secret` values
+    Secret,
+}
+
+impl DomainKind {
+    pub fn from_keyword(keyword: &str) -> Option<DomainKind> {
+        match keyword {
+            token::USER => Some(DomainKind::User),
+            token::REPO => Some(DomainKind::Repo),
+            token::CI_CONFIG => Some(DomainKind::CiConfig),
+            token::DEPLOYMENT => Some(DomainKind::Deployment),
+            token::MANIFEST => Some(DomainKind::Manifest),
+            token::SECRET => Some(DomainKind::Secret),
+            _ => None,
+        }
+    }
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            DomainKind::User => token::USER,
+            DomainKind::Repo => token::REPO,
+            DomainKind::CiConfig => token::CI_CONFIG,
+            DomainKind::Deployment => token::DEPLOYMENT,
+            DomainKind::Manifest => token::MANIFEST,
+            DomainKind::Secret => token::SECRET,
+        }
+    }
+
+    /// Type of the name of a declaration of this kind.
+    pub fn value_type(self) -> Type {
+        match self {
+            DomainKind::Secret => Type::Secret,
+            kind => Type::Domain(kind),
+        }
+    }
+}
+
+impl Display for DomainKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.keyword())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Negate,
+}
+
+impl UnaryOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnaryOp::Not => token::NOT,
+            UnaryOp::Negate => token::MINUS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostfixOp {
+    Increment,
+    Decrement,
+}
+
+impl PostfixOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PostfixOp::Increment => token::POST_INCREMENT,
+            PostfixOp::Decrement => token::POST_DECREMENT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitAnd,
+    Eq,
+    Neq,
+    Lt,
+    Rt,
+    LtEq,
+    RtEq,
+    Plus,
+    Minus,
+    Times,
+    Div,
+}
+
+impl BinaryOp {
+    pub fn from_token(token_type: &str) -> Option<BinaryOp> {
+        match token_type {
+            token::OR => Some(BinaryOp::Or),
+            token::AND => Some(BinaryOp::And),
+            token::PIPE => Some(BinaryOp::BitOr),
+            token::AMP => Some(BinaryOp::BitAnd),
+            token::EQ => Some(BinaryOp::Eq),
+            token::NEQ => Some(BinaryOp::Neq),
+            token::LT => Some(BinaryOp::Lt),
+            token::RT => Some(BinaryOp::Rt),
+            token::LT_EQ => Some(BinaryOp::LtEq),
+            token::RT_EQ => Some(BinaryOp::RtEq),
+            token::PLUS => Some(BinaryOp::Plus),
+            token::MINUS => Some(BinaryOp::Minus),
+            token::TIMES => Some(BinaryOp::Times),
+            token::DIV => Some(BinaryOp::Div),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryOp::Or => token::OR,
+            BinaryOp::And => token::AND,
+            BinaryOp::BitOr => token::PIPE,
+            BinaryOp::BitAnd => token::AMP,
+            BinaryOp::Eq => token::EQ,
+            BinaryOp::Neq => token::NEQ,
+            BinaryOp::Lt => token::LT,
+            BinaryOp::Rt => token::RT,
+            BinaryOp::LtEq => token::LT_EQ,
+            BinaryOp::RtEq => token::RT_EQ,
+            BinaryOp::Plus => token::PLUS,
+            BinaryOp::Minus => token::MINUS,
+            BinaryOp::Times => token::TIMES,
+            BinaryOp::Div => token::DIV,
+        }
+    }
+
+    /// Binding power of the operator, higher binds tighter. All binary operators are left associative.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::BitOr => 3,
+            BinaryOp::BitAnd => 4,
+            BinaryOp::Eq | BinaryOp::Neq => 5,
+            BinaryOp::Lt | BinaryOp::Rt | BinaryOp::LtEq | BinaryOp::RtEq => 6,
+            BinaryOp::Plus | BinaryOp::Minus => 7,
+            BinaryOp::Times | BinaryOp::Div => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    Import(ImportDecl),
+    Func(FuncDecl),
+    Complex(ComplexDecl),
+    Domain(DomainDecl),
+    Command(CommandDecl),
+    Stmt(Stmt),
+}
+
+impl Item {
+    pub fn span(&self) -> Span {
+        match self {
+            Item::Import(import) => import.span,
+            Item::Func(func) => func.span,
+            Item::Complex(complex) => complex.span,
+            Item::Domain(domain) => domain.span,
+            Item::Command(command) => command.span,
+            Item::Stmt(stmt) => stmt.span,
+        }
+    }
+
+    /// Name of the declaration and whether it's visible to importing files, `None` for statements and imports.
+    pub fn declared_name(&self) -> Option<(&str, bool)> {
+        match self {
+            Item::Func(func) => Some((&func.name, func.public)),
+            Item::Complex(complex) => Some((&complex.name, complex.public)),
+            Item::Domain(domain) => Some((&domain.name, domain.public)),
+            Item::Command(command) => Some((&command.name, command.public)),
+            Item::Import(_) | Item::Stmt(_) => None,
+        }
+    }
+}
+
+/// `import "path/to/common.shanty";`, the path is relative to the importing file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportDecl {
+    pub path: String,
+    pub path_span: Span,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncDecl {
+    pub public: bool,
+    pub name: String,
+    pub name_span: Span,
+    pub params: Vec<Param>,
+    pub return_type: Type,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub name_span: Span,
+    pub ty: Type,
+    pub span: Span,
+}
+
+/// `complex Name { field: type; ... }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComplexDecl {
+    pub public: bool,
+    pub name: String,
+    pub name_span: Span,
+    pub fields: Vec<FieldDecl>,
+    pub span: Span,
+}
+
+impl ComplexDecl {
+    pub fn field(&self, name: &str) -> Option<&FieldDecl> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDecl {
+    pub name: String,
+    pub ty: Type,
+    pub span: Span,
+}
+
+/// Configuration block such as `repo backend { url = "..."; branch = "main"; }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainDecl {
+    pub public: bool,
+    pub kind: DomainKind,
+    pub name: String,
+    pub name_span: Span,
+    pub fields: Vec<DomainField>,
+    pub span: Span,
+}
+
+impl DomainDecl {
+    pub fn field(&self, name: &str) -> Option<&DomainField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainField {
+    pub name: String,
+    pub name_span: Span,
+    pub value: Expr,
+    pub span: Span,
+}
+
+/// `command build(string target) { run = "cargo build --bin " + target; timeout = 600; }`
+///
+/// Calling a command runs the process described by its fields, with the parameters bound to the arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandDecl {
+    pub public: bool,
+    pub name: String,
+    pub name_span: Span,
+    pub params: Vec<Param>,
+    pub fields: Vec<DomainField>,
+    pub span: Span,
+}
+
+impl CommandDecl {
+    pub fn field(&self, name: &str) -> Option<&DomainField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    /// `integer x = 1;` or `var x = 1;`, `ty` is `None` for `var`
+    VarDecl {
+        name: String,
+        name_span: Span,
+        ty: Option<Type>,
+        value: Option<Expr>,
+    },
+    If {
+        condition: Expr,
+        then_branch: Block,
+        else_branch: Option<Box<Stmt>>,
+    },
+    While {
+        condition: Expr,
+        body: Block,
+    },
+    Return(Option<Expr>),
+    Block(Block),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Integer(i64),
+    String(String),
+    Rune(char),
+    Boolean(bool),
+    Identifier(String),
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Postfix {
+        op: PostfixOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Assign {
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// `value.field`
+    Field {
+        object: Box<Expr>,
+        field: String,
+        field_span: Span,
+    },
+    /// `Name { field: value, ... }`
+    ComplexLiteral {
+        name: String,
+        name_span: Span,
+        fields: Vec<FieldInit>,
+    },
+    /// `[value, ...]`
+    List(Vec<Expr>),
+    /// `[key: value, ...]`, `[:]` when empty
+    Map(Vec<(Expr, Expr)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInit {
+    pub name: String,
+    pub name_span: Span,
+    pub value: Expr,
+}
@@ -0,0 +1,408 @@
+//! Compact bytecode for the stack machine in `vm`, produced by `compiler`.
+//!
+//! Each instruction is a one byte opcode followed by its operands, most of them two byte big-endian indexes
+//! into the constants, locals or globals. Jumps hold the absolute offset of their target.
+
+use std::fmt::Write;
+
+use crate::ast::{BinaryOp, Span};
+use crate::stdlib;
+use crate::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Op {
+    /// Push constant `index`
+    Constant,
+    Pop,
+    Dup,
+    GetLocal,
+    /// Store the top of the stack in local `slot`, leaving it on the stack
+    SetLocal,
+    /// Mark local `slot` as declared without a value
+    UnsetLocal,
+    /// Store the top of the stack into a field of local `slot` following path `index`, leaving it on the stack
+    SetLocalField,
+    GetGlobal,
+    SetGlobal,
+    SetGlobalField,
+    /// Declare global `slot` with the value popped from the stack
+    DefineGlobal,
+    /// Declare global `slot` without a value
+    DeclareGlobal,
+    /// Push the value of domain `index`, evaluating it on first use
+    GetDomain,
+    /// Replace the object on the stack by its field named by constant `index`
+    GetField,
+    /// Build a complex value of layout `index` from the fields on the stack
+    Complex,
+    Negate,
+    Not,
+    Increment,
+    Decrement,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    BitAnd,
+    BitOr,
+    And,
+    Or,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Jump,
+    /// Pop a boolean and jump if it's false
+    JumpIfFalse,
+    /// Jump if the top of the stack is `false`, leaving it on the stack
+    JumpIfFalseKeep,
+    /// Jump if the top of the stack is `true`, leaving it on the stack
+    JumpIfTrueKeep,
+    /// Call function `index` with the `count` arguments on the stack
+    Call,
+    Return,
+    /// Fail with the message of constant `index`
+    Fail,
+    /// Call the settings function `index` of a command with the `count` arguments on the stack, then run the command
+    Exec,
+    /// Replace the settings of the secret named by constant `index` with its value
+    LoadSecret,
+    /// Build a list from the `count` elements on the stack
+    List,
+    /// Build a map from the `count` keys and values on the stack, alternating
+    Map,
+    /// Call builtin `index` of `stdlib::BUILTINS` with the `count` arguments on the stack
+    Builtin,
+}
+
+const OPS: [Op; 45] = [
+    Op::Constant,
+    Op::Pop,
+    Op::Dup,
+    Op::GetLocal,
+    Op::SetLocal,
+    Op::UnsetLocal,
+    Op::SetLocalField,
+    Op::GetGlobal,
+    Op::SetGlobal,
+    Op::SetGlobalField,
+    Op::DefineGlobal,
+    Op::DeclareGlobal,
+    Op::GetDomain,
+    Op::GetField,
+    Op::Complex,
+    Op::Negate,
+    Op::Not,
+    Op::Increment,
+    Op::Decrement,
+    Op::Add,
+    Op::Subtract,
+    Op::Multiply,
+    Op::Divide,
+    Op::BitAnd,
+    Op::BitOr,
+    Op::And,
+    Op::Or,
+    Op::Equal,
+    Op::NotEqual,
+    Op::Less,
+    Op::Greater,
+    Op::LessEqual,
+    Op::GreaterEqual,
+    Op::Jump,
+    Op::JumpIfFalse,
+    Op::JumpIfFalseKeep,
+    Op::JumpIfTrueKeep,
+    Op::Call,
+    Op::Return,
+    Op::Fail,
+    Op::Exec,
+    Op::LoadSecret,
+    Op::List,
+    Op::Map,
+    Op::Builtin,
+];
+
+impl Op {
+    pub fn from_byte(byte: u8) -> Option<Op> {
+        OPS.get(byte as usize).copied()
+    }
+
+    /// Sizes in bytes of the operands following the opcode.
+    pub fn operands(self) -> &'static [usize] {
+        match self {
+            Op::SetLocalField | Op::SetGlobalField => &[2, 2],
+            Op::Call | Op::Exec => &[2, 1],
+            Op::Builtin => &[1, 1],
+            Op::Constant
+            | Op::GetLocal
+            | Op::SetLocal
+            | Op::UnsetLocal
+            | Op::GetGlobal
+            | Op::SetGlobal
+            | Op::DefineGlobal
+            | Op::DeclareGlobal
+            | Op::GetDomain
+            | Op::GetField
+            | Op::Complex
+            | Op::Jump
+            | Op::JumpIfFalse
+            | Op::JumpIfFalseKeep
+            | Op::JumpIfTrueKeep
+            | Op::Fail
+            | Op::LoadSecret
+            | Op::List
+            | Op::Map => &[2],
+            _ => &[],
+        }
+    }
+
+    /// Length of the instruction, opcode included.
+    pub fn size(self) -> usize {
+        1 + self.operands().iter().sum::<usize>()
+    }
+
+    /// Operator applied by arithmetic, comparison and logical instructions.
+    pub fn binary(self) -> Option<BinaryOp> {
+        match self {
+            Op::Add => Some(BinaryOp::Plus),
+            Op::Subtract => Some(BinaryOp::Minus),
+            Op::Multiply => Some(BinaryOp::Times),
+            Op::Divide => Some(BinaryOp::Div),
+            Op::BitAnd => Some(BinaryOp::BitAnd),
+            Op::BitOr => Some(BinaryOp::BitOr),
+            Op::And => Some(BinaryOp::And),
+            Op::Or => Some(BinaryOp::Or),
+            Op::Equal => Some(BinaryOp::Eq),
+            Op::NotEqual => Some(BinaryOp::Neq),
+            Op::Less => Some(BinaryOp::Lt),
+            Op::Greater => Some(BinaryOp::Rt),
+            Op::LessEqual => Some(BinaryOp::LtEq),
+            Op::GreaterEqual => Some(BinaryOp::RtEq),
+            _ => None,
+        }
+    }
+
+    pub fn from_binary(op: BinaryOp) -> Op {
+        match op {
+            BinaryOp::Plus => Op::Add,
+            BinaryOp::Minus => Op::Subtract,
+            BinaryOp::Times => Op::Multiply,
+            BinaryOp::Div => Op::Divide,
+            BinaryOp::BitAnd => Op::BitAnd,
+            BinaryOp::BitOr => Op::BitOr,
+            BinaryOp::And => Op::And,
+            BinaryOp::Or => Op::Or,
+            BinaryOp::Eq => Op::Equal,
+            BinaryOp::Neq => Op::NotEqual,
+            BinaryOp::Lt => Op::Less,
+            BinaryOp::Rt => Op::Greater,
+            BinaryOp::LtEq => Op::LessEqual,
+            BinaryOp::RtEq => Op::GreaterEqual,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Op::Constant => "CONSTANT",
+            Op::Pop => "POP",
+            Op::Dup => "DUP",
+            Op::GetLocal => "GET_LOCAL",
+            Op::SetLocal => "SET_LOCAL",
+            Op::UnsetLocal => "UNSET_LOCAL",
+            Op::SetLocalField => "SET_LOCAL_FIELD",
+            Op::GetGlobal => "GET_GLOBAL",
+            Op::SetGlobal => "SET_GLOBAL",
+            Op::SetGlobalField => "SET_GLOBAL_FIELD",
+            Op::DefineGlobal => "DEFINE_GLOBAL",
+            Op::DeclareGlobal => "DECLARE_GLOBAL",
+            Op::GetDomain => "GET_DOMAIN",
+            Op::GetField => "GET_FIELD",
+            Op::Complex => "COMPLEX",
+            Op::Negate => "NEGATE",
+            Op::Not => "NOT",
+            Op::Increment => "INCREMENT",
+            Op::Decrement => "DECREMENT",
+            Op::Add => "ADD",
+            Op::Subtract => "SUBTRACT",
+            Op::Multiply => "MULTIPLY",
+            Op::Divide => "DIVIDE",
+            Op::BitAnd => "BIT_AND",
+            Op::BitOr => "BIT_OR",
+            Op::And => "AND",
+            Op::Or => "OR",
+            Op::Equal => "EQUAL",
+            Op::NotEqual => "NOT_EQUAL",
+            Op::Less => "LESS",
+            Op::Greater => "GREATER",
+            Op::LessEqual => "LESS_EQUAL",
+            Op::GreaterEqual => "GREATER_EQUAL",
+            Op::Jump => "JUMP",
+            Op::JumpIfFalse => "JUMP_IF_FALSE",
+            Op::JumpIfFalseKeep => "JUMP_IF_FALSE_KEEP",
+            Op::JumpIfTrueKeep => "JUMP_IF_TRUE_KEEP",
+            Op::Call => "CALL",
+            Op::Return => "RETURN",
+            Op::Fail => "FAIL",
+            Op::Exec => "EXEC",
+            Op::LoadSecret => "LOAD_SECRET",
+            Op::List => "LIST",
+            Op::Map => "MAP",
+            Op::Builtin => "BUILTIN",
+        }
+    }
+}
+
+/// Instructions of one function with their constants and source locations.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    /// Offset of the first instruction compiled from each span, in offset order
+    pub spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    /// Span of the source the instruction at `offset` was compiled from.
+    pub fn span_at(&self, offset: usize) -> Span {
+        match self.spans.binary_search_by_key(&offset, |&(start, _)| start) {
+            Ok(i) => self.spans[i].1,
+            Err(0) => Span::default(),
+            Err(i) => self.spans[i - 1].1,
+        }
+    }
+
+    /// Operand of `size` bytes at `offset`.
+    pub fn operand(&self, offset: usize, size: usize) -> usize {
+        self.code[offset..offset + size].iter().fold(0, |value, &byte| (value << 8) | byte as usize)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    /// Names of the local slots, parameters first, for error messages and disassembly
+    pub locals: Vec<String>,
+    pub chunk: Chunk,
+}
+
+/// Shape of the complex values built by a `COMPLEX` instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub name: String,
+    /// Field names in declaration order
+    pub fields: Vec<String>,
+    /// For each value on the stack, first pushed first, its position in `fields`
+    pub order: Vec<usize>,
+}
+
+/// A domain declaration, evaluated by calling `function` the first time it's used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Domain {
+    /// `kind name`, as in `repo backend`
+    pub name: String,
+    pub function: usize,
+}
+
+/// Compiled program.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bytecode {
+    pub functions: Vec<Function>,
+    /// Names of the global slots
+    pub globals: Vec<String>,
+    pub domains: Vec<Domain>,
+    pub layouts: Vec<Layout>,
+    /// Field paths of `SET_*_FIELD` instructions, in access order, with the span of each field name
+    pub paths: Vec<Vec<(String, Span)>>,
+    /// Function running the top-level statements of every file
+    pub script: usize,
+    pub main: Option<usize>,
+}
+
+/// Human-readable listing of every function, one instruction per line.
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut out = String::new();
+
+    for (index, function) in bytecode.functions.iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+        }
+        let _ = writeln!(out, "== {} ({} params, {} locals) ==", function.name, function.arity, function.locals.len());
+
+        let chunk = &function.chunk;
+        let mut offset = 0;
+        let mut line = None;
+        while offset < chunk.code.len() {
+            let op = match Op::from_byte(chunk.code[offset]) {
+                Some(op) => op,
+                None => {
+                    let _ = writeln!(out, "{:04}      <invalid {}>", offset, chunk.code[offset]);
+                    offset += 1;
+                    continue;
+                }
+            };
+
+            let span = chunk.span_at(offset);
+            if line == Some(span.line) {
+                let _ = write!(out, "{:04}    | ", offset);
+            } else {
+                let _ = write!(out, "{:04} {:>4} ", offset, span.line);
+                line = Some(span.line);
+            }
+            let _ = writeln!(out, "{}", instruction(bytecode, function, op, offset).trim_end());
+
+            offset += op.size();
+        }
+    }
+
+    out
+}
+
+fn instruction(bytecode: &Bytecode, function: &Function, op: Op, offset: usize) -> String {
+    let chunk = &function.chunk;
+    let operand = |i: usize| {
+        let position = offset + 1 + op.operands()[..i].iter().sum::<usize>();
+        chunk.operand(position, op.operands()[i])
+    };
+    let name = |names: &[String], index: usize| names.get(index).cloned().unwrap_or_else(|| "?".to_string());
+    let constant = |index: usize| match chunk.constants.get(index) {
+        Some(Value::String(text)) => format!("\"{}\"", text),
+        Some(Value::Rune(rune)) => format!("'{}'", rune),
+        Some(value) => value.to_string(),
+        None => "?".to_string(),
+    };
+
+    let detail = match op {
+        Op::Constant | Op::GetField | Op::Fail | Op::LoadSecret => format!("{} ({})", operand(0), constant(operand(0))),
+        Op::GetLocal | Op::SetLocal | Op::UnsetLocal => format!("{} ({})", operand(0), name(&function.locals, operand(0))),
+        Op::GetGlobal | Op::SetGlobal | Op::DefineGlobal | Op::DeclareGlobal => format!("{} ({})", operand(0), name(&bytecode.globals, operand(0))),
+        Op::SetLocalField | Op::SetGlobalField => {
+            let names = if op == Op::SetLocalField { &function.locals } else { &bytecode.globals };
+            let path: Vec<&str> = bytecode.paths.get(operand(1)).map(|path| path.iter().map(|(field, _)| field.as_str()).collect()).unwrap_or_default();
+            format!("{} ({}.{})", operand(0), name(names, operand(0)), path.join("."))
+        }
+        Op::GetDomain => format!("{} ({})", operand(0), bytecode.domains.get(operand(0)).map_or("?", |domain| domain.name.as_str())),
+        Op::Complex => match bytecode.layouts.get(operand(0)) {
+            Some(layout) => format!("{} ({} {{ {} }})", operand(0), layout.name, layout.fields.join(", ")),
+            None => format!("{} (?)", operand(0)),
+        },
+        Op::Jump | Op::JumpIfFalse | Op::JumpIfFalseKeep | Op::JumpIfTrueKeep => format!("-> {:04}", operand(0)),
+        Op::Call | Op::Exec => {
+            let callee = bytecode.functions.get(operand(0)).map_or("?", |function| function.name.as_str());
+            format!("{} ({}) {} args", operand(0), callee, operand(1))
+        }
+        Op::Builtin => {
+            let callee = stdlib::BUILTINS.get(operand(0)).map_or("?", |builtin| builtin.name);
+            format!("{} ({}) {} args", operand(0), callee, operand(1))
+        }
+        Op::List => format!("{} elements", operand(0)),
+        Op::Map => format!("{} entries", operand(0)),
+        _ => String::new(),
+    };
+
+    format!("{:<18} {}", op.name(), detail)
+}
@@ -0,0 +1,220 @@
+use core::fmt::Display;
+
+use crate::ast::{Block, Expr, ExprKind, FuncDecl, Item, Program, Span, Stmt, StmtKind, Type};
+
+pub type BlockId = usize;
+
+/// Straight-line run of statements, ending where control may go more than one way.
+#[derive(Debug, Default)]
+pub struct BasicBlock<'a> {
+    pub stmts: Vec<&'a Stmt>,
+    /// Condition deciding between the successors of an `if` or `while` block
+    pub condition: Option<&'a Expr>,
+    pub successors: Vec<BlockId>,
+}
+
+/// Control-flow graph of a function body.
+#[derive(Debug)]
+pub struct Cfg<'a> {
+    pub blocks: Vec<BasicBlock<'a>>,
+    pub entry: BlockId,
+    /// Reached by every `return`
+    pub exit: BlockId,
+    /// Reached by falling off the end of the body
+    pub end: BlockId,
+    /// `while (true)` loops, with the range of blocks making up their bodies
+    loops: Vec<InfiniteLoop>,
+}
+
+#[derive(Debug)]
+struct InfiniteLoop {
+    span: Span,
+    body: std::ops::Range<BlockId>,
+}
+
+impl<'a> Cfg<'a> {
+    /// Builds the graph of a function body.
+    pub fn build(func: &'a FuncDecl) -> Cfg<'a> {
+        let mut cfg = Cfg { blocks: vec![], entry: 0, exit: 0, end: 0, loops: vec![] };
+        cfg.entry = cfg.add_block();
+        cfg.exit = cfg.add_block();
+
+        let last = cfg.block(&func.body, cfg.entry);
+        cfg.end = cfg.add_block();
+        cfg.edge(last, cfg.end);
+        cfg
+    }
+
+    /// Blocks that can run, starting from the entry.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![self.entry];
+
+        while let Some(block) = stack.pop() {
+            if reachable[block] {
+                continue;
+            }
+            reachable[block] = true;
+            stack.extend(&self.blocks[block].successors);
+        }
+
+        reachable
+    }
+
+    /// Blocks leading into each block.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![vec![]; self.blocks.len()];
+        for (id, block) in self.blocks.iter().enumerate() {
+            for &successor in &block.successors {
+                predecessors[successor].push(id);
+            }
+        }
+        predecessors
+    }
+
+    fn add_block(&mut self) -> BlockId {
+        self.blocks.push(BasicBlock::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, from: BlockId, to: BlockId) {
+        self.blocks[from].successors.push(to);
+    }
+
+    /// Adds the statements of `block` starting in `current`, returning the block control ends up in.
+    fn block(&mut self, block: &'a Block, mut current: BlockId) -> BlockId {
+        for stmt in &block.stmts {
+            current = self.stmt(stmt, current);
+        }
+        current
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt, current: BlockId) -> BlockId {
+        match &stmt.kind {
+            StmtKind::If { condition, then_branch, else_branch } => {
+                self.blocks[current].stmts.push(stmt);
+                self.blocks[current].condition = Some(condition);
+
+                let then_start = self.add_block();
+                self.edge(current, then_start);
+                let then_end = self.block(then_branch, then_start);
+
+                let else_end = match else_branch {
+                    Some(else_branch) => {
+                        let else_start = self.add_block();
+                        self.edge(current, else_start);
+                        self.stmt(else_branch, else_start)
+                    }
+                    None => current,
+                };
+
+                let join = self.add_block();
+                self.edge(then_end, join);
+                self.edge(else_end, join);
+                join
+            }
+            StmtKind::While { condition, body } => {
+                let header = self.add_block();
+                self.edge(current, header);
+                self.blocks[header].stmts.push(stmt);
+                self.blocks[header].condition = Some(condition);
+
+                let body_start = self.add_block();
+                self.edge(header, body_start);
+                let body_end = self.block(body, body_start);
+                self.edge(body_end, header);
+
+                let after = self.add_block();
+                if is_true(condition) {
+                    self.loops.push(InfiniteLoop { span: stmt.span, body: body_start..after });
+                } else {
+                    self.edge(header, after);
+                }
+                after
+            }
+            StmtKind::Return(_) => {
+                self.blocks[current].stmts.push(stmt);
+                self.edge(current, self.exit);
+                // Whatever follows a return starts a block nothing leads to
+                self.add_block()
+            }
+            StmtKind::Block(block) => self.block(block, current),
+            StmtKind::VarDecl { .. } | StmtKind::Expr(_) => {
+                self.blocks[current].stmts.push(stmt);
+                current
+            }
+        }
+    }
+}
+
+fn is_true(condition: &Expr) -> bool {
+    matches!(condition.kind, ExprKind::Boolean(true))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowError {
+    pub message: String,
+    pub span: Span,
+    /// Unreachable code is only worth a warning
+    pub warning: bool,
+}
+
+impl Display for FlowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Reports functions that can end without returning their value, code that can never run
+/// and `while (true)` loops without a `return` to leave them.
+pub fn check(program: &Program) -> Vec<FlowError> {
+    let mut errors = vec![];
+
+    for item in &program.items {
+        if let Item::Func(func) = item {
+            check_func(func, &mut errors);
+        }
+    }
+
+    errors.sort_by_key(|e| (e.span.file, e.span.line, e.span.column));
+    errors
+}
+
+fn check_func(func: &FuncDecl, errors: &mut Vec<FlowError>) {
+    let cfg = Cfg::build(func);
+    let reachable = cfg.reachable();
+    let predecessors = cfg.predecessors();
+
+    // Only the first statement of a dead region is reported, not every block it leads to.
+    // Blocks are numbered in source order, so only loops lead back to a block with a lower id.
+    let mut covered = vec![false; cfg.blocks.len()];
+    for id in 0..cfg.blocks.len() {
+        covered[id] = !reachable[id]
+            && predecessors[id].iter().any(|&p| p < id && !reachable[p] && (covered[p] || !cfg.blocks[p].stmts.is_empty()));
+    }
+
+    for (id, block) in cfg.blocks.iter().enumerate() {
+        if !reachable[id] && !covered[id] {
+            if let Some(stmt) = block.stmts.first() {
+                errors.push(FlowError { message: "Unreachable code".to_string(), span: stmt.span, warning: true });
+            }
+        }
+    }
+
+    for infinite in &cfg.loops {
+        let leaves = infinite.body.clone().any(|id| reachable[id] && cfg.blocks[id].successors.contains(&cfg.exit));
+        if reachable[infinite.body.start] && !leaves {
+            errors.push(FlowError { message: "while (true) never ends, its body has no return".to_string(), span: infinite.span, warning: false });
+        }
+    }
+
+    if func.return_type != Type::Void && reachable[cfg.end] {
+        let body = func.body.span;
+        let closing_brace = Span::new(body.end_line, body.end_column - 1, body.end_line, body.end_column).in_file(body.file);
+        errors.push(FlowError {
+            message: format!("Function {} returns {} but can reach its end without a return", func.name, func.return_type),
+            span: closing_brace,
+            warning: false,
+        });
+    }
+}
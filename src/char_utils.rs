@@ -29,7 +29,7 @@ const COMP_OPERATORS: [char; 4] = [
     '>',
 ];
 
-const RESERVED: [&str; 18] = [
+const RESERVED: [&str; 23] = [
     token::INTEGER,
     token::RUNE,
     token::STRING,
@@ -39,6 +39,9 @@ const RESERVED: [&str; 18] = [
     token::CI_CONFIG,
     token::DEPLOYMENT,
     token::MANIFEST,
+    token::SECRET,
+    token::LIST,
+    token::MAP,
     token::IF,
     token::ELSE,
     token::WHILE,
@@ -48,6 +51,8 @@ const RESERVED: [&str; 18] = [
     token::RETURN,
     token::COMMAND,
     token::FUNC,
+    token::IMPORT,
+    token::PUB,
 ];
 
 const POSTFIX_OPERATORS: [char; 2] = [
@@ -60,9 +65,10 @@ const LOGICAL_OPERATORS: [char; 2] = [
     '|',
 ];
 
-const PUNCTUATION_CHARS: [char; 2] = [
+const PUNCTUATION_CHARS: [char; 3] = [
     ';',
-    ','
+    ',',
+    ':',
 ];
 
 const ARITHMETIC_OPERATORS: [&str; 5] = [
@@ -78,15 +84,15 @@ pub fn is_letter(c: char) -> bool {
 }
 
 pub fn is_digit(c: char) -> bool {
-    c.is_digit(10)
+    c.is_ascii_digit()
 }
 
 pub fn is_operator(s: &str) -> bool {
-    OPERATORS.iter().any(|&op| op == s)
+    OPERATORS.contains(&s)
 }
 
 pub fn is_comparison_operator(c: char) -> bool {
-    COMP_OPERATORS.iter().any(|&op| op == c)
+    COMP_OPERATORS.contains(&c)
 }
 
 pub fn is_arithmetic_operator(c: char) -> bool {
@@ -94,11 +100,11 @@ pub fn is_arithmetic_operator(c: char) -> bool {
 }
 
 pub fn is_postfix_operator(c: char) -> bool {
-    POSTFIX_OPERATORS.iter().any(|&op| op == c)
+    POSTFIX_OPERATORS.contains(&c)
 }
 
 pub fn is_logical_operator(c: char) -> bool {
-    LOGICAL_OPERATORS.iter().any(|&op| op == c)
+    LOGICAL_OPERATORS.contains(&c)
 }
 
 pub fn is_parenthesis(c: char) -> bool {
@@ -110,19 +116,19 @@ pub fn is_new_line(c: char) -> bool {
 }
 
 pub fn is_whitespace_or_new_line(c: char) -> bool {
-    c == ' ' || c == '\t' || is_new_line(c)
+    c == ' ' || c == '\t' || c == '\r' || is_new_line(c)
 }
 
 pub fn is_punctuation(c: char) -> bool {
-    PUNCTUATION_CHARS.iter().any(|&op| op == c)
+    PUNCTUATION_CHARS.contains(&c)
 }
 
 pub fn is_identifier_reserved(identifier: &str) -> bool {
-    RESERVED.iter().any(|&op| op == identifier)
+    RESERVED.contains(&identifier)
 }
 
 pub fn is_bracket(c: char) -> bool {
-    c == '{' || c == '}'
+    c == '{' || c == '}' || c == '[' || c == ']'
 }
 
 pub fn is_boolean_literal(identifier: &str) -> bool {
@@ -0,0 +1,713 @@
+use std::collections::{HashMap, HashSet};
+
+use core::fmt::Display;
+
+use crate::ast::{BinaryOp, Block, CommandDecl, ComplexDecl, DomainDecl, Expr, ExprKind, FuncDecl, Item, Program, Span, Stmt, StmtKind, Type, UnaryOp};
+use crate::command;
+use crate::module::Modules;
+use crate::resolve::Resolution;
+use crate::stdlib;
+use crate::suggest;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Type of `left op right`, or `None` if the operator doesn't apply to these operand types.
+///
+/// Arithmetic works on integers, `+` also concatenates strings and runes into a string.
+/// `&` and `|` are bitwise on integers and non short-circuiting on booleans.
+/// Ordering compares integers, runes or strings with each other, equality compares any two values of the same type.
+pub fn binary_type(op: BinaryOp, left: &Type, right: &Type) -> Option<Type> {
+    match (op, left, right) {
+        (BinaryOp::Plus, Type::Integer, Type::Integer) => Some(Type::Integer),
+        (BinaryOp::Plus, Type::String, Type::String)
+        | (BinaryOp::Plus, Type::String, Type::Rune)
+        | (BinaryOp::Plus, Type::Rune, Type::String) => Some(Type::String),
+        (BinaryOp::Plus, Type::Secret, Type::String | Type::Rune | Type::Secret) | (BinaryOp::Plus, Type::String | Type::Rune, Type::Secret) => Some(Type::Secret),
+        (BinaryOp::Minus, Type::Integer, Type::Integer)
+        | (BinaryOp::Times, Type::Integer, Type::Integer)
+        | (BinaryOp::Div, Type::Integer, Type::Integer) => Some(Type::Integer),
+        (BinaryOp::And, Type::Boolean, Type::Boolean) | (BinaryOp::Or, Type::Boolean, Type::Boolean) => Some(Type::Boolean),
+        (BinaryOp::BitAnd, Type::Integer, Type::Integer) | (BinaryOp::BitOr, Type::Integer, Type::Integer) => Some(Type::Integer),
+        (BinaryOp::BitAnd, Type::Boolean, Type::Boolean) | (BinaryOp::BitOr, Type::Boolean, Type::Boolean) => Some(Type::Boolean),
+        (BinaryOp::Lt, _, _) | (BinaryOp::Rt, _, _) | (BinaryOp::LtEq, _, _) | (BinaryOp::RtEq, _, _) => {
+            let ordered = matches!(left, Type::Integer | Type::Rune | Type::String);
+            if ordered && left == right {
+                Some(Type::Boolean)
+            } else {
+                None
+            }
+        }
+        (BinaryOp::Eq, _, _) | (BinaryOp::Neq, _, _) => {
+            if left == right && *left != Type::Void {
+                Some(Type::Boolean)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Message for a value of type `actual` given where `what` expects `expected`.
+/// Secrets are only accepted by commands, never where a plain string could be shown or exported.
+pub fn mismatch(what: &str, expected: &Type, actual: &Type) -> String {
+    match (expected, actual) {
+        (Type::String, Type::Secret) => format!("{} expects string but got secret, which only commands accept", what),
+        _ => format!("{} expects {} but got {}", what, expected, actual),
+    }
+}
+
+/// Type of `op operand`, or `None` if the operator doesn't apply.
+pub fn unary_type(op: UnaryOp, operand: &Type) -> Option<Type> {
+    match (op, operand) {
+        (UnaryOp::Not, Type::Boolean) => Some(Type::Boolean),
+        (UnaryOp::Negate, Type::Integer) => Some(Type::Integer),
+        _ => None,
+    }
+}
+
+/// Types inferred for the bindings of a program, alongside the type errors found while inferring them.
+#[derive(Debug, Default)]
+pub struct Types {
+    /// Span of a declared name (variable, parameter or domain declaration) to its type
+    pub bindings: HashMap<Span, Type>,
+    /// Span of an expression to its type, for the expressions whose type is known
+    pub expressions: HashMap<Span, Type>,
+    pub errors: Vec<TypeError>,
+}
+
+impl Types {
+    /// Type of the binding declared at `span`.
+    pub fn binding(&self, span: Span) -> Option<&Type> {
+        self.bindings.get(&span)
+    }
+
+    /// Type of the binding declared at `span`, or of the one the identifier at `span` refers to.
+    pub fn binding_at(&self, resolution: &Resolution, span: Span) -> Option<&Type> {
+        self.binding(span).or_else(|| self.binding(resolution.definition(span)?.span))
+    }
+}
+
+/// Type checks a single file.
+///
+/// Operators must be applied to operands they support, calls must match the signature of the called function,
+/// returns must match the return type of their function and `if`/`while` conditions must be boolean.
+/// Complex literals must set every declared field with a value of the field's type, and accessed fields must exist.
+pub fn check(program: &Program) -> Vec<TypeError> {
+    infer(program).errors
+}
+
+/// Checks every loaded file, each one seeing the `pub` declarations of the files it imports.
+pub fn check_modules(modules: &Modules) -> Vec<TypeError> {
+    infer_modules(modules).errors
+}
+
+/// Type checks a single file and infers the type of every binding.
+///
+/// `var` declarations take the type of their initializer, or of the first value assigned to them when they have none.
+pub fn infer(program: &Program) -> Types {
+    infer_with_imports(program, &[])
+}
+
+/// Infers the types of every loaded file, each one seeing the `pub` declarations of the files it imports.
+pub fn infer_modules(modules: &Modules) -> Types {
+    let mut types = Types::default();
+
+    for &file in &modules.order {
+        let imports: Vec<&Program> = modules.files[file].imports.iter().map(|&id| &modules.files[id].program).collect();
+        let file_types = infer_with_imports(&modules.files[file].program, &imports);
+        types.bindings.extend(file_types.bindings);
+        types.expressions.extend(file_types.expressions);
+        types.errors.extend(file_types.errors);
+    }
+
+    types
+}
+
+fn infer_with_imports(program: &Program, imports: &[&Program]) -> Types {
+    let mut checker = Checker {
+        complexes: HashMap::new(),
+        functions: HashMap::new(),
+        commands: HashMap::new(),
+        scopes: vec![HashMap::new()],
+        private: HashMap::new(),
+        current_function: None,
+        bindings: HashMap::new(),
+        expressions: HashMap::new(),
+        errors: vec![],
+    };
+
+    for import in imports {
+        checker.register_import(import);
+    }
+    checker.check_program(program);
+    checker.close_scope();
+
+    checker.errors.sort_by_key(|e| (e.span.file, e.span.line, e.span.column));
+    Types { bindings: checker.bindings, expressions: checker.expressions, errors: checker.errors }
+}
+
+struct Binding {
+    name_span: Span,
+    /// `None` when the type is unknown because of an error
+    ty: Option<Type>,
+    /// A `var` without initializer whose type comes from the first value assigned to it
+    pending: bool,
+}
+
+struct Checker<'a> {
+    complexes: HashMap<&'a str, &'a ComplexDecl>,
+    functions: HashMap<&'a str, &'a FuncDecl>,
+    commands: HashMap<&'a str, &'a CommandDecl>,
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Declarations of imported files that aren't `pub`, to explain why they can't be found
+    private: HashMap<&'a str, Span>,
+    /// Function whose body is being checked, `None` at the top level
+    current_function: Option<&'a FuncDecl>,
+    bindings: HashMap<Span, Type>,
+    expressions: HashMap<Span, Type>,
+    errors: Vec<TypeError>,
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, message: String, span: Span) {
+        self.errors.push(TypeError { message, span });
+    }
+
+    fn register_import(&mut self, program: &'a Program) {
+        for item in &program.items {
+            match item.declared_name() {
+                Some((name, false)) => {
+                    self.private.insert(name, item.span());
+                    continue;
+                }
+                Some((_, true)) => {}
+                None => continue,
+            }
+
+            // Like the resolver, the first imported file to declare a name keeps it
+            match item {
+                Item::Complex(complex) => {
+                    self.complexes.entry(&complex.name).or_insert(complex);
+                }
+                Item::Func(func) => {
+                    self.functions.entry(&func.name).or_insert(func);
+                }
+                Item::Command(command) => {
+                    self.commands.entry(&command.name).or_insert(command);
+                }
+                Item::Domain(domain) => {
+                    if !self.scopes[0].contains_key(&domain.name) {
+                        self.declare(&domain.name, domain.name_span, Some(domain.kind.value_type()));
+                    }
+                }
+                Item::Import(_) | Item::Stmt(_) => {}
+            }
+        }
+    }
+
+    /// Error for a name that couldn't be found, pointing out imported declarations that aren't `pub`.
+    fn unknown(&mut self, what: String, name: &str, span: Span) {
+        if self.private.contains_key(name) {
+            self.error(format!("{} is not pub in the file that declares it", what), span);
+            return;
+        }
+
+        self.error(format!("Unknown {}", what), span);
+    }
+
+    fn check_program(&mut self, program: &'a Program) {
+        // Functions and commands of the file, which replace the imported ones of the same name but not each other
+        let mut declared = HashSet::new();
+        for item in &program.items {
+            match item {
+                Item::Complex(complex) => {
+                    if self.complexes.insert(&complex.name, complex).is_some() {
+                        self.error(format!("Complex type {} is declared more than once", complex.name), complex.name_span);
+                    }
+                }
+                Item::Func(func) => {
+                    if !declared.insert(&func.name) {
+                        self.error(format!("Function {} is declared more than once", func.name), func.name_span);
+                        continue;
+                    }
+                    self.functions.insert(&func.name, func);
+                }
+                Item::Command(command) => {
+                    if !declared.insert(&command.name) {
+                        self.error(format!("Command {} is declared more than once", command.name), command.name_span);
+                        continue;
+                    }
+                    self.commands.insert(&command.name, command);
+                }
+                Item::Domain(domain) => {
+                    if self.scopes[0].contains_key(&domain.name) {
+                        self.error(format!("{} is declared more than once", domain.name), domain.name_span);
+                    }
+                    self.declare(&domain.name, domain.name_span, Some(domain.kind.value_type()));
+                }
+                Item::Import(_) | Item::Stmt(_) => {}
+            }
+        }
+
+        for item in &program.items {
+            match item {
+                Item::Complex(complex) => self.check_complex(complex),
+                Item::Domain(domain) => self.check_domain(domain),
+                Item::Stmt(stmt) => self.check_stmt(stmt),
+                Item::Func(_) | Item::Command(_) | Item::Import(_) => {}
+            }
+        }
+
+        // Like name resolution, function and command bodies come last so that they see every top-level variable
+        for item in &program.items {
+            match item {
+                Item::Func(func) => self.check_func(func),
+                Item::Command(command) => self.check_command(command),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_func(&mut self, func: &'a FuncDecl) {
+        self.check_type(&func.return_type, func.name_span);
+        self.scopes.push(HashMap::new());
+        for param in &func.params {
+            self.check_type(&param.ty, param.span);
+            self.declare(&param.name, param.name_span, Some(param.ty.clone()));
+        }
+
+        self.current_function = Some(func);
+        self.check_block(&func.body);
+        self.current_function = None;
+        self.close_scope();
+    }
+
+    /// Parameters are in scope of the fields, which must be among `command::SETTINGS` with the declared types.
+    fn check_command(&mut self, command: &'a CommandDecl) {
+        self.scopes.push(HashMap::new());
+        for param in &command.params {
+            self.check_type(&param.ty, param.span);
+            self.declare(&param.name, param.name_span, Some(param.ty.clone()));
+        }
+
+        for (i, field) in command.fields.iter().enumerate() {
+            let value_ty = self.value_type(&field.value);
+            if command.fields[..i].iter().any(|f| f.name == field.name) {
+                self.error(format!("Field {} is set more than once", field.name), field.name_span);
+                continue;
+            }
+
+            match command::SETTINGS.iter().find(|setting| setting.name == field.name) {
+                // Secrets are masked when the command runs
+                Some(setting) if setting.ty == Type::String && value_ty == Some(Type::Secret) => {}
+                Some(setting) => self.expect_type(&setting.ty, value_ty, field.value.span, &format!("Field {}", field.name)),
+                None => {
+                    let message = match suggest::closest(&field.name, command::SETTINGS.iter().map(|setting| setting.name)) {
+                        Some(suggestion) => format!("Command has no field {}, did you mean {}?", field.name, suggestion),
+                        None => format!("Command has no field {}", field.name),
+                    };
+                    self.error(message, field.name_span);
+                }
+            }
+        }
+
+        let missing: Vec<&str> = command::SETTINGS.iter().filter(|setting| setting.required && command.field(setting.name).is_none()).map(|setting| setting.name).collect();
+        if !missing.is_empty() {
+            self.error(format!("Missing fields {} in command {}", missing.join(", "), command.name), command.name_span);
+        }
+
+        self.close_scope();
+    }
+
+    fn check_complex(&mut self, complex: &ComplexDecl) {
+        for (i, field) in complex.fields.iter().enumerate() {
+            if complex.fields[..i].iter().any(|f| f.name == field.name) {
+                self.error(format!("Field {} is declared more than once in {}", field.name, complex.name), field.span);
+            }
+            self.check_type(&field.ty, field.span);
+        }
+    }
+
+    /// Types the field values, whether they fit the schema of the domain kind is left to `domain::validate`.
+    fn check_domain(&mut self, domain: &DomainDecl) {
+        for field in &domain.fields {
+            self.value_type(&field.value);
+        }
+    }
+
+    /// Reports uses of complex types that were never declared.
+    fn check_type(&mut self, ty: &Type, span: Span) {
+        match ty {
+            Type::Complex(name) if !self.complexes.contains_key(name.as_str()) => self.unknown(format!("type {}", name), name, span),
+            Type::List(element) => self.check_type(element, span),
+            Type::Map(key, value) => {
+                self.check_type(key, span);
+                self.check_type(value, span);
+            }
+            _ => {}
+        }
+    }
+
+    fn declare(&mut self, name: &str, name_span: Span, ty: Option<Type>) {
+        if let Some(ty) = &ty {
+            self.bindings.insert(name_span, ty.clone());
+        }
+        self.scopes.last_mut().expect("scope").insert(name.to_string(), Binding { name_span, ty, pending: false });
+    }
+
+    fn lookup(&self, name: &str) -> Option<Type> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).and_then(|binding| binding.ty.clone())
+    }
+
+    /// Gives a `var` declared without initializer the type of the first value assigned to it.
+    fn infer_pending(&mut self, name: &str, ty: Option<Type>) {
+        let binding = match self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            Some(binding) if binding.pending => binding,
+            _ => return,
+        };
+
+        binding.pending = false;
+        if let Some(ty) = ty {
+            self.bindings.insert(binding.name_span, ty.clone());
+            binding.ty = Some(ty);
+        }
+    }
+
+    /// Pops the innermost scope, reporting `var`s whose type could never be inferred.
+    fn close_scope(&mut self) {
+        let scope = self.scopes.pop().expect("scope");
+        let mut pending: Vec<(&String, &Binding)> = scope.iter().filter(|(_, binding)| binding.pending).collect();
+        pending.sort_by_key(|(_, binding)| (binding.name_span.line, binding.name_span.column));
+
+        for (name, binding) in pending {
+            self.error(format!("Can't infer the type of {}, it's never given a value", name), binding.name_span);
+        }
+    }
+
+    fn check_block(&mut self, block: &Block) {
+        self.scopes.push(HashMap::new());
+        for stmt in &block.stmts {
+            self.check_stmt(stmt);
+        }
+        self.close_scope();
+    }
+
+    fn check_condition(&mut self, condition: &Expr, statement: &str) {
+        let ty = self.value_type(condition);
+        self.expect_type(&Type::Boolean, ty, condition.span, &format!("Condition of {}", statement));
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::VarDecl { name, name_span, ty, value } => {
+                if let Some(ty) = ty {
+                    self.check_type(ty, *name_span);
+                }
+
+                let value_ty = value.as_ref().and_then(|value| self.value_type(value));
+                if let (Some(ty), Some(value)) = (ty, value) {
+                    self.expect_type(ty, value_ty.clone(), value.span, &format!("Variable {}", name));
+                }
+                self.declare(name, *name_span, ty.clone().or(value_ty));
+                if ty.is_none() && value.is_none() {
+                    self.scopes.last_mut().expect("scope").get_mut(name).expect("declared").pending = true;
+                }
+            }
+            StmtKind::If { condition, then_branch, else_branch } => {
+                self.check_condition(condition, "if");
+                self.check_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check_stmt(else_branch);
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.check_condition(condition, "while");
+                self.check_block(body);
+            }
+            StmtKind::Return(value) => self.check_return(value.as_ref(), stmt.span),
+            StmtKind::Block(block) => self.check_block(block),
+            StmtKind::Expr(expr) => {
+                self.type_of(expr);
+            }
+        }
+    }
+
+    fn check_return(&mut self, value: Option<&Expr>, span: Span) {
+        let func = match self.current_function {
+            Some(func) => func,
+            None => {
+                if let Some(value) = value {
+                    self.type_of(value);
+                }
+                self.error("return is only allowed inside a function".to_string(), span);
+                return;
+            }
+        };
+
+        match (value, &func.return_type) {
+            (None, Type::Void) => {}
+            (None, return_type) => self.error(format!("Function {} returns {} but return has no value", func.name, return_type), span),
+            (Some(value), Type::Void) => {
+                self.type_of(value);
+                self.error(format!("Function {} returns void but return has a value", func.name), value.span);
+            }
+            (Some(value), return_type) => {
+                let value_ty = self.value_type(value);
+                self.expect_type(return_type, value_ty, value.span, &format!("Function {}", func.name));
+            }
+        }
+    }
+
+    fn expect_type(&mut self, expected: &Type, actual: Option<Type>, span: Span, what: &str) {
+        // Unknown types are reported where they are declared
+        if let Type::Complex(name) = expected {
+            if !self.complexes.contains_key(name.as_str()) {
+                return;
+            }
+        }
+
+        if let Some(actual) = actual {
+            if &actual != expected {
+                self.error(mismatch(what, expected, &actual), span);
+            }
+        }
+    }
+
+    /// Type of an expression whose value is used, calls to void functions have no value to use.
+    fn value_type(&mut self, expr: &Expr) -> Option<Type> {
+        let ty = self.type_of(expr)?;
+        if ty == Type::Void {
+            self.error("Expression has no value, it calls a void function".to_string(), expr.span);
+            return None;
+        }
+
+        Some(ty)
+    }
+
+    /// Type of an expression, `None` when it can't be told because of an error that was already reported.
+    fn type_of(&mut self, expr: &Expr) -> Option<Type> {
+        let ty = self.compute_type(expr)?;
+        self.expressions.insert(expr.span, ty.clone());
+        Some(ty)
+    }
+
+    fn compute_type(&mut self, expr: &Expr) -> Option<Type> {
+        match &expr.kind {
+            ExprKind::Integer(_) => Some(Type::Integer),
+            ExprKind::String(_) => Some(Type::String),
+            ExprKind::Rune(_) => Some(Type::Rune),
+            ExprKind::Boolean(_) => Some(Type::Boolean),
+            ExprKind::Identifier(name) => {
+                let ty = self.lookup(name);
+                if ty.is_none() && self.functions.contains_key(name.as_str()) {
+                    self.error(format!("Function {} can only be called", name), expr.span);
+                }
+                if ty.is_none() && self.commands.contains_key(name.as_str()) {
+                    self.error(format!("Command {} can only be called", name), expr.span);
+                }
+                if ty.is_none() && !self.functions.contains_key(name.as_str()) && stdlib::get(name).is_some() {
+                    self.error(format!("Builtin {} can only be called", name), expr.span);
+                }
+                ty
+            }
+            ExprKind::List(elements) => {
+                let types = elements.iter().map(|element| (element.span, self.value_type(element))).collect();
+                let element_ty = self.element_type(types, "List element")?;
+                Some(Type::List(Box::new(element_ty)))
+            }
+            ExprKind::Map(entries) => {
+                let mut key_types = vec![];
+                let mut value_types = vec![];
+                for (key, value) in entries {
+                    key_types.push((key.span, self.value_type(key)));
+                    value_types.push((value.span, self.value_type(value)));
+                }
+                let key_ty = self.element_type(key_types, "Map key");
+                let value_ty = self.element_type(value_types, "Map value");
+                Some(Type::Map(Box::new(key_ty?), Box::new(value_ty?)))
+            }
+            ExprKind::Unary { op, operand } => {
+                let operand_ty = self.value_type(operand)?;
+                let ty = unary_type(*op, &operand_ty);
+                if ty.is_none() {
+                    self.error(format!("Operator {} can't be applied to {}", op.as_str(), operand_ty), expr.span);
+                }
+                ty
+            }
+            ExprKind::Postfix { op, operand } => {
+                let operand_ty = self.value_type(operand)?;
+                if operand_ty != Type::Integer {
+                    self.error(format!("Operator {} can't be applied to {}", op.as_str(), operand_ty), expr.span);
+                    return None;
+                }
+                Some(Type::Integer)
+            }
+            ExprKind::Binary { op, left, right } => {
+                let left_ty = self.value_type(left);
+                let right_ty = self.value_type(right);
+                let (left_ty, right_ty) = (left_ty?, right_ty?);
+
+                let ty = binary_type(*op, &left_ty, &right_ty);
+                if ty.is_none() {
+                    self.error(format!("Operator {} can't be applied to {} and {}", op.as_str(), left_ty, right_ty), expr.span);
+                }
+                ty
+            }
+            ExprKind::Assign { target, value } => {
+                let target_ty = self.type_of(target);
+                let value_ty = self.value_type(value);
+                if let ExprKind::Identifier(name) = &target.kind {
+                    if target_ty.is_none() {
+                        self.infer_pending(name, value_ty.clone());
+                        return value_ty;
+                    }
+                }
+                if let Some(target_ty) = &target_ty {
+                    let what = match &target.kind {
+                        ExprKind::Field { field, .. } => format!("Field {}", field),
+                        ExprKind::Identifier(name) => format!("Variable {}", name),
+                        _ => "Assignment".to_string(),
+                    };
+                    self.expect_type(target_ty, value_ty, value.span, &what);
+                }
+                target_ty
+            }
+            ExprKind::Call { callee, args } => self.check_call(callee, args, expr.span),
+            ExprKind::Field { object, field, field_span } => {
+                let object_ty = self.value_type(object)?;
+                if object_ty == Type::Command {
+                    let ty = command::result_field(field);
+                    if ty.is_none() {
+                        self.error(format!("Type command has no field {}", field), *field_span);
+                    }
+                    return ty;
+                }
+                let complex = match &object_ty {
+                    Type::Complex(name) => *self.complexes.get(name.as_str())?,
+                    _ => {
+                        self.error(format!("Type {} has no fields", object_ty), *field_span);
+                        return None;
+                    }
+                };
+
+                match complex.field(field) {
+                    Some(declared) => Some(declared.ty.clone()),
+                    None => {
+                        self.error(format!("Complex type {} has no field {}", complex.name, field), *field_span);
+                        None
+                    }
+                }
+            }
+            ExprKind::ComplexLiteral { name, name_span, fields } => {
+                let complex = match self.complexes.get(name.as_str()) {
+                    Some(complex) => *complex,
+                    None => {
+                        self.unknown(format!("type {}", name), name, *name_span);
+                        for field in fields {
+                            self.type_of(&field.value);
+                        }
+                        return None;
+                    }
+                };
+
+                for (i, field) in fields.iter().enumerate() {
+                    let value_ty = self.value_type(&field.value);
+                    if fields[..i].iter().any(|f| f.name == field.name) {
+                        self.error(format!("Field {} is set more than once", field.name), field.name_span);
+                        continue;
+                    }
+
+                    match complex.field(&field.name) {
+                        Some(declared) => self.expect_type(&declared.ty, value_ty, field.value.span, &format!("Field {}", field.name)),
+                        None => self.error(format!("Complex type {} has no field {}", complex.name, field.name), field.name_span),
+                    }
+                }
+
+                let missing: Vec<&str> =
+                    complex.fields.iter().filter(|f| !fields.iter().any(|init| init.name == f.name)).map(|f| f.name.as_str()).collect();
+                if !missing.is_empty() {
+                    self.error(format!("Missing fields {} in {} literal", missing.join(", "), complex.name), *name_span);
+                }
+
+                Some(Type::Complex(name.clone()))
+            }
+        }
+    }
+
+    /// Type shared by the elements of a literal, the first one they are checked against.
+    fn element_type(&mut self, elements: Vec<(Span, Option<Type>)>, what: &str) -> Option<Type> {
+        let mut element_ty: Option<Type> = None;
+        for (span, ty) in elements {
+            match &element_ty {
+                Some(expected) => {
+                    let expected = expected.clone();
+                    self.expect_type(&expected, ty, span, what);
+                }
+                None => element_ty = ty,
+            }
+        }
+        element_ty
+    }
+
+    fn check_call(&mut self, callee: &Expr, args: &[Expr], span: Span) -> Option<Type> {
+        let arg_types: Vec<Option<Type>> = args.iter().map(|arg| self.value_type(arg)).collect();
+
+        let name = match &callee.kind {
+            ExprKind::Identifier(name) => name,
+            _ => {
+                self.type_of(callee);
+                self.error("Only functions can be called".to_string(), callee.span);
+                return None;
+            }
+        };
+
+        if let Some(command) = self.commands.get(name.as_str()).copied() {
+            if args.len() != command.params.len() {
+                self.error(format!("Command {} expects {} arguments but got {}", command.name, command.params.len(), args.len()), span);
+            }
+            for (i, ((arg, arg_ty), param)) in args.iter().zip(arg_types).zip(&command.params).enumerate() {
+                self.expect_type(&param.ty, arg_ty, arg.span, &format!("Argument {} of {}", i + 1, command.name));
+            }
+            return Some(Type::Command);
+        }
+
+        let func = match self.functions.get(name.as_str()) {
+            Some(func) => *func,
+            None => {
+                if let Some(ty) = self.lookup(name) {
+                    self.error(format!("{} is a variable of type {}, not a function", name, ty), callee.span);
+                    return None;
+                }
+                let builtin = stdlib::get(name)?;
+                return match builtin.check(&arg_types) {
+                    Ok(ty) => ty,
+                    Err((message, arg)) => {
+                        self.error(message, arg.map_or(span, |i| args[i].span));
+                        None
+                    }
+                };
+            }
+        };
+
+        if args.len() != func.params.len() {
+            self.error(format!("Function {} expects {} arguments but got {}", func.name, func.params.len(), args.len()), span);
+        }
+
+        for (i, ((arg, arg_ty), param)) in args.iter().zip(arg_types).zip(&func.params).enumerate() {
+            self.expect_type(&param.ty, arg_ty, arg.span, &format!("Argument {} of {}", i + 1, func.name));
+        }
+
+        Some(func.return_type.clone())
+    }
+}
@@ -0,0 +1,485 @@
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use shanty::bytecode;
+use shanty::cfg;
+use shanty::checker;
+use shanty::command::{self, DryRun, Runner};
+use shanty::compiler;
+use shanty::constant;
+use shanty::dap;
+use shanty::diagnostic::{self, Diagnostic, Severity, Source, Sources};
+use shanty::diff;
+use shanty::domain::{self, Schemas};
+use shanty::formatter;
+use shanty::graph;
+use shanty::init;
+use shanty::interpreter;
+use shanty::limits::{self, Limits};
+use shanty::lint::{self, Config};
+use shanty::module::{self, Modules};
+use shanty::resolve;
+use shanty::value::Value;
+use shanty::vm;
+
+use crate::repl;
+
+pub(crate) const USAGE: &str = "Usage: shanty [<command> [args]]
+
+Commands:
+    fmt [--check] <file>...    Format files in place, or only report unformatted ones with --check
+    check <file>...            Report name and type errors
+    lint [--fix] <file>...     Report style and safety issues, applying the available fixes with --fix
+    graph <file>               Print the dependencies between domain declarations as DOT
+    run [--vm] <file>          Check a program and run it, printing the value returned by main
+        --dry-run              Record the commands it would run and print them before the value
+        --stubs <file>         With --dry-run, answer commands with the `command = status [stdout [stderr]]` lines of a file
+        --max-steps <n>        Stop the program after n calls and loop iterations
+        --max-depth <n>        Stop the program when n calls are in progress, 1000 by default and 10000 at most
+        --max-heap <bytes>     Stop the program once the values it built take more than this many bytes
+        --max-time <ms>        Stop the program after running this many milliseconds
+    disasm <file>              Print the bytecode a program compiles to
+    dap                        Serve the debug adapter protocol on stdin and stdout, for editors to debug programs
+    repl                       Read and run declarations, statements and expressions interactively, the default command
+
+Options:
+    --format text|json         Print diagnostics as text with source excerpts, or as JSON lines, and dry-run plans as text or JSON";
+
+/// Runs a subcommand and returns the process exit code.
+pub fn run(command: &str, args: &[String]) -> i32 {
+    let (output, args) = match Output::from_args(args) {
+        Some(parsed) => parsed,
+        None => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    match command {
+        "fmt" => fmt(&args, &output),
+        "check" => check(&args, &output),
+        "lint" => lint(&args, &output),
+        "graph" => dependency_graph(&args, &output),
+        "run" => run_program(&args, &output),
+        "disasm" => disassemble(&args, &output),
+        "dap" => debug_adapter(&args),
+        "repl" => repl::run(&args, &output),
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    }
+}
+
+/// How diagnostics are printed.
+pub(crate) struct Output {
+    json: bool,
+    color: bool,
+}
+
+impl Output {
+    /// Takes the `--format` option out of the arguments.
+    fn from_args(args: &[String]) -> Option<(Output, Vec<String>)> {
+        let mut json = false;
+        let mut rest = vec![];
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let format = match arg.strip_prefix("--format") {
+                Some("") => args.next()?.as_str(),
+                Some(value) => value.strip_prefix('=')?,
+                None => {
+                    rest.push(arg.clone());
+                    continue;
+                }
+            };
+            json = match format {
+                "json" => true,
+                "text" => false,
+                _ => return None,
+            };
+        }
+
+        let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        Some((Output { json, color }, rest))
+    }
+
+    /// Prints diagnostics to stderr, returning 1 if any of them is an error and 0 otherwise.
+    pub(crate) fn emit(&self, diagnostics: &[Diagnostic], sources: &dyn Sources) -> i32 {
+        for diagnostic in diagnostics {
+            if self.json {
+                eprintln!("{}", diagnostic.to_json(sources));
+            } else {
+                eprintln!("{}", diagnostic.render(sources, self.color));
+            }
+        }
+
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Loads a file and its imports, printing the errors if some can't be loaded.
+    fn load(&self, file: &str) -> Option<Modules> {
+        match module::load(Path::new(file)) {
+            Ok(modules) => Some(modules),
+            Err((modules, errors)) => {
+                let diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+                self.emit(&diagnostics, &modules);
+                None
+            }
+        }
+    }
+}
+
+fn fmt(args: &[String], output: &Output) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let files: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+
+    if files.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+
+    let mut status = 0;
+    for file in files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                status = 2;
+                continue;
+            }
+        };
+
+        let formatted = match formatter::format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                output.emit(&[Diagnostic::from(&e)], &Source { name: file, text: &source });
+                status = 2;
+                continue;
+            }
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            let diff = diff::unified(&source, &formatted, file);
+            if diff.is_empty() {
+                println!("{}: line endings differ", file);
+            } else {
+                print!("{}", diff);
+            }
+            status = status.max(1);
+        } else if let Err(e) = fs::write(file, formatted) {
+            eprintln!("{}: {}", file, e);
+            status = 2;
+        }
+    }
+
+    status
+}
+
+fn check(files: &[String], output: &Output) -> i32 {
+    if files.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+
+    let mut status = 0;
+    for file in files {
+        let modules = match output.load(file) {
+            Some(modules) => modules,
+            None => {
+                status = 2;
+                continue;
+            }
+        };
+
+        let diagnostics = analyze(&modules);
+        status = status.max(output.emit(&diagnostics, &modules));
+    }
+
+    status
+}
+
+/// Diagnostics of every static analysis of the loaded files, in source order.
+pub(crate) fn analyze(modules: &Modules) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+
+    let resolution = resolve::resolve_modules(modules);
+    diagnostics.extend(resolution.errors.iter().map(Diagnostic::from));
+
+    let types = checker::infer_modules(modules);
+    diagnostics.extend(types.errors.iter().map(Diagnostic::from));
+
+    for &file in &modules.order {
+        diagnostics.extend(cfg::check(&modules.files[file].program).iter().map(Diagnostic::from));
+    }
+
+    diagnostics.extend(init::check_modules(modules, &resolution).iter().map(Diagnostic::from));
+
+    let constants = constant::fold_modules(modules, &resolution);
+    diagnostics.extend(constants.errors.iter().map(Diagnostic::from));
+
+    diagnostics.extend(domain::validate_modules(modules, &Schemas::builtin(), &types, &constants).iter().map(Diagnostic::from));
+
+    let graph = graph::build_modules(modules, &resolution);
+    diagnostics.extend(graph.cycles().iter().map(|error| diagnostic::cycle(error, &graph)));
+
+    diagnostics.sort_by_key(|d| d.span().map(|span| (span.file, span.line, span.column)));
+    diagnostics
+}
+
+fn run_program(args: &[String], output: &Output) -> i32 {
+    let mut use_vm = false;
+    let mut dry_run = false;
+    let mut stubs = None;
+    let mut limits = Limits::default();
+    let mut files = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // Whether the option is complete and valid
+        let valid = match arg.as_str() {
+            "--vm" => {
+                use_vm = true;
+                true
+            }
+            "--dry-run" => {
+                dry_run = true;
+                true
+            }
+            "--stubs" => {
+                stubs = args.next();
+                stubs.is_some()
+            }
+            "--max-steps" => {
+                limits.steps = limit(args.next());
+                limits.steps.is_some()
+            }
+            "--max-depth" => {
+                limits.depth = limit(args.next());
+                if limits.depth.is_some_and(|depth| depth > limits::MAX_DEPTH) {
+                    eprintln!("--max-depth can't be above {}, the interpreter has no room for deeper calls", limits::MAX_DEPTH);
+                    return 2;
+                }
+                limits.depth.is_some()
+            }
+            "--max-heap" => {
+                limits.heap = limit(args.next());
+                limits.heap.is_some()
+            }
+            "--max-time" => {
+                limits.time = limit(args.next()).map(Duration::from_millis);
+                limits.time.is_some()
+            }
+            _ => {
+                files.push(arg);
+                true
+            }
+        };
+        if !valid {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    let file = match files[..] {
+        [file] if dry_run || stubs.is_none() => file,
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    let mut dry_run = match (dry_run, stubs) {
+        (false, _) => None,
+        (true, None) => Some(DryRun::default()),
+        (true, Some(stubs)) => match fs::read_to_string(stubs).map_err(|e| e.to_string()).and_then(|text| DryRun::parse_stubs(&text)) {
+            Ok(dry_run) => Some(dry_run),
+            Err(e) => {
+                eprintln!("{}: {}", stubs, e);
+                return 2;
+            }
+        },
+    };
+
+    let modules = match output.load(file) {
+        Some(modules) => modules,
+        None => return 2,
+    };
+
+    // Warnings are printed but don't stop the program from running
+    if output.emit(&analyze(&modules), &modules) != 0 {
+        return 1;
+    }
+
+    let mut local = command::Local::default();
+    let runner: &mut dyn Runner = match &mut dry_run {
+        Some(dry_run) => dry_run,
+        None => &mut local,
+    };
+
+    let resolution = resolve::resolve_modules(&modules);
+    let result = if use_vm {
+        match compiler::compile_modules(&modules, &resolution) {
+            Ok(bytecode) => vm::run_limited(&bytecode, runner, limits),
+            Err(error) => return output.emit(&[Diagnostic::from(&error)], &modules),
+        }
+    } else {
+        interpreter::run_modules_limited(&modules, &resolution, runner, limits)
+    };
+
+    // The plan is printed even if the program failed, up to the failure
+    if let Some(dry_run) = &dry_run {
+        if output.json {
+            println!("{}", command::plan_json(&dry_run.plan));
+        } else {
+            print!("{}", command::plan_text(&dry_run.plan));
+        }
+    }
+
+    match result {
+        Ok(Value::Void) => 0,
+        Ok(value) => {
+            println!("{}", value);
+            0
+        }
+        Err(error) => output.emit(&[Diagnostic::from(&error)], &modules),
+    }
+}
+
+/// Value of a limit option, `None` if it's missing or not a number.
+fn limit<T: FromStr>(value: Option<&String>) -> Option<T> {
+    value.and_then(|value| value.parse().ok())
+}
+
+fn debug_adapter(args: &[String]) -> i32 {
+    if !args.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+
+    match dap::serve(&mut io::stdin().lock(), &mut io::stdout().lock()) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Debug adapter failed: {}", e);
+            1
+        }
+    }
+}
+
+fn disassemble(args: &[String], output: &Output) -> i32 {
+    let file = match args {
+        [file] => file,
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    let modules = match output.load(file) {
+        Some(modules) => modules,
+        None => return 2,
+    };
+
+    match compiler::compile_modules(&modules, &resolve::resolve_modules(&modules)) {
+        Ok(bytecode) => {
+            print!("{}", bytecode::disassemble(&bytecode));
+            0
+        }
+        Err(error) => output.emit(&[Diagnostic::from(&error)], &modules),
+    }
+}
+
+fn dependency_graph(args: &[String], output: &Output) -> i32 {
+    let file = match args {
+        [file] => file,
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    let modules = match output.load(file) {
+        Some(modules) => modules,
+        None => return 2,
+    };
+
+    let graph = graph::build_modules(&modules, &resolve::resolve_modules(&modules));
+    print!("{}", graph.to_dot());
+
+    let diagnostics: Vec<Diagnostic> = graph.cycles().iter().map(|error| diagnostic::cycle(error, &graph)).collect();
+    output.emit(&diagnostics, &modules)
+}
+
+fn lint(args: &[String], output: &Output) -> i32 {
+    let fix = args.iter().any(|arg| arg == "--fix");
+    let files: Vec<&String> = args.iter().filter(|arg| *arg != "--fix").collect();
+
+    if files.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+
+    let mut status = 0;
+    for file in files {
+        let config = match lint_config(Path::new(file)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}", e);
+                status = 2;
+                continue;
+            }
+        };
+
+        let modules = match output.load(file) {
+            Some(modules) => modules,
+            None => {
+                status = 2;
+                continue;
+            }
+        };
+
+        // Only the named file is linted, the files it imports are linted when named themselves
+        let root = &modules.files[0];
+        let findings = lint::lint(&root.source, &root.program, &resolve::resolve_modules(&modules), &config);
+        let reported: Vec<Diagnostic> = findings.iter().filter(|finding| !(fix && finding.fix.is_some())).map(Diagnostic::from).collect();
+        status = status.max(output.emit(&reported, &modules));
+
+        if fix && findings.iter().any(|finding| finding.fix.is_some()) {
+            if let Err(e) = fs::write(file, lint::apply_fixes(&root.source, &findings)) {
+                eprintln!("{}: {}", file, e);
+                status = 2;
+            }
+        }
+    }
+
+    status
+}
+
+/// Configuration from the closest lint config file in the directory of `file` or above it.
+fn lint_config(file: &Path) -> Result<Config, String> {
+    let mut dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    loop {
+        let candidate: PathBuf = dir.join(lint::CONFIG_FILE);
+        if candidate.is_file() {
+            let text = fs::read_to_string(&candidate).map_err(|e| format!("{}: {}", candidate.display(), e))?;
+            return Config::parse(&text).map_err(|e| format!("{}: {}", candidate.display(), e));
+        }
+
+        let absolute = if dir.as_os_str().is_empty() { std::env::current_dir().ok() } else { Some(dir.clone()) };
+        match absolute.as_deref().and_then(Path::parent) {
+            Some(parent) => dir = parent.to_path_buf(),
+            None => return Ok(Config::default()),
+        }
+    }
+}
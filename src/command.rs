@@ -0,0 +1,533 @@
+//! Running the processes described by `command` declarations.
+//!
+//! A command sets `run`, the program and its arguments split like a shell would split words but without any expansion,
+//! and optionally `cwd`, `env` as `NAME=value` words and `timeout` in seconds. Calling it returns a `command` value
+//! holding the exit status and the captured output.
+//!
+//! A `Runner` carries the invocations out: `Local` spawns processes, `DryRun` only records them into a plan.
+//! Fields may hold secrets, whose values are masked in the output, the plan and error messages.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ast::Type;
+use crate::domain::FieldSchema;
+use crate::json;
+use crate::secret::{self, Source};
+use crate::value::Value;
+
+pub const RUN: &str = "run";
+pub const CWD: &str = "cwd";
+pub const ENV: &str = "env";
+pub const TIMEOUT: &str = "timeout";
+
+/// Fields a command declaration may set.
+pub static SETTINGS: [FieldSchema; 4] = [
+    FieldSchema::required(RUN, Type::String),
+    FieldSchema::optional(CWD, Type::String),
+    FieldSchema::optional(ENV, Type::String),
+    FieldSchema::optional(TIMEOUT, Type::Integer),
+];
+
+pub const STATUS: &str = "status";
+pub const STDOUT: &str = "stdout";
+pub const STDERR: &str = "stderr";
+pub const TIMED_OUT: &str = "timedOut";
+
+/// How long the output of a timed out command is still read after it was killed. A process that left its group
+/// survives the kill and may hold the pipes open indefinitely, what it writes after that is lost.
+const CAPTURE_GRACE: Duration = Duration::from_secs(1);
+
+/// Type of a field of the value returned by a command call.
+pub fn result_field(name: &str) -> Option<Type> {
+    match name {
+        STATUS => Some(Type::Integer),
+        STDOUT | STDERR => Some(Type::String),
+        TIMED_OUT => Some(Type::Boolean),
+        _ => None,
+    }
+}
+
+/// A process to start.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    /// Name of the command declaration
+    pub command: String,
+    /// Program followed by its arguments
+    pub argv: Vec<String>,
+    pub cwd: Option<String>,
+    /// Variables added to the environment inherited from shanty
+    pub env: Vec<(String, String)>,
+    /// Seconds after which the process and its children are killed
+    pub timeout: Option<u64>,
+    /// Values of the secrets used by the fields
+    pub secrets: Vec<String>,
+}
+
+impl Invocation {
+    /// Reads the fields evaluated from a command declaration named `command`. Error messages are masked.
+    pub fn from_settings(command: &str, settings: &Value) -> Result<Invocation, String> {
+        let mut secrets = vec![];
+        for field in [RUN, CWD, ENV] {
+            if let Some(Value::Secret(secret)) = settings.field(field) {
+                secrets.extend(secret.values.iter().cloned());
+            }
+        }
+
+        Invocation::read(command, settings, secrets.clone()).map_err(|message| secret::mask(&message, &secrets))
+    }
+
+    /// Copy of the invocation also masking `secrets`, values its fields may not hold but its output could show.
+    pub fn with_secrets(&self, secrets: &[String]) -> Invocation {
+        let mut invocation = self.clone();
+        invocation.secrets.extend(secrets.iter().filter(|value| !self.secrets.contains(value)).cloned());
+        invocation
+    }
+
+    fn read(command: &str, settings: &Value, secrets: Vec<String>) -> Result<Invocation, String> {
+        let string = |field: &str| match settings.field(field) {
+            Some(Value::String(text)) => Ok(Some(text.clone())),
+            Some(Value::Secret(secret)) => Ok(Some(secret.text.clone())),
+            Some(other) => Err(format!("Field {} of command {} expects string but got {}", field, command, other.type_name())),
+            None => Ok(None),
+        };
+
+        let run = string(RUN)?.ok_or_else(|| format!("Command {} has no {} field", command, RUN))?;
+        let argv = words(&run).map_err(|e| format!("Can't split the {} field of command {}: {}", RUN, command, e))?;
+        if argv.is_empty() {
+            return Err(format!("Command {} has nothing to run", command));
+        }
+
+        let mut env = vec![];
+        if let Some(text) = string(ENV)? {
+            for word in words(&text).map_err(|e| format!("Can't split the {} field of command {}: {}", ENV, command, e))? {
+                match word.split_once('=') {
+                    Some((name, value)) if !name.is_empty() => env.push((name.to_string(), value.to_string())),
+                    _ => return Err(format!("Environment entry {} of command {} isn't NAME=value", word, command)),
+                }
+            }
+        }
+
+        let timeout = match settings.field(TIMEOUT) {
+            Some(&Value::Integer(seconds)) if seconds > 0 => Some(seconds as u64),
+            Some(Value::Integer(seconds)) => return Err(format!("Timeout of command {} must be positive but got {}", command, seconds)),
+            Some(other) => return Err(format!("Field {} of command {} expects integer but got {}", TIMEOUT, command, other.type_name())),
+            None => None,
+        };
+
+        Ok(Invocation { command: command.to_string(), argv, cwd: string(CWD)?, env, timeout, secrets })
+    }
+
+    /// `text` with the secret values of the invocation masked.
+    pub fn mask(&self, text: &str) -> String {
+        secret::mask(text, &self.secrets)
+    }
+
+    /// Copy of the invocation safe to show, secret values masked.
+    pub fn masked(&self) -> Invocation {
+        Invocation {
+            command: self.command.clone(),
+            argv: self.argv.iter().map(|word| self.mask(word)).collect(),
+            cwd: self.cwd.as_deref().map(|cwd| self.mask(cwd)),
+            env: self.env.iter().map(|(name, value)| (name.clone(), self.mask(value))).collect(),
+            timeout: self.timeout,
+            secrets: vec![],
+        }
+    }
+}
+
+/// How a process ended.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outcome {
+    /// Exit code, or 128 plus the signal number for a process killed by a signal
+    pub status: i64,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+}
+
+impl Outcome {
+    /// The `command` value returned by the call.
+    pub fn to_value(&self) -> Value {
+        Value::Complex {
+            name: crate::token::COMMAND.to_string(),
+            fields: vec![
+                (STATUS.to_string(), Value::Integer(self.status)),
+                (STDOUT.to_string(), Value::String(self.stdout.clone())),
+                (STDERR.to_string(), Value::String(self.stderr.clone())),
+                (TIMED_OUT.to_string(), Value::Boolean(self.timed_out)),
+            ],
+        }
+    }
+}
+
+/// Carries out command invocations and secret loads for the interpreter.
+pub trait Runner {
+    /// Runs `invocation` to completion, failing only if it can't be started.
+    /// The outcome and the error message must not show the secret values of the invocation, nor any loaded by `secret`.
+    fn run(&mut self, invocation: &Invocation) -> Result<Outcome, String>;
+
+    /// Loads the value of the secret declared as `name`. A runner showing output keeps the value to mask it there.
+    fn secret(&mut self, name: &str, source: &Source) -> Result<String, String> {
+        source.load().map_err(|e| format!("Can't load secret {}: {}", name, e))
+    }
+}
+
+/// Spawns local processes, streaming their output line by line prefixed with `[command]` while capturing it.
+/// The values of every secret loaded through it are masked in both, whether the command uses them or not.
+#[derive(Debug, Clone, Default)]
+pub struct Local {
+    /// Only capture the output, without echoing it
+    pub quiet: bool,
+    secrets: Vec<String>,
+}
+
+impl Local {
+    /// A runner only capturing the output.
+    pub fn quiet() -> Local {
+        Local { quiet: true, ..Local::default() }
+    }
+}
+
+impl Runner for Local {
+    fn run(&mut self, invocation: &Invocation) -> Result<Outcome, String> {
+        let invocation = &invocation.with_secrets(&self.secrets);
+        let mut process = Command::new(&invocation.argv[0]);
+        process.args(&invocation.argv[1..]).envs(invocation.env.iter().map(|(k, v)| (k, v))).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        if let Some(cwd) = &invocation.cwd {
+            process.current_dir(cwd);
+        }
+        // The process leads its own group so that a timeout also kills whatever it started
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut process, 0);
+
+        let program = invocation.mask(&invocation.argv[0]);
+        let mut child = process.spawn().map_err(|e| format!("Can't run {}: {}", program, e))?;
+
+        let prefix = if self.quiet { None } else { Some(format!("[{}] ", invocation.command)) };
+        let stdout = capture(child.stdout.take().expect("piped stdout"), prefix.clone(), invocation.secrets.clone(), false);
+        let stderr = capture(child.stderr.take().expect("piped stderr"), prefix, invocation.secrets.clone(), true);
+
+        let deadline = invocation.timeout.map(|timeout| Instant::now() + Duration::from_secs(timeout));
+        let (status, timed_out) = wait(&mut child, deadline).map_err(|e| format!("Can't wait for {}: {}", program, e))?;
+
+        let deadline = deadline.map(|deadline| deadline.max(Instant::now()) + CAPTURE_GRACE);
+        Ok(Outcome {
+            status: exit_code(status),
+            stdout: invocation.mask(&stdout.finish(deadline)),
+            stderr: invocation.mask(&stderr.finish(deadline)),
+            timed_out,
+        })
+    }
+
+    fn secret(&mut self, name: &str, source: &Source) -> Result<String, String> {
+        let value = source.load().map_err(|e| format!("Can't load secret {}: {}", name, e))?;
+        self.secrets.push(value.clone());
+        Ok(value)
+    }
+}
+
+/// Records invocations, secrets masked, instead of running them. Each answers with the next outcome stubbed for
+/// its command, the last one repeating, or with a successful run without output when there is none.
+#[derive(Debug, Clone, Default)]
+pub struct DryRun {
+    /// Invocations in the order the program made them
+    pub plan: Vec<Invocation>,
+    stubs: HashMap<String, VecDeque<Outcome>>,
+    /// Values of the secrets loaded, masked in the plan
+    secrets: Vec<String>,
+}
+
+impl DryRun {
+    /// Queues `outcome` as the result of a later call of `command`.
+    pub fn stub(&mut self, command: &str, outcome: Outcome) {
+        self.stubs.entry(command.to_string()).or_default().push_back(outcome);
+    }
+
+    /// Parses `command = status [stdout [stderr]]` lines, the values split like a `run` field, `#` starting a comment.
+    /// Lines naming the same command are its outcomes for successive calls.
+    pub fn parse_stubs(text: &str) -> Result<DryRun, String> {
+        let mut dry_run = DryRun::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (command, values) = match line.split_once('=') {
+                Some((command, values)) if !command.trim().is_empty() => (command.trim(), values),
+                _ => return Err(format!("Expected command = status [stdout [stderr]] at line {}", i + 1)),
+            };
+            let values = words(values).map_err(|e| format!("{} at line {}", e, i + 1))?;
+            let (status, output) = match values.split_first() {
+                Some((status, output)) if output.len() <= 2 => (status, output),
+                _ => return Err(format!("Expected command = status [stdout [stderr]] at line {}", i + 1)),
+            };
+            let status = status.parse().map_err(|_| format!("Expected an integer status for {} at line {}", command, i + 1))?;
+
+            let output = |i: usize| output.get(i).cloned().unwrap_or_default();
+            dry_run.stub(command, Outcome { status, stdout: output(0), stderr: output(1), timed_out: false });
+        }
+
+        Ok(dry_run)
+    }
+}
+
+impl Runner for DryRun {
+    fn run(&mut self, invocation: &Invocation) -> Result<Outcome, String> {
+        self.plan.push(invocation.with_secrets(&self.secrets).masked());
+
+        let outcome = match self.stubs.get_mut(&invocation.command) {
+            Some(outcomes) if outcomes.len() > 1 => outcomes.pop_front(),
+            Some(outcomes) => outcomes.front().cloned(),
+            None => None,
+        };
+        Ok(outcome.unwrap_or_default())
+    }
+
+    /// Secrets that can't be loaded are replaced by a placeholder, a plan doesn't need them.
+    fn secret(&mut self, name: &str, source: &Source) -> Result<String, String> {
+        match source.load() {
+            Ok(value) => {
+                self.secrets.push(value.clone());
+                Ok(value)
+            }
+            Err(_) => Ok(format!("<{}>", name)),
+        }
+    }
+}
+
+/// A plan as text, one numbered block per invocation.
+pub fn plan_text(plan: &[Invocation]) -> String {
+    let mut text = String::new();
+
+    for (i, invocation) in plan.iter().enumerate() {
+        let argv: Vec<String> = invocation.argv.iter().map(|word| quote(word)).collect();
+        text.push_str(&format!("{}. {}\n    run: {}\n", i + 1, invocation.command, argv.join(" ")));
+        if let Some(cwd) = &invocation.cwd {
+            text.push_str(&format!("    cwd: {}\n", cwd));
+        }
+        for (name, value) in &invocation.env {
+            text.push_str(&format!("    env: {}={}\n", name, quote(value)));
+        }
+        if let Some(timeout) = invocation.timeout {
+            text.push_str(&format!("    timeout: {}s\n", timeout));
+        }
+    }
+
+    text
+}
+
+/// A plan as a JSON array with one object per invocation.
+pub fn plan_json(plan: &[Invocation]) -> String {
+    json::array(plan.iter().map(|invocation| {
+        let env: Vec<(&str, String)> = invocation.env.iter().map(|(name, value)| (name.as_str(), json::string(value))).collect();
+        json::object(&[
+            ("command", json::string(&invocation.command)),
+            ("argv", json::array(invocation.argv.iter().map(|word| json::string(word)))),
+            ("cwd", invocation.cwd.as_deref().map_or_else(|| json::NULL.to_string(), json::string)),
+            ("env", json::object(&env)),
+            ("timeout", invocation.timeout.map_or_else(|| json::NULL.to_string(), |timeout| timeout.to_string())),
+        ])
+    }))
+}
+
+/// Output of a stream being read on another thread.
+struct Capture {
+    output: Arc<Mutex<Vec<u8>>>,
+    /// Disconnected once the stream ended
+    done: mpsc::Receiver<()>,
+}
+
+impl Capture {
+    /// The output read until the stream ended, or until `deadline` when it comes first.
+    fn finish(self, deadline: Option<Instant>) -> String {
+        // Nothing is ever sent, both return once the reading thread has ended
+        match deadline {
+            Some(deadline) => drop(self.done.recv_timeout(deadline.saturating_duration_since(Instant::now()))),
+            None => drop(self.done.recv()),
+        }
+        let output = self.output.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        String::from_utf8_lossy(&output).into_owned()
+    }
+}
+
+/// Reads a stream to its end on another thread, echoing each line after `prefix` when there is one, `secrets` masked.
+fn capture<R: Read + Send + 'static>(stream: R, prefix: Option<String>, secrets: Vec<String>, error: bool) -> Capture {
+    let output = Arc::new(Mutex::new(vec![]));
+    let (done, receiver) = mpsc::channel::<()>();
+    let captured = Arc::clone(&output);
+
+    thread::spawn(move || {
+        let _done = done;
+        let mut reader = BufReader::new(stream);
+        let mut echo = prefix.map(|prefix| Echo { prefix, span: secrets.iter().map(|value| value.matches('\n').count()).max().unwrap_or(0), secrets, error, held: String::new() });
+        let mut line = vec![];
+
+        while let Ok(read) = reader.read_until(b'\n', &mut line) {
+            if read == 0 {
+                break;
+            }
+            if let Some(echo) = &mut echo {
+                echo.push(&String::from_utf8_lossy(&line));
+            }
+            captured.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).append(&mut line);
+        }
+        if let Some(echo) = &mut echo {
+            echo.flush();
+        }
+    });
+
+    Capture { output, done: receiver }
+}
+
+/// Echoes the lines of a stream after a prefix, secret values masked. A line is held back while it may start a secret
+/// spanning several lines, until as many lines as the secret has newlines follow it.
+struct Echo {
+    prefix: String,
+    secrets: Vec<String>,
+    /// Most newlines in a secret
+    span: usize,
+    error: bool,
+    /// Lines read but not echoed yet, masked
+    held: String,
+}
+
+impl Echo {
+    fn push(&mut self, line: &str) {
+        self.held.push_str(line);
+        self.held = secret::mask(&self.held, &self.secrets);
+        while self.held.matches('\n').count() > self.span {
+            let end = self.held.find('\n').map_or(self.held.len(), |end| end + 1);
+            let line: String = self.held.drain(..end).collect();
+            self.write(&line);
+        }
+    }
+
+    /// Echoes the lines held back, once the stream ended.
+    fn flush(&mut self) {
+        let held = std::mem::take(&mut self.held);
+        for line in held.split_inclusive('\n') {
+            self.write(line);
+        }
+    }
+
+    fn write(&self, line: &str) {
+        let line = line.trim_end_matches(['\n', '\r']);
+        // Output is best effort, a closed terminal mustn't stop the capture
+        let _ = if self.error { writeln!(io::stderr(), "{}{}", self.prefix, line) } else { writeln!(io::stdout(), "{}{}", self.prefix, line) };
+    }
+}
+
+/// Waits for `child` to exit, killing its process group once `deadline` has passed.
+fn wait(child: &mut Child, deadline: Option<Instant>) -> io::Result<(ExitStatus, bool)> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, false));
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            kill_group(child);
+            return Ok((child.wait()?, true));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Kills the group `child` leads with the `kill` utility, the standard library only killing the child itself, which
+/// remains the fallback.
+#[cfg(unix)]
+fn kill_group(child: &mut Child) {
+    let group = format!("-{}", child.id());
+    let killed = Command::new("kill").args(["-s", "KILL", "--", &group]).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status();
+    if !killed.is_ok_and(|status| status.success()) {
+        let _ = child.kill();
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_group(child: &mut Child) {
+    let _ = child.kill();
+}
+
+fn exit_code(status: ExitStatus) -> i64 {
+    if let Some(code) = status.code() {
+        return code as i64;
+    }
+
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal as i64;
+    }
+
+    -1
+}
+
+/// `word` as `words` would read it back, single-quoted unless it's made of safe characters only.
+pub fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Splits `text` into words at whitespace. Single quotes keep everything up to the next one literally,
+/// double quotes allow `\"` and `\\` escapes, and a backslash outside quotes escapes the next character.
+pub fn words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(word) = word {
+        words.push(word);
+    }
+    Ok(words)
+}
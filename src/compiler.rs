@@ -0,0 +1,574 @@
+use std::collections::HashMap;
+
+use core::fmt::Display;
+
+use crate::ast::{BinaryOp, Block, CommandDecl, ComplexDecl, DomainDecl, DomainKind, Expr, ExprKind, FuncDecl, Item, PostfixOp, Program, Span, Stmt, StmtKind, UnaryOp};
+use crate::bytecode::{Bytecode, Domain, Function, Layout, Op};
+use crate::module::Modules;
+use crate::resolve::{Resolution, SymbolId};
+use crate::stdlib;
+use crate::value::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Compiles a single file to bytecode that behaves like running it with `interpreter::run`.
+pub fn compile(program: &Program, resolution: &Resolution) -> Result<Bytecode, CompileError> {
+    let mut compiler = Compiler::new(resolution);
+    compiler.declare(program);
+    compiler.main_from(program);
+    compiler.compile(&[program])
+}
+
+/// Compiles every loaded file, `resolution` being the result of `resolve_modules` on the same files.
+pub fn compile_modules(modules: &Modules, resolution: &Resolution) -> Result<Bytecode, CompileError> {
+    let mut compiler = Compiler::new(resolution);
+    let programs: Vec<&Program> = modules.order.iter().map(|&file| &modules.files[file].program).collect();
+    for program in &programs {
+        compiler.declare(program);
+    }
+    compiler.main_from(&modules.files[0].program);
+    compiler.compile(&programs)
+}
+
+/// Where the value of a symbol lives at run time.
+#[derive(Debug, Clone, Copy)]
+enum Place {
+    Local(usize),
+    Global(usize),
+    Function(usize),
+    /// Index of the function evaluating the command's fields
+    Command(usize),
+    Domain(usize),
+}
+
+/// Function being compiled.
+#[derive(Default)]
+struct Builder {
+    function: Function,
+    locals: HashMap<SymbolId, usize>,
+    /// Top-level statements declare globals rather than locals
+    script: bool,
+}
+
+struct Compiler<'ast> {
+    resolution: &'ast Resolution,
+    /// Span of a declared name to its symbol
+    declarations: HashMap<Span, SymbolId>,
+    bytecode: Bytecode,
+    places: HashMap<SymbolId, Place>,
+    functions: Vec<&'ast FuncDecl>,
+    commands: Vec<&'ast CommandDecl>,
+    domains: Vec<&'ast DomainDecl>,
+    complexes: HashMap<SymbolId, &'ast ComplexDecl>,
+    builder: Builder,
+}
+
+impl<'ast> Compiler<'ast> {
+    fn new(resolution: &'ast Resolution) -> Self {
+        let declarations = resolution.symbols.iter().enumerate().map(|(id, symbol)| (symbol.span, id)).collect();
+        Compiler {
+            resolution,
+            declarations,
+            bytecode: Bytecode::default(),
+            places: HashMap::new(),
+            functions: vec![],
+            commands: vec![],
+            domains: vec![],
+            complexes: HashMap::new(),
+            builder: Builder::default(),
+        }
+    }
+
+    /// Gives a place to every function, command, domain and global variable of `program`.
+    fn declare(&mut self, program: &'ast Program) {
+        for item in &program.items {
+            match item {
+                Item::Func(func) => {
+                    if let Some(&id) = self.declarations.get(&func.name_span) {
+                        self.places.insert(id, Place::Function(self.functions.len()));
+                        self.functions.push(func);
+                    }
+                }
+                Item::Command(command) => {
+                    if let Some(&id) = self.declarations.get(&command.name_span) {
+                        self.places.insert(id, Place::Command(self.commands.len()));
+                        self.commands.push(command);
+                    }
+                }
+                Item::Domain(domain) => {
+                    if let Some(&id) = self.declarations.get(&domain.name_span) {
+                        self.places.insert(id, Place::Domain(self.domains.len()));
+                        self.domains.push(domain);
+                    }
+                }
+                Item::Complex(complex) => {
+                    if let Some(&id) = self.declarations.get(&complex.name_span) {
+                        self.complexes.insert(id, complex);
+                    }
+                }
+                Item::Stmt(stmt) => self.declare_globals(stmt),
+                Item::Import(_) => {}
+            }
+        }
+    }
+
+    fn main_from(&mut self, program: &Program) {
+        self.bytecode.main = program.items.iter().find_map(|item| match item {
+            Item::Func(func) if func.name == "main" => self.functions.iter().position(|f| std::ptr::eq(*f, func)),
+            _ => None,
+        });
+    }
+
+    /// Every variable declared by a top-level statement is global, even inside a block.
+    fn declare_globals(&mut self, stmt: &'ast Stmt) {
+        match &stmt.kind {
+            StmtKind::VarDecl { name, name_span, .. } => {
+                if let Some(&id) = self.declarations.get(name_span) {
+                    self.places.insert(id, Place::Global(self.bytecode.globals.len()));
+                    self.bytecode.globals.push(name.clone());
+                }
+            }
+            StmtKind::If { then_branch, else_branch, .. } => {
+                then_branch.stmts.iter().for_each(|stmt| self.declare_globals(stmt));
+                if let Some(else_branch) = else_branch {
+                    self.declare_globals(else_branch);
+                }
+            }
+            StmtKind::While { body: block, .. } | StmtKind::Block(block) => block.stmts.iter().for_each(|stmt| self.declare_globals(stmt)),
+            StmtKind::Return(_) | StmtKind::Expr(_) => {}
+        }
+    }
+
+    fn compile(mut self, programs: &[&'ast Program]) -> Result<Bytecode, CompileError> {
+        // Functions come first so that their index is their place, commands, domains and the script follow
+        self.bytecode.functions = vec![Function::default(); self.functions.len()];
+        // Places of commands are made function indexes once functions are known
+        for place in self.places.values_mut() {
+            if let Place::Command(index) = place {
+                *index += self.functions.len();
+            }
+        }
+
+        for index in 0..self.functions.len() {
+            let func = self.functions[index];
+            self.begin(&func.name, false);
+            for param in &func.params {
+                let slot = self.add_local(&param.name);
+                if let Some(&id) = self.declarations.get(&param.name_span) {
+                    self.builder.locals.insert(id, slot);
+                }
+            }
+            self.builder.function.arity = func.params.len();
+
+            self.block(&func.body)?;
+            self.finish_function(func.body.span)?;
+            self.bytecode.functions[index] = std::mem::take(&mut self.builder.function);
+        }
+
+        for index in 0..self.commands.len() {
+            let command = self.commands[index];
+            self.begin(&command.name, false);
+            for param in &command.params {
+                let slot = self.add_local(&param.name);
+                if let Some(&id) = self.declarations.get(&param.name_span) {
+                    self.builder.locals.insert(id, slot);
+                }
+            }
+            self.builder.function.arity = command.params.len();
+
+            for field in &command.fields {
+                self.expr(&field.value)?;
+            }
+            let fields: Vec<String> = command.fields.iter().map(|field| field.name.clone()).collect();
+            let layout = self.add_layout(Layout { name: command.name.clone(), order: (0..fields.len()).collect(), fields }, command.span)?;
+            self.emit(Op::Complex, &[layout], command.span)?;
+            self.emit(Op::Return, &[], command.span)?;
+            self.bytecode.functions.push(std::mem::take(&mut self.builder.function));
+        }
+
+        for index in 0..self.domains.len() {
+            let domain = self.domains[index];
+            let name = format!("{} {}", domain.kind, domain.name);
+            self.begin(&name, false);
+            for field in &domain.fields {
+                self.expr(&field.value)?;
+            }
+            let fields: Vec<String> = domain.fields.iter().map(|field| field.name.clone()).collect();
+            let layout = self.add_layout(Layout { name: domain.kind.keyword().to_string(), order: (0..fields.len()).collect(), fields }, domain.span)?;
+            self.emit(Op::Complex, &[layout], domain.span)?;
+            if domain.kind == DomainKind::Secret {
+                let name = self.add_constant(Value::String(domain.name.clone()), domain.name_span)?;
+                self.emit(Op::LoadSecret, &[name], domain.name_span)?;
+            }
+            self.emit(Op::Return, &[], domain.span)?;
+
+            self.bytecode.domains.push(Domain { name, function: self.bytecode.functions.len() });
+            self.bytecode.functions.push(std::mem::take(&mut self.builder.function));
+        }
+
+        self.begin("<script>", true);
+        for program in programs {
+            for item in &program.items {
+                match item {
+                    Item::Stmt(stmt) => self.stmt(stmt)?,
+                    // Evaluated in order so that their errors show up even if nothing uses them
+                    Item::Domain(domain) => {
+                        if let Some(Place::Domain(index)) = self.place(domain.name_span, true) {
+                            self.emit(Op::GetDomain, &[index], domain.name_span)?;
+                            self.emit(Op::Pop, &[], domain.name_span)?;
+                        }
+                    }
+                    Item::Import(_) | Item::Func(_) | Item::Command(_) | Item::Complex(_) => {}
+                }
+            }
+        }
+        self.finish_function(Span::default())?;
+        self.bytecode.script = self.bytecode.functions.len();
+        self.bytecode.functions.push(std::mem::take(&mut self.builder.function));
+
+        Ok(self.bytecode)
+    }
+
+    fn begin(&mut self, name: &str, script: bool) {
+        self.builder = Builder { function: Function { name: name.to_string(), ..Function::default() }, script, ..Builder::default() };
+    }
+
+    /// Returns void from the end of a function that didn't return before.
+    fn finish_function(&mut self, span: Span) -> Result<(), CompileError> {
+        let void = self.add_constant(Value::Void, span)?;
+        self.emit(Op::Constant, &[void], span)?;
+        self.emit(Op::Return, &[], span)?;
+        Ok(())
+    }
+
+    /// Place of the symbol declared at or referred to from `span`.
+    fn place(&self, span: Span, declaration: bool) -> Option<Place> {
+        let id = if declaration { self.declarations.get(&span) } else { self.resolution.uses.get(&span) }?;
+        match self.builder.locals.get(id) {
+            Some(&slot) => Some(Place::Local(slot)),
+            None => self.places.get(id).copied(),
+        }
+    }
+
+    fn emit(&mut self, op: Op, operands: &[usize], span: Span) -> Result<usize, CompileError> {
+        let chunk = &mut self.builder.function.chunk;
+        let offset = chunk.code.len();
+        if offset + op.size() > u16::MAX as usize {
+            return Err(CompileError { message: format!("{} is too large to compile", self.builder.function.name), span });
+        }
+
+        if chunk.spans.last().map(|&(_, last)| last) != Some(span) {
+            chunk.spans.push((offset, span));
+        }
+        chunk.code.push(op as u8);
+        for (&operand, &size) in operands.iter().zip(op.operands()) {
+            for i in (0..size).rev() {
+                chunk.code.push((operand >> (8 * i)) as u8);
+            }
+        }
+        Ok(offset)
+    }
+
+    /// Points the jump at `offset` to the next instruction.
+    fn patch(&mut self, offset: usize) {
+        let target = self.builder.function.chunk.code.len();
+        let code = &mut self.builder.function.chunk.code;
+        code[offset + 1] = (target >> 8) as u8;
+        code[offset + 2] = target as u8;
+    }
+
+    fn here(&self) -> usize {
+        self.builder.function.chunk.code.len()
+    }
+
+    fn add_constant(&mut self, value: Value, span: Span) -> Result<usize, CompileError> {
+        let constants = &mut self.builder.function.chunk.constants;
+        if let Some(index) = constants.iter().position(|constant| *constant == value) {
+            return Ok(index);
+        }
+        if constants.len() > u16::MAX as usize {
+            return Err(CompileError { message: format!("{} has too many constants", self.builder.function.name), span });
+        }
+        constants.push(value);
+        Ok(constants.len() - 1)
+    }
+
+    fn add_local(&mut self, name: &str) -> usize {
+        self.builder.function.locals.push(name.to_string());
+        self.builder.function.locals.len() - 1
+    }
+
+    fn add_layout(&mut self, layout: Layout, span: Span) -> Result<usize, CompileError> {
+        if self.bytecode.layouts.len() > u16::MAX as usize {
+            return Err(CompileError { message: "Too many complex values to compile".to_string(), span });
+        }
+        self.bytecode.layouts.push(layout);
+        Ok(self.bytecode.layouts.len() - 1)
+    }
+
+    fn fail(&mut self, message: &str, span: Span) -> Result<(), CompileError> {
+        let message = self.add_constant(Value::String(message.to_string()), span)?;
+        self.emit(Op::Fail, &[message], span)?;
+        Ok(())
+    }
+
+    fn block(&mut self, block: &'ast Block) -> Result<(), CompileError> {
+        for stmt in &block.stmts {
+            self.stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &'ast Stmt) -> Result<(), CompileError> {
+        match &stmt.kind {
+            StmtKind::VarDecl { name, name_span, value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value)?;
+                }
+
+                let place = match self.declarations.get(name_span) {
+                    Some(&id) if self.builder.script => self.places.get(&id).copied(),
+                    Some(&id) => {
+                        let slot = self.add_local(name);
+                        self.builder.locals.insert(id, slot);
+                        Some(Place::Local(slot))
+                    }
+                    None => None,
+                };
+
+                match (place, value.is_some()) {
+                    (Some(Place::Global(slot)), true) => self.emit(Op::DefineGlobal, &[slot], stmt.span)?,
+                    (Some(Place::Global(slot)), false) => self.emit(Op::DeclareGlobal, &[slot], stmt.span)?,
+                    (Some(Place::Local(slot)), true) => {
+                        self.emit(Op::SetLocal, &[slot], stmt.span)?;
+                        self.emit(Op::Pop, &[], stmt.span)?
+                    }
+                    (Some(Place::Local(slot)), false) => self.emit(Op::UnsetLocal, &[slot], stmt.span)?,
+                    (_, true) => self.emit(Op::Pop, &[], stmt.span)?,
+                    (_, false) => 0,
+                };
+            }
+            StmtKind::If { condition, then_branch, else_branch } => {
+                self.expr(condition)?;
+                let to_else = self.emit(Op::JumpIfFalse, &[0], condition.span)?;
+                self.block(then_branch)?;
+                match else_branch {
+                    Some(else_branch) => {
+                        let to_end = self.emit(Op::Jump, &[0], stmt.span)?;
+                        self.patch(to_else);
+                        self.stmt(else_branch)?;
+                        self.patch(to_end);
+                    }
+                    None => self.patch(to_else),
+                }
+            }
+            StmtKind::While { condition, body } => {
+                let start = self.here();
+                self.expr(condition)?;
+                let to_end = self.emit(Op::JumpIfFalse, &[0], condition.span)?;
+                self.block(body)?;
+                self.emit(Op::Jump, &[start], stmt.span)?;
+                self.patch(to_end);
+            }
+            StmtKind::Return(value) => {
+                match value {
+                    Some(value) => self.expr(value)?,
+                    None => {
+                        let void = self.add_constant(Value::Void, stmt.span)?;
+                        self.emit(Op::Constant, &[void], stmt.span)?;
+                    }
+                }
+                if self.builder.script {
+                    self.fail("return is only allowed inside a function", stmt.span)?;
+                } else {
+                    self.emit(Op::Return, &[], stmt.span)?;
+                }
+            }
+            StmtKind::Block(block) => self.block(block)?,
+            StmtKind::Expr(expr) => {
+                self.expr(expr)?;
+                self.emit(Op::Pop, &[], stmt.span)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &'ast Expr) -> Result<(), CompileError> {
+        match &expr.kind {
+            ExprKind::Integer(value) => self.constant(Value::Integer(*value), expr.span)?,
+            ExprKind::String(value) => self.constant(Value::String(value.clone()), expr.span)?,
+            ExprKind::Rune(value) => self.constant(Value::Rune(*value), expr.span)?,
+            ExprKind::Boolean(value) => self.constant(Value::Boolean(*value), expr.span)?,
+            ExprKind::Identifier(name) => match self.place(expr.span, false) {
+                Some(Place::Local(slot)) => {
+                    self.emit(Op::GetLocal, &[slot], expr.span)?;
+                }
+                Some(Place::Global(slot)) => {
+                    self.emit(Op::GetGlobal, &[slot], expr.span)?;
+                }
+                Some(Place::Domain(index)) => {
+                    self.emit(Op::GetDomain, &[index], expr.span)?;
+                }
+                Some(Place::Function(_)) => self.fail(&format!("Function {} can only be called", name), expr.span)?,
+                Some(Place::Command(_)) => self.fail(&format!("Command {} can only be called", name), expr.span)?,
+                None if !self.resolution.uses.contains_key(&expr.span) && stdlib::get(name).is_some() => {
+                    self.fail(&format!("Builtin {} can only be called", name), expr.span)?
+                }
+                None if self.resolution.uses.contains_key(&expr.span) => self.fail(&format!("{} is read before it's declared", name), expr.span)?,
+                None => self.fail(&format!("Undefined name {}", name), expr.span)?,
+            },
+            ExprKind::Unary { op, operand } => {
+                self.expr(operand)?;
+                let op = match op {
+                    UnaryOp::Not => Op::Not,
+                    UnaryOp::Negate => Op::Negate,
+                };
+                self.emit(op, &[], expr.span)?;
+            }
+            ExprKind::Postfix { op, operand } => {
+                self.expr(operand)?;
+                self.emit(Op::Dup, &[], expr.span)?;
+                let op = match op {
+                    PostfixOp::Increment => Op::Increment,
+                    PostfixOp::Decrement => Op::Decrement,
+                };
+                self.emit(op, &[], expr.span)?;
+                self.store(operand)?;
+                self.emit(Op::Pop, &[], expr.span)?;
+            }
+            ExprKind::Binary { op: op @ (BinaryOp::And | BinaryOp::Or), left, right } => {
+                self.expr(left)?;
+                let jump = if *op == BinaryOp::And { Op::JumpIfFalseKeep } else { Op::JumpIfTrueKeep };
+                let to_end = self.emit(jump, &[0], expr.span)?;
+                self.expr(right)?;
+                self.emit(Op::from_binary(*op), &[], expr.span)?;
+                self.patch(to_end);
+            }
+            ExprKind::Binary { op, left, right } => {
+                self.expr(left)?;
+                self.expr(right)?;
+                self.emit(Op::from_binary(*op), &[], expr.span)?;
+            }
+            ExprKind::Assign { target, value } => {
+                self.expr(value)?;
+                self.store(target)?;
+            }
+            ExprKind::Call { callee, args } => {
+                let (op, function) = match &callee.kind {
+                    ExprKind::Identifier(name) => match self.place(callee.span, false) {
+                        Some(Place::Function(index)) => (Op::Call, index),
+                        Some(Place::Command(index)) => (Op::Exec, index),
+                        None if !self.resolution.uses.contains_key(&callee.span) && stdlib::get(name).is_some() => {
+                            (Op::Builtin, stdlib::BUILTINS.iter().position(|builtin| builtin.name == name).expect("builtin"))
+                        }
+                        _ => return self.fail(&format!("{} is not a function", name), callee.span),
+                    },
+                    _ => return self.fail("Only functions can be called", callee.span),
+                };
+
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                if args.len() > u8::MAX as usize {
+                    return Err(CompileError { message: format!("Calls can't have more than {} arguments", u8::MAX), span: expr.span });
+                }
+                self.emit(op, &[function, args.len()], expr.span)?;
+            }
+            ExprKind::Field { object, field, field_span } => {
+                self.expr(object)?;
+                let name = self.add_constant(Value::String(field.clone()), *field_span)?;
+                self.emit(Op::GetField, &[name], *field_span)?;
+            }
+            ExprKind::ComplexLiteral { name, name_span, fields } => {
+                for field in fields {
+                    self.expr(&field.value)?;
+                }
+
+                // Fields are kept in declaration order so that equal values compare equal
+                let mut names: Vec<String> = fields.iter().map(|field| field.name.clone()).collect();
+                if let Some(decl) = self.resolution.uses.get(name_span).and_then(|id| self.complexes.get(id)) {
+                    names.sort_by_key(|field| decl.fields.iter().position(|f| &f.name == field));
+                }
+                let order = fields.iter().map(|field| names.iter().position(|name| *name == field.name).unwrap_or(0)).collect();
+
+                let layout = self.add_layout(Layout { name: name.clone(), fields: names, order }, expr.span)?;
+                self.emit(Op::Complex, &[layout], expr.span)?;
+            }
+            ExprKind::List(elements) => {
+                for element in elements {
+                    self.expr(element)?;
+                }
+                self.collection(Op::List, elements.len(), expr.span)?;
+            }
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.expr(key)?;
+                    self.expr(value)?;
+                }
+                self.collection(Op::Map, entries.len(), expr.span)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn collection(&mut self, op: Op, count: usize, span: Span) -> Result<(), CompileError> {
+        if count > u16::MAX as usize {
+            return Err(CompileError { message: format!("Literals can't have more than {} elements", u16::MAX), span });
+        }
+        self.emit(op, &[count], span)?;
+        Ok(())
+    }
+
+    fn constant(&mut self, value: Value, span: Span) -> Result<(), CompileError> {
+        let index = self.add_constant(value, span)?;
+        self.emit(Op::Constant, &[index], span)?;
+        Ok(())
+    }
+
+    /// Stores the value on top of the stack into `target`, leaving it on the stack.
+    fn store(&mut self, target: &'ast Expr) -> Result<(), CompileError> {
+        let mut path = vec![];
+        let mut root = target;
+        while let ExprKind::Field { object, field, field_span } = &root.kind {
+            path.push((field.clone(), *field_span));
+            root = object;
+        }
+        path.reverse();
+
+        let name = match &root.kind {
+            ExprKind::Identifier(name) => name,
+            _ => return self.fail("Only variables and their fields can be assigned to", target.span),
+        };
+
+        let (slot, local) = match self.place(root.span, false) {
+            Some(Place::Local(slot)) => (slot, true),
+            Some(Place::Global(slot)) => (slot, false),
+            Some(Place::Function(_)) | Some(Place::Command(_)) | Some(Place::Domain(_)) => return self.fail(&format!("{} can't be assigned to", name), root.span),
+            None if self.resolution.uses.contains_key(&root.span) => return self.fail(&format!("{} is assigned before it's declared", name), root.span),
+            None => return self.fail(&format!("{} can't be assigned to", name), root.span),
+        };
+
+        if path.is_empty() {
+            let op = if local { Op::SetLocal } else { Op::SetGlobal };
+            self.emit(op, &[slot], root.span)?;
+        } else {
+            if self.bytecode.paths.len() > u16::MAX as usize {
+                return Err(CompileError { message: "Too many field assignments to compile".to_string(), span: target.span });
+            }
+            self.bytecode.paths.push(path);
+            let op = if local { Op::SetLocalField } else { Op::SetGlobalField };
+            self.emit(op, &[slot, self.bytecode.paths.len() - 1], root.span)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::{HashMap, HashSet};
+
+use core::fmt::Display;
+
+use crate::ast::{BinaryOp, Expr, ExprKind, Item, Program, Span, Stmt, StmtKind, Type, UnaryOp};
+use crate::checker;
+use crate::module::Modules;
+use crate::resolve::Resolution;
+use crate::value::Value;
+use crate::visit::{self, Visitor};
+
+/// Value of an expression known at compile time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Integer(i64),
+    String(String),
+    Rune(char),
+    Boolean(bool),
+}
+
+impl Constant {
+    pub fn value_type(&self) -> Type {
+        match self {
+            Constant::Integer(_) => Type::Integer,
+            Constant::String(_) => Type::String,
+            Constant::Rune(_) => Type::Rune,
+            Constant::Boolean(_) => Type::Boolean,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            Constant::Integer(value) => Value::Integer(*value),
+            Constant::String(value) => Value::String(value.clone()),
+            Constant::Rune(value) => Value::Rune(*value),
+            Constant::Boolean(value) => Value::Boolean(*value),
+        }
+    }
+
+    /// The constant of an integer, string, rune or boolean value.
+    pub fn from_value(value: &Value) -> Option<Constant> {
+        match value {
+            Value::Integer(value) => Some(Constant::Integer(*value)),
+            Value::String(value) => Some(Constant::String(value.clone())),
+            Value::Rune(value) => Some(Constant::Rune(*value)),
+            Value::Boolean(value) => Some(Constant::Boolean(*value)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Constant::Integer(value) => write!(f, "{}", value),
+            Constant::String(value) => write!(f, "\"{}\"", value),
+            Constant::Rune(value) => write!(f, "'{}'", value),
+            Constant::Boolean(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for ConstError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Folded values of every expression that can be computed at compile time.
+#[derive(Debug, Default)]
+pub struct Constants {
+    /// Span of an expression to its value
+    pub values: HashMap<Span, Constant>,
+    pub errors: Vec<ConstError>,
+}
+
+impl Constants {
+    /// Value of `expr`, if it's known at compile time.
+    pub fn value(&self, expr: &Expr) -> Option<&Constant> {
+        self.values.get(&expr.span)
+    }
+}
+
+/// Folds the literals of a single file.
+///
+/// Variables initialized with a constant and never assigned to afterwards are constants themselves.
+/// Division by zero and integer overflow in folded expressions are reported as errors.
+pub fn fold(program: &Program, resolution: &Resolution) -> Constants {
+    let mut folder = Folder::new(resolution);
+    folder.fold(program);
+    folder.finish()
+}
+
+/// Folds every loaded file, `resolution` being the result of `resolve_modules` on the same files.
+pub fn fold_modules(modules: &Modules, resolution: &Resolution) -> Constants {
+    let mut folder = Folder::new(resolution);
+    for &file in &modules.order {
+        folder.fold(&modules.files[file].program);
+    }
+    folder.finish()
+}
+
+/// Value of `left op right`, `Ok(None)` when the operator doesn't apply to these constants.
+/// Computed like at runtime, see `value`.
+pub fn binary(op: BinaryOp, left: &Constant, right: &Constant) -> Result<Option<Constant>, String> {
+    if checker::binary_type(op, &left.value_type(), &right.value_type()).is_none() {
+        return Ok(None);
+    }
+    left.to_value().binary(op, &right.to_value()).map(|value| Constant::from_value(&value))
+}
+
+/// Value of `op operand`, `Ok(None)` when the operator doesn't apply to the constant.
+pub fn unary(op: UnaryOp, operand: &Constant) -> Result<Option<Constant>, String> {
+    if checker::unary_type(op, &operand.value_type()).is_none() {
+        return Ok(None);
+    }
+    operand.to_value().unary(op).map(|value| Constant::from_value(&value))
+}
+
+struct Folder<'r> {
+    resolution: &'r Resolution,
+    /// Declarations of variables that are assigned to after their declaration
+    mutated: HashSet<Span>,
+    /// Declarations of constant variables to their value
+    variables: HashMap<Span, Constant>,
+    values: HashMap<Span, Constant>,
+    errors: Vec<ConstError>,
+}
+
+impl<'r> Folder<'r> {
+    fn new(resolution: &'r Resolution) -> Self {
+        Folder { resolution, mutated: HashSet::new(), variables: HashMap::new(), values: HashMap::new(), errors: vec![] }
+    }
+
+    fn finish(mut self) -> Constants {
+        self.errors.sort_by_key(|e| (e.span.file, e.span.line, e.span.column));
+        Constants { values: self.values, errors: self.errors }
+    }
+
+    fn fold(&mut self, program: &Program) {
+        let mut mutations = Mutations { resolution: self.resolution, mutated: &mut self.mutated };
+        mutations.visit_program(program);
+        self.visit_program(program);
+    }
+
+    fn value(&self, expr: &Expr) -> Option<&Constant> {
+        self.values.get(&expr.span)
+    }
+
+    fn evaluate(&self, expr: &Expr) -> Result<Option<Constant>, String> {
+        match &expr.kind {
+            ExprKind::Integer(value) => Ok(Some(Constant::Integer(*value))),
+            ExprKind::String(value) => Ok(Some(Constant::String(value.clone()))),
+            ExprKind::Rune(value) => Ok(Some(Constant::Rune(*value))),
+            ExprKind::Boolean(value) => Ok(Some(Constant::Boolean(*value))),
+            ExprKind::Identifier(_) => {
+                let declaration = self.resolution.definition(expr.span).map(|symbol| symbol.span);
+                Ok(declaration.and_then(|span| self.variables.get(&span)).cloned())
+            }
+            ExprKind::Unary { op, operand } => match self.value(operand) {
+                Some(operand) => unary(*op, operand),
+                None => Ok(None),
+            },
+            ExprKind::Binary { op, left, right } => match (self.value(left), self.value(right)) {
+                (Some(left), Some(right)) => binary(*op, left, right),
+                (_, Some(Constant::Integer(0))) if *op == BinaryOp::Div => Err("Division by zero".to_string()),
+                // `false && x` and `true || x` are known without knowing `x`
+                (Some(Constant::Boolean(false)), None) if *op == BinaryOp::And => Ok(Some(Constant::Boolean(false))),
+                (Some(Constant::Boolean(true)), None) if *op == BinaryOp::Or => Ok(Some(Constant::Boolean(true))),
+                _ => Ok(None),
+            },
+            ExprKind::Postfix { .. } | ExprKind::Assign { .. } | ExprKind::Call { .. } | ExprKind::Field { .. } | ExprKind::ComplexLiteral { .. } | ExprKind::List(_) | ExprKind::Map(_) => {
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for Folder<'_> {
+    /// Like name resolution, function and command bodies come last so that they see every top-level constant.
+    fn visit_program(&mut self, program: &'ast Program) {
+        for item in &program.items {
+            if !matches!(item, Item::Func(_) | Item::Command(_)) {
+                self.visit_item(item);
+            }
+        }
+
+        for item in &program.items {
+            if matches!(item, Item::Func(_) | Item::Command(_)) {
+                self.visit_item(item);
+            }
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        visit::walk_stmt(self, stmt);
+
+        if let StmtKind::VarDecl { name_span, value: Some(value), .. } = &stmt.kind {
+            if let Some(constant) = self.value(value) {
+                if !self.mutated.contains(name_span) {
+                    self.variables.insert(*name_span, constant.clone());
+                }
+            }
+        }
+    }
+
+    /// Operands are folded before the expression using them.
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        visit::walk_expr(self, expr);
+
+        match self.evaluate(expr) {
+            Ok(Some(constant)) => {
+                self.values.insert(expr.span, constant);
+            }
+            Ok(None) => {}
+            Err(message) => self.errors.push(ConstError { message, span: expr.span }),
+        }
+    }
+}
+
+/// Collects the declarations of variables that are assigned to or incremented.
+struct Mutations<'a> {
+    resolution: &'a Resolution,
+    mutated: &'a mut HashSet<Span>,
+}
+
+impl<'ast> Visitor<'ast> for Mutations<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        let target = match &expr.kind {
+            ExprKind::Assign { target, .. } => Some(target),
+            ExprKind::Postfix { operand, .. } => Some(operand),
+            _ => None,
+        };
+
+        if let Some(target) = target {
+            if let Some(symbol) = self.resolution.definition(target.span) {
+                self.mutated.insert(symbol.span);
+            }
+        }
+
+        visit::walk_expr(self, expr)
+    }
+}
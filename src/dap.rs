@@ -0,0 +1,480 @@
+//! Debug adapter protocol server, for editors to run a program under the debugger of `debug`.
+//!
+//! Requests are handled one at a time in the order they come. The program runs on the same thread once it's both
+//! launched and configured, requests being read again whenever it stops, so a running program can't be paused.
+//! Lines and columns are 1-based. Command output is captured and sent as output events, stdout being the protocol.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::ast::Span;
+use crate::checker;
+use crate::command::{self, Invocation, Outcome, Runner};
+use crate::debug::{self, Hook, Reason, Resume, StackFrame, Stepper};
+use crate::diagnostic::Diagnostic;
+use crate::interpreter;
+use crate::json::{self, Json};
+use crate::module::{self, Modules};
+use crate::parser;
+use crate::resolve;
+use crate::secret::Source;
+use crate::value::Value;
+
+/// The program has a single thread, reported under this id.
+const THREAD: i64 = 1;
+
+/// Serves requests read from `input` until the client disconnects or closes it.
+pub fn serve(input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<()> {
+    let connection = RefCell::new(Connection { input, output, seq: 0 });
+    let mut breakpoints: HashMap<PathBuf, BTreeSet<usize>> = HashMap::new();
+    let mut launch: Option<Launch> = None;
+    let mut configured = false;
+    let mut ran = false;
+
+    loop {
+        let request = match connection.borrow_mut().read()? {
+            Some(request) => request,
+            None => return Ok(()),
+        };
+
+        let mut client = connection.borrow_mut();
+        match request.command.as_str() {
+            "initialize" => {
+                let capabilities = json::object(&[("supportsConfigurationDoneRequest", "true".to_string()), ("supportsTerminateRequest", "true".to_string())]);
+                client.respond(&request, Some(capabilities))?;
+                client.event("initialized", None)?;
+            }
+            "launch" => match Launch::load(&request.arguments) {
+                Ok(loaded) => {
+                    launch = Some(loaded);
+                    client.respond(&request, None)?;
+                }
+                Err(message) => client.fail(&request, &message)?,
+            },
+            "setBreakpoints" => {
+                let (path, lines) = set_breakpoints(&mut client, &request)?;
+                breakpoints.insert(path, lines);
+            }
+            "setExceptionBreakpoints" => client.respond(&request, None)?,
+            "configurationDone" => {
+                configured = true;
+                client.respond(&request, None)?;
+            }
+            "threads" => client.respond(&request, Some(threads()))?,
+            "disconnect" => return client.respond(&request, None),
+            "terminate" => {
+                client.respond(&request, None)?;
+                client.event("terminated", None)?;
+            }
+            command => client.fail(&request, &format!("Can't handle {} while the program isn't stopped", command))?,
+        }
+        drop(client);
+
+        if let (Some(launch), true, false) = (&launch, configured, ran) {
+            ran = true;
+            if run(&connection, launch, &mut breakpoints)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A request of the client.
+struct Request {
+    seq: i64,
+    command: String,
+    arguments: Json,
+}
+
+impl Request {
+    fn argument(&self, key: &str) -> Option<&Json> {
+        self.arguments.get(key)
+    }
+}
+
+/// Messages framed by a `Content-Length` header.
+struct Connection<'io> {
+    input: &'io mut dyn BufRead,
+    output: &'io mut dyn Write,
+    /// Sequence number of the last message sent
+    seq: i64,
+}
+
+impl Connection<'_> {
+    /// Reads the next request, `None` once the input is closed. Messages that aren't requests are skipped.
+    fn read(&mut self) -> io::Result<Option<Request>> {
+        loop {
+            let mut length = None;
+            loop {
+                let mut header = String::new();
+                if self.input.read_line(&mut header)? == 0 {
+                    return Ok(None);
+                }
+                let header = header.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.trim().eq_ignore_ascii_case("Content-Length") {
+                        length = value.trim().parse::<usize>().ok();
+                    }
+                }
+            }
+
+            let length = length.ok_or_else(|| invalid("Message without a Content-Length header".to_string()))?;
+            let mut body = vec![0; length];
+            self.input.read_exact(&mut body)?;
+            let message = json::parse(&String::from_utf8_lossy(&body)).map_err(invalid)?;
+
+            if message.get("type").and_then(Json::as_str) != Some("request") {
+                continue;
+            }
+            let seq = message.get("seq").and_then(Json::as_i64).unwrap_or(0);
+            let command = message.get("command").and_then(Json::as_str).unwrap_or("").to_string();
+            let arguments = message.get("arguments").cloned().unwrap_or(Json::Object(vec![]));
+            return Ok(Some(Request { seq, command, arguments }));
+        }
+    }
+
+    /// Sends a message given its fields other than `seq`.
+    fn send(&mut self, fields: &[(&str, String)]) -> io::Result<()> {
+        self.seq += 1;
+        let mut message = vec![("seq", self.seq.to_string())];
+        message.extend(fields.iter().cloned());
+        let body = json::object(&message);
+        write!(self.output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.output.flush()
+    }
+
+    fn respond(&mut self, request: &Request, body: Option<String>) -> io::Result<()> {
+        let mut fields = vec![
+            ("type", json::string("response")),
+            ("request_seq", request.seq.to_string()),
+            ("success", "true".to_string()),
+            ("command", json::string(&request.command)),
+        ];
+        if let Some(body) = body {
+            fields.push(("body", body));
+        }
+        self.send(&fields)
+    }
+
+    fn fail(&mut self, request: &Request, message: &str) -> io::Result<()> {
+        self.send(&[
+            ("type", json::string("response")),
+            ("request_seq", request.seq.to_string()),
+            ("success", "false".to_string()),
+            ("command", json::string(&request.command)),
+            ("message", json::string(message)),
+        ])
+    }
+
+    fn event(&mut self, event: &str, body: Option<String>) -> io::Result<()> {
+        let mut fields = vec![("type", json::string("event")), ("event", json::string(event))];
+        if let Some(body) = body {
+            fields.push(("body", body));
+        }
+        self.send(&fields)
+    }
+
+    fn output(&mut self, category: &str, text: &str) -> io::Result<()> {
+        self.event("output", Some(json::object(&[("category", json::string(category)), ("output", json::string(text))])))
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn threads() -> String {
+    json::object(&[("threads", json::array(vec![json::object(&[("id", THREAD.to_string()), ("name", json::string("main"))])]))])
+}
+
+/// Path under which breakpoints and loaded files are matched.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Answers a `setBreakpoints` request, each breakpoint moving to the first line at or after it where a statement
+/// starts. Returns the file and the lines of the breakpoints that could be placed.
+fn set_breakpoints(connection: &mut Connection, request: &Request) -> io::Result<(PathBuf, BTreeSet<usize>)> {
+    let path = request.argument("source").and_then(|source| source.get("path")).and_then(Json::as_str).unwrap_or("");
+    let requested: Vec<i64> =
+        request.argument("breakpoints").and_then(Json::as_array).unwrap_or(&[]).iter().filter_map(|breakpoint| breakpoint.get("line").and_then(Json::as_i64)).collect();
+
+    let statements = fs::read_to_string(path).ok().and_then(|source| parser::parse(&source).ok()).map(|program| debug::statement_lines(&program)).unwrap_or_default();
+    let mut lines = BTreeSet::new();
+    let breakpoints = requested.iter().map(|&line| match statements.range(line.max(1) as usize..).next() {
+        Some(&actual) => {
+            lines.insert(actual);
+            json::object(&[("verified", "true".to_string()), ("line", actual.to_string())])
+        }
+        None => json::object(&[("verified", "false".to_string()), ("line", line.to_string()), ("message", json::string("No statement at or after this line"))]),
+    });
+    let body = json::object(&[("breakpoints", json::array(breakpoints.collect::<Vec<String>>()))]);
+
+    connection.respond(request, Some(body))?;
+    Ok((canonical(Path::new(path)), lines))
+}
+
+/// A program to run, from the arguments of a `launch` request.
+struct Launch {
+    modules: Modules,
+    stop_on_entry: bool,
+    /// Run without stopping
+    no_debug: bool,
+}
+
+impl Launch {
+    /// Loads the program, failing with its name and type errors.
+    fn load(arguments: &Json) -> Result<Launch, String> {
+        let program = arguments.get("program").and_then(Json::as_str).ok_or("Launch expects the path of a program")?;
+        let modules = module::load(Path::new(program)).map_err(|(_, errors)| errors.iter().map(ToString::to_string).collect::<Vec<String>>().join("\n"))?;
+
+        let mut errors: Vec<String> = resolve::resolve_modules(&modules).errors.iter().filter(|error| !error.warning).map(ToString::to_string).collect();
+        errors.extend(checker::infer_modules(&modules).errors.iter().map(ToString::to_string));
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
+
+        Ok(Launch {
+            modules,
+            stop_on_entry: arguments.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false),
+            no_debug: arguments.get("noDebug").and_then(Json::as_bool).unwrap_or(false),
+        })
+    }
+}
+
+/// Runs the launched program to its end, returning whether the client disconnected meanwhile.
+fn run(connection: &RefCell<Connection>, launch: &Launch, breakpoints: &mut HashMap<PathBuf, BTreeSet<usize>>) -> io::Result<bool> {
+    let modules = &launch.modules;
+    let resolution = resolve::resolve_modules(modules);
+    let mut debuggee = Debuggee { connection, modules, breakpoints, stepper: Stepper::new(launch.stop_on_entry), variables: vec![], ended: false, disconnected: false, error: None };
+    for file in 0..modules.files.len() {
+        debuggee.place_breakpoints(file);
+    }
+
+    let mut runner = Captured { connection, local: command::Local::quiet() };
+    let result = if launch.no_debug {
+        interpreter::run_modules_with(modules, &resolution, &mut runner)
+    } else {
+        interpreter::debug_modules_with(modules, &resolution, &mut runner, &mut debuggee)
+    };
+    if let Some(error) = debuggee.error {
+        return Err(error);
+    }
+
+    let mut connection = connection.borrow_mut();
+    let exit_code = match result {
+        Ok(Value::Void) => 0,
+        Ok(value) => {
+            connection.output("stdout", &format!("{}\n", value))?;
+            0
+        }
+        Err(_) if debuggee.disconnected => return Ok(true),
+        Err(_) if debuggee.ended => {
+            connection.event("terminated", None)?;
+            return Ok(false);
+        }
+        Err(error) => {
+            connection.output("stderr", &format!("{}\n", Diagnostic::from(&error).render(modules, false)))?;
+            1
+        }
+    };
+    connection.event("exited", Some(json::object(&[("exitCode", exit_code.to_string())])))?;
+    connection.event("terminated", None)?;
+    Ok(false)
+}
+
+/// Answers requests while the program is stopped.
+struct Debuggee<'c, 'io, 'm> {
+    connection: &'c RefCell<Connection<'io>>,
+    modules: &'m Modules,
+    breakpoints: &'m mut HashMap<PathBuf, BTreeSet<usize>>,
+    stepper: Stepper,
+    /// Variables shown since the last stop, a reference being an index plus one
+    variables: Vec<Vec<(String, Option<Value>)>>,
+    /// The client ended the program
+    ended: bool,
+    /// The client is gone
+    disconnected: bool,
+    /// Failure to talk to the client, which ends the program
+    error: Option<io::Error>,
+}
+
+impl Debuggee<'_, '_, '_> {
+    fn place_breakpoints(&mut self, file: usize) {
+        let lines = self.breakpoints.get(&canonical(self.modules.path(file))).cloned().unwrap_or_default();
+        self.stepper.set_breakpoints(file, lines);
+    }
+
+    /// Handles requests until one resumes or ends the program.
+    fn serve_stopped(&mut self, stack: &[StackFrame]) -> io::Result<Option<Resume>> {
+        let connection = self.connection;
+        loop {
+            let request = match connection.borrow_mut().read()? {
+                Some(request) => request,
+                None => {
+                    self.ended = true;
+                    self.disconnected = true;
+                    return Ok(None);
+                }
+            };
+
+            let mut connection = connection.borrow_mut();
+            let resume = match request.command.as_str() {
+                "continue" => Resume::Continue,
+                "next" => Resume::StepOver,
+                "stepIn" => Resume::StepIn,
+                "stepOut" => Resume::StepOut,
+                "disconnect" | "terminate" => {
+                    self.ended = true;
+                    self.disconnected = request.command == "disconnect";
+                    connection.respond(&request, None)?;
+                    return Ok(None);
+                }
+                "threads" => {
+                    connection.respond(&request, Some(threads()))?;
+                    continue;
+                }
+                "stackTrace" => {
+                    connection.respond(&request, Some(self.stack_trace(stack)))?;
+                    continue;
+                }
+                "scopes" => {
+                    let frame = request.argument("frameId").and_then(Json::as_i64).unwrap_or(0) as usize;
+                    let scope = |name: &str, reference: usize| json::object(&[("name", json::string(name)), ("variablesReference", reference.to_string()), ("expensive", "false".to_string())]);
+                    // Frame `i` has its locals at reference `i + 1`, the globals follow the frames
+                    let scopes = if frame + 1 < stack.len() { vec![scope("Locals", frame + 1), scope("Globals", stack.len())] } else { vec![scope("Globals", stack.len())] };
+                    connection.respond(&request, Some(json::object(&[("scopes", json::array(scopes))])))?;
+                    continue;
+                }
+                "variables" => {
+                    let reference = request.argument("variablesReference").and_then(Json::as_i64).unwrap_or(0) as usize;
+                    match self.variables.get(reference.wrapping_sub(1)).cloned() {
+                        Some(variables) => {
+                            let variables: Vec<String> = variables.iter().map(|(name, value)| self.variable(name, value.as_ref())).collect();
+                            connection.respond(&request, Some(json::object(&[("variables", json::array(variables))])))?;
+                        }
+                        None => connection.fail(&request, &format!("No variables with reference {}", reference))?,
+                    }
+                    continue;
+                }
+                "setBreakpoints" => {
+                    let (path, lines) = set_breakpoints(&mut connection, &request)?;
+                    self.breakpoints.insert(path, lines);
+                    for file in 0..self.modules.files.len() {
+                        self.place_breakpoints(file);
+                    }
+                    continue;
+                }
+                "pause" | "setExceptionBreakpoints" => {
+                    connection.respond(&request, None)?;
+                    continue;
+                }
+                command => {
+                    connection.fail(&request, &format!("Can't handle {} while the program is stopped", command))?;
+                    continue;
+                }
+            };
+
+            let body = if resume == Resume::Continue { Some(json::object(&[("allThreadsContinued", "true".to_string())])) } else { None };
+            connection.respond(&request, body)?;
+            return Ok(Some(resume));
+        }
+    }
+
+    fn stack_trace(&self, stack: &[StackFrame]) -> String {
+        let frames: Vec<String> = stack
+            .iter()
+            .enumerate()
+            .map(|(id, frame)| {
+                let path = canonical(self.modules.path(frame.span.file));
+                let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                let source = json::object(&[("name", json::string(&name)), ("path", json::string(&path.display().to_string()))]);
+                json::object(&[
+                    ("id", id.to_string()),
+                    ("name", json::string(&frame.name)),
+                    ("source", source),
+                    ("line", frame.span.line.to_string()),
+                    ("column", frame.span.column.to_string()),
+                ])
+            })
+            .collect();
+        json::object(&[("stackFrames", json::array(frames)), ("totalFrames", stack.len().to_string())])
+    }
+
+    /// A variable of a `variables` response, its elements or fields shown under a new reference.
+    fn variable(&mut self, name: &str, value: Option<&Value>) -> String {
+        let children: Vec<(String, Option<Value>)> = match value {
+            Some(Value::Complex { fields, .. }) => fields.iter().map(|(field, value)| (field.clone(), Some(value.clone()))).collect(),
+            Some(Value::List(elements)) => elements.iter().enumerate().map(|(i, element)| (format!("[{}]", i), Some(element.clone()))).collect(),
+            Some(Value::Map(entries)) => entries.iter().map(|(key, value)| (key.quoted(), Some(value.clone()))).collect(),
+            _ => vec![],
+        };
+        let reference = if children.is_empty() {
+            0
+        } else {
+            self.variables.push(children);
+            self.variables.len()
+        };
+
+        json::object(&[
+            ("name", json::string(name)),
+            ("value", json::string(&value.map_or_else(|| "<no value>".to_string(), Value::quoted))),
+            ("type", json::string(value.map_or("", Value::type_name))),
+            ("variablesReference", reference.to_string()),
+        ])
+    }
+}
+
+impl Hook for Debuggee<'_, '_, '_> {
+    fn should_stop(&mut self, span: Span, depth: usize) -> Option<Reason> {
+        self.stepper.should_stop(span, depth)
+    }
+
+    fn stopped(&mut self, reason: Reason, stack: &[StackFrame], globals: &[(String, Option<Value>)]) -> Result<(), String> {
+        self.variables = stack[..stack.len() - 1].iter().map(|frame| frame.locals.clone()).collect();
+        self.variables.push(globals.to_vec());
+
+        let body = json::object(&[("reason", json::string(reason.as_str())), ("threadId", THREAD.to_string()), ("allThreadsStopped", "true".to_string())]);
+        let sent = self.connection.borrow_mut().event("stopped", Some(body));
+        match sent.and_then(|_| self.serve_stopped(stack)) {
+            Ok(Some(resume)) => {
+                self.stepper.resume(resume, stack.len() - 1);
+                Ok(())
+            }
+            Ok(None) => Err("The debugger ended the program".to_string()),
+            Err(error) => {
+                self.error = Some(error);
+                Err("The debugger ended the program".to_string())
+            }
+        }
+    }
+}
+
+/// Runs commands quietly, sending their output to the client once they're done.
+struct Captured<'c, 'io> {
+    connection: &'c RefCell<Connection<'io>>,
+    local: command::Local,
+}
+
+impl Runner for Captured<'_, '_> {
+    fn run(&mut self, invocation: &Invocation) -> Result<Outcome, String> {
+        let outcome = self.local.run(invocation)?;
+        let mut connection = self.connection.borrow_mut();
+        for (category, text) in [("stdout", &outcome.stdout), ("stderr", &outcome.stderr)] {
+            for line in text.lines() {
+                connection.output(category, &format!("[{}] {}\n", invocation.command, line)).map_err(|e| format!("Can't send output to the debugger: {}", e))?;
+            }
+        }
+        Ok(outcome)
+    }
+
+    fn secret(&mut self, name: &str, source: &Source) -> Result<String, String> {
+        self.local.secret(name, source)
+    }
+}
@@ -0,0 +1,138 @@
+//! Debugging hooks of the interpreter: breakpoints by line, stepping, and views of the call stack and variables.
+//!
+//! The interpreter asks its `Hook` before each statement whether to stop there, and hands it the stack when it does.
+//! `Stepper` makes that decision from breakpoints and the last step request, `dap` puts both behind the debug adapter
+//! protocol.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ast::{FileId, Program, Span, Stmt, StmtKind};
+use crate::value::Value;
+use crate::visit::{self, Visitor};
+
+/// Why the program stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Entry,
+    Breakpoint,
+    Step,
+}
+
+impl Reason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reason::Entry => "entry",
+            Reason::Breakpoint => "breakpoint",
+            Reason::Step => "step",
+        }
+    }
+}
+
+/// A call in progress while stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    /// Name of the function or command, `TOP_LEVEL` for the top-level statements
+    pub name: String,
+    /// Statement being run
+    pub span: Span,
+    /// Variables declared so far in the call, in declaration order, `None` for those without a value yet
+    pub locals: Vec<(String, Option<Value>)>,
+}
+
+/// Name of the frame running the top-level statements of the files.
+pub const TOP_LEVEL: &str = "<top level>";
+
+/// Called by the interpreter as it runs.
+pub trait Hook {
+    /// Whether to stop before the statement at `span`, `depth` being the number of calls in progress.
+    fn should_stop(&mut self, span: Span, depth: usize) -> Option<Reason>;
+
+    /// Called once stopped with the stack, innermost call first, and the global variables.
+    /// Returns when the program should go on, or fails to end it with the error message.
+    fn stopped(&mut self, reason: Reason, stack: &[StackFrame], globals: &[(String, Option<Value>)]) -> Result<(), String>;
+}
+
+/// How the program goes on after a stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Until the next breakpoint
+    Continue,
+    /// Until the next statement, within a call if it's one
+    StepIn,
+    /// Until the next statement of the same call or one of its callers
+    StepOver,
+    /// Until the next statement of a caller
+    StepOut,
+}
+
+/// Breakpoints and stepping state, deciding where to stop.
+#[derive(Debug, Clone)]
+pub struct Stepper {
+    /// Lines with a breakpoint, by file
+    breakpoints: HashMap<FileId, BTreeSet<usize>>,
+    resume: Resume,
+    /// Call depth at the last stop
+    depth: usize,
+    /// Stop before the first statement
+    stop_on_entry: bool,
+}
+
+impl Stepper {
+    pub fn new(stop_on_entry: bool) -> Self {
+        Stepper { breakpoints: HashMap::new(), resume: Resume::Continue, depth: 0, stop_on_entry }
+    }
+
+    /// Replaces the breakpoints of `file`.
+    pub fn set_breakpoints<I: IntoIterator<Item = usize>>(&mut self, file: FileId, lines: I) {
+        self.breakpoints.insert(file, lines.into_iter().collect());
+    }
+
+    /// Goes on after a stop at call depth `depth`.
+    pub fn resume(&mut self, resume: Resume, depth: usize) {
+        self.resume = resume;
+        self.depth = depth;
+    }
+
+    pub fn should_stop(&mut self, span: Span, depth: usize) -> Option<Reason> {
+        if self.stop_on_entry {
+            self.stop_on_entry = false;
+            return Some(Reason::Entry);
+        }
+
+        let step = match self.resume {
+            Resume::Continue => false,
+            Resume::StepIn => true,
+            Resume::StepOver => depth <= self.depth,
+            Resume::StepOut => depth < self.depth,
+        };
+        if step {
+            return Some(Reason::Step);
+        }
+
+        match self.breakpoints.get(&span.file) {
+            Some(lines) if lines.contains(&span.line) => Some(Reason::Breakpoint),
+            _ => None,
+        }
+    }
+}
+
+/// Lines of `program` on which a statement starts, where breakpoints can stop.
+pub fn statement_lines(program: &Program) -> BTreeSet<usize> {
+    let mut lines = StatementLines { lines: BTreeSet::new() };
+    lines.visit_program(program);
+    lines.lines
+}
+
+struct StatementLines {
+    lines: BTreeSet<usize>,
+}
+
+impl<'ast> Visitor<'ast> for StatementLines {
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        // Blocks only group statements, the interpreter doesn't stop on them
+        if !matches!(stmt.kind, StmtKind::Block(_)) {
+            self.lines.insert(stmt.span.line);
+        }
+        visit::walk_stmt(self, stmt);
+    }
+}
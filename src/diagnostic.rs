@@ -0,0 +1,412 @@
+//! Errors and warnings of every pass in one shape, rendered for terminals or as JSON lines.
+
+use core::fmt::Display;
+
+use crate::ast::{FileId, Span};
+use crate::cfg::FlowError;
+use crate::checker::TypeError;
+use crate::compiler::CompileError;
+use crate::constant::ConstError;
+use crate::domain::SchemaError;
+use crate::graph::{CycleError, DependencyGraph};
+use crate::init::InitError;
+use crate::interpreter::RuntimeError;
+use crate::json;
+use crate::lint::Finding;
+use crate::module::{ModuleError, Modules};
+use crate::parser::ParseError;
+use crate::resolve::NameError;
+
+pub const SYNTAX: &str = "E0001";
+pub const IMPORT: &str = "E0002";
+pub const NAME: &str = "E0100";
+pub const SHADOWING: &str = "W0100";
+pub const TYPE: &str = "E0200";
+pub const FLOW: &str = "E0300";
+pub const UNREACHABLE: &str = "W0300";
+pub const UNINITIALIZED: &str = "E0400";
+pub const CONSTANT: &str = "E0500";
+pub const SCHEMA: &str = "E0600";
+pub const CYCLE: &str = "E0700";
+pub const RUNTIME: &str = "E0800";
+pub const COMPILE: &str = "E0801";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Warning => YELLOW,
+            Severity::Error => RED,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A span of source pointed at by a diagnostic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    /// The primary label is where the problem is, secondary ones give context
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: String,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &str, message: &str) -> Self {
+        Diagnostic { severity, code: code.to_string(), message: message.to_string(), labels: vec![], notes: vec![], help: vec![] }
+    }
+
+    pub fn error(code: &str, message: &str) -> Self {
+        Diagnostic::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &str, message: &str) -> Self {
+        Diagnostic::new(Severity::Warning, code, message)
+    }
+
+    pub fn with_label(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label { span, message: message.to_string(), primary: true });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label { span, message: message.to_string(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help.push(help.to_string());
+        self
+    }
+
+    /// Span of the first primary label.
+    pub fn span(&self) -> Option<Span> {
+        self.labels.iter().find(|label| label.primary).map(|label| label.span)
+    }
+
+    /// Multi-line text with source excerpts, colored with ANSI escapes when `color` is set.
+    pub fn render(&self, sources: &dyn Sources, color: bool) -> String {
+        Renderer { sources, color, out: String::new() }.render(self)
+    }
+
+    /// Single-line JSON object, with file paths taken from `sources`.
+    pub fn to_json(&self, sources: &dyn Sources) -> String {
+        let labels = self.labels.iter().map(|label| {
+            json::object(&[
+                ("file", json::string(&sources.name(label.span.file))),
+                ("line", label.span.line.to_string()),
+                ("column", label.span.column.to_string()),
+                ("end_line", label.span.end_line.to_string()),
+                ("end_column", label.span.end_column.to_string()),
+                ("message", json::string(&label.message)),
+                ("primary", label.primary.to_string()),
+            ])
+        });
+
+        json::object(&[
+            ("severity", json::string(self.severity.as_str())),
+            ("code", json::string(&self.code)),
+            ("message", json::string(&self.message)),
+            ("labels", json::array(labels)),
+            ("notes", json::array(self.notes.iter().map(|note| json::string(note)))),
+            ("help", json::array(self.help.iter().map(|help| json::string(help)))),
+        ])
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self.span() {
+            Some(span) => write!(f, "{}[{}]: {} at {}", self.severity, self.code, self.message, span),
+            None => write!(f, "{}[{}]: {}", self.severity, self.code, self.message),
+        }
+    }
+}
+
+/// Names and text of the files that spans point into.
+pub trait Sources {
+    fn name(&self, file: FileId) -> String;
+
+    /// Text of a file, `None` when it isn't available and excerpts are left out.
+    fn text(&self, file: FileId) -> Option<&str>;
+}
+
+/// A single file, every span pointing into it whatever its file id.
+pub struct Source<'a> {
+    pub name: &'a str,
+    pub text: &'a str,
+}
+
+impl Sources for Source<'_> {
+    fn name(&self, _file: FileId) -> String {
+        self.name.to_string()
+    }
+
+    fn text(&self, _file: FileId) -> Option<&str> {
+        Some(self.text)
+    }
+}
+
+impl Sources for Modules {
+    fn name(&self, file: FileId) -> String {
+        self.files.get(file).map_or_else(String::new, |f| f.path.display().to_string())
+    }
+
+    fn text(&self, file: FileId) -> Option<&str> {
+        self.files.get(file).map(|f| f.source.as_str())
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+struct Renderer<'a> {
+    sources: &'a dyn Sources,
+    color: bool,
+    out: String,
+}
+
+impl Renderer<'_> {
+    fn paint(&mut self, style: &str, text: &str) {
+        if self.color {
+            self.out.push_str(style);
+            self.out.push_str(text);
+            self.out.push_str(RESET);
+        } else {
+            self.out.push_str(text);
+        }
+    }
+
+    fn render(mut self, diagnostic: &Diagnostic) -> String {
+        self.paint(diagnostic.severity.color(), &format!("{}[{}]", diagnostic.severity, diagnostic.code));
+        self.paint(BOLD, &format!(": {}", diagnostic.message));
+        self.out.push('\n');
+
+        // Labels in the file of the primary one come first, under the header naming that file
+        let file = diagnostic.span().map(|primary| primary.file);
+        let mut labels: Vec<&Label> = diagnostic.labels.iter().collect();
+        labels.sort_by_key(|label| (Some(label.span.file) != file, label.span.file, label.span.line, label.span.column));
+        let width = labels.iter().map(|label| label.span.line.to_string().len()).max().unwrap_or(1);
+        let gutter = " ".repeat(width);
+
+        if let Some(primary) = diagnostic.span() {
+            self.paint(BLUE, &format!("{}--> ", gutter));
+            self.out.push_str(&format!("{}:{}\n", self.sources.name(primary.file), primary));
+        }
+
+        let mut previous: Option<(FileId, usize)> = None;
+        for (i, label) in labels.iter().enumerate() {
+            let text = match self.sources.text(label.span.file) {
+                Some(text) => text,
+                None => continue,
+            };
+            let line_text = text.lines().nth(label.span.line.saturating_sub(1)).unwrap_or("");
+
+            match previous {
+                Some((file, _)) if file != label.span.file => {
+                    self.paint(BLUE, &format!("{}::: ", gutter));
+                    self.out.push_str(&format!("{}:{}\n", self.sources.name(label.span.file), label.span));
+                }
+                None => {
+                    self.paint(BLUE, &format!("{} |\n", gutter));
+                }
+                _ => {}
+            }
+            if previous != Some((label.span.file, label.span.line)) {
+                if matches!(previous, Some((file, line)) if file == label.span.file && line + 1 < label.span.line) {
+                    self.paint(BLUE, "...\n");
+                }
+                self.paint(BLUE, &format!("{:>width$} | ", label.span.line, width = width));
+                self.out.push_str(line_text);
+                self.out.push('\n');
+            }
+            previous = Some((label.span.file, label.span.line));
+
+            self.paint(BLUE, &format!("{} | ", gutter));
+            self.underline(label, line_text, diagnostic.severity);
+            if i + 1 == labels.len() {
+                self.paint(BLUE, &format!("{} |\n", gutter));
+            }
+        }
+
+        for note in &diagnostic.notes {
+            self.paint(BLUE, &format!("{} = ", gutter));
+            self.paint(BOLD, "note");
+            self.out.push_str(&format!(": {}\n", note));
+        }
+        for help in &diagnostic.help {
+            self.paint(BLUE, &format!("{} = ", gutter));
+            self.paint(BOLD, "help");
+            self.out.push_str(&format!(": {}\n", help));
+        }
+
+        self.out
+    }
+
+    /// Marks the columns of `label` on its first line, up to the end of that line for multi-line spans.
+    fn underline(&mut self, label: &Label, line_text: &str, severity: Severity) {
+        let start = (label.span.column.max(1) - 1).min(line_text.len());
+        let end = if label.span.end_line == label.span.line { (label.span.end_column.max(1) - 1).min(line_text.len()) } else { line_text.len() };
+
+        // Tabs are kept so that the marks line up with the excerpt
+        let indent: String = line_text.get(..start).unwrap_or("").chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let length = line_text.get(start..end).map_or(0, |marked| marked.chars().count()).max(1);
+        let (mark, style) = if label.primary { ("^", severity.color()) } else { ("-", BLUE) };
+
+        self.out.push_str(&indent);
+        let mut marks = mark.repeat(length);
+        if !label.message.is_empty() {
+            marks.push(' ');
+            marks.push_str(&label.message);
+        }
+        self.paint(style, &marks);
+        self.out.push('\n');
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let span = Span::new(error.line, error.column, error.line, error.column + 1);
+        Diagnostic::error(SYNTAX, &error.message).with_label(span, "")
+    }
+}
+
+impl From<&ModuleError> for Diagnostic {
+    fn from(error: &ModuleError) -> Self {
+        let diagnostic = Diagnostic::error(if error.syntax { SYNTAX } else { IMPORT }, &error.message);
+        match error.span {
+            Some(span) => diagnostic.with_label(span, ""),
+            None => diagnostic,
+        }
+    }
+}
+
+impl From<&NameError> for Diagnostic {
+    fn from(error: &NameError) -> Self {
+        let diagnostic = if error.warning { Diagnostic::warning(SHADOWING, &error.message) } else { Diagnostic::error(NAME, &error.message) };
+        let diagnostic = diagnostic.with_label(error.span, "");
+        match error.previous {
+            Some(previous) => diagnostic.with_secondary(previous, "declared here"),
+            None => diagnostic,
+        }
+    }
+}
+
+impl From<&TypeError> for Diagnostic {
+    fn from(error: &TypeError) -> Self {
+        Diagnostic::error(TYPE, &error.message).with_label(error.span, "")
+    }
+}
+
+impl From<&FlowError> for Diagnostic {
+    fn from(error: &FlowError) -> Self {
+        if error.warning {
+            Diagnostic::warning(UNREACHABLE, &error.message).with_label(error.span, "")
+        } else {
+            Diagnostic::error(FLOW, &error.message).with_label(error.span, "")
+        }
+    }
+}
+
+impl From<&InitError> for Diagnostic {
+    fn from(error: &InitError) -> Self {
+        Diagnostic::error(UNINITIALIZED, &error.message)
+            .with_label(error.span, "read here")
+            .with_secondary(error.declared, "declared without a value")
+            .with_help("give the variable a value where it's declared, or on every path before this read")
+    }
+}
+
+impl From<&ConstError> for Diagnostic {
+    fn from(error: &ConstError) -> Self {
+        Diagnostic::error(CONSTANT, &error.message).with_label(error.span, "evaluated at compile time")
+    }
+}
+
+impl From<&SchemaError> for Diagnostic {
+    fn from(error: &SchemaError) -> Self {
+        Diagnostic::error(SCHEMA, &error.message).with_label(error.span, "")
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    /// Each place calls were made from gets a single label, with the number of calls when there are several as in
+    /// recursion, the innermost first.
+    fn from(error: &RuntimeError) -> Self {
+        let mut calls: Vec<(Span, usize)> = vec![];
+        for &call in &error.calls {
+            match calls.iter_mut().find(|(span, _)| *span == call) {
+                Some((_, count)) => *count += 1,
+                None => calls.push((call, 1)),
+            }
+        }
+
+        let mut diagnostic = Diagnostic::error(RUNTIME, &error.message).with_label(error.span, "");
+        for (call, count) in calls {
+            let label = if count == 1 { "called from here".to_string() } else { format!("called from here ({} times)", count) };
+            diagnostic = diagnostic.with_secondary(call, &label);
+        }
+        diagnostic
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(error: &CompileError) -> Self {
+        Diagnostic::error(COMPILE, &error.message).with_label(error.span, "")
+    }
+}
+
+impl From<&Finding> for Diagnostic {
+    fn from(finding: &Finding) -> Self {
+        let diagnostic = Diagnostic::new(finding.severity, finding.rule, &finding.message).with_label(finding.span, "");
+        match &finding.fix {
+            Some(fix) => diagnostic.with_help(&format!("replace with `{}`", fix.replacement)),
+            None => diagnostic,
+        }
+    }
+}
+
+/// A cycle with a label on each declaration involved, in the order of the cycle.
+pub fn cycle(error: &CycleError, graph: &DependencyGraph) -> Diagnostic {
+    let mut diagnostic = Diagnostic::error(CYCLE, &error.message).with_label(error.span, "closes the cycle");
+    for &node in &error.path[..error.path.len() - 1] {
+        let node = &graph.nodes[node];
+        diagnostic = diagnostic.with_secondary(node.span, &format!("{} {} is on the cycle", node.kind, node.name));
+    }
+    diagnostic
+}
@@ -0,0 +1,101 @@
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Line changes turning `old` into `new`, computed from their longest common subsequence.
+/// Common prefix and suffix are skipped before building the table so that small edits in big files stay cheap.
+fn changes<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Change, &'a str)> {
+    let prefix = old.iter().zip(new.iter()).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let (n, m) = (old_middle.len(), new_middle.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_middle[i] == new_middle[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut result: Vec<(Change, &str)> = old[..prefix].iter().map(|&line| (Change::Equal, line)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_middle[i] == new_middle[j] {
+            result.push((Change::Equal, old_middle[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            result.push((Change::Delete, old_middle[i]));
+            i += 1;
+        } else {
+            result.push((Change::Insert, new_middle[j]));
+            j += 1;
+        }
+    }
+    result.extend(old[old.len() - suffix..].iter().map(|&line| (Change::Equal, line)));
+
+    result
+}
+
+/// Unified diff between two texts, empty if they are equal.
+pub fn unified(old: &str, new: &str, name: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let changes = changes(&old_lines, &new_lines);
+
+    if changes.iter().all(|(change, _)| *change == Change::Equal) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", name, name);
+    let mut index = 0;
+
+    while index < changes.len() {
+        if changes[index].0 == Change::Equal {
+            index += 1;
+            continue;
+        }
+
+        // Grow the hunk until the next change is further than two contexts away
+        let start = index.saturating_sub(CONTEXT);
+        let mut end = index;
+        while end < changes.len() {
+            if changes[end].0 != Change::Equal {
+                end += 1;
+                continue;
+            }
+
+            let next_change = changes[end..].iter().position(|(change, _)| *change != Change::Equal);
+            match next_change {
+                Some(distance) if distance <= CONTEXT * 2 => end += distance,
+                _ => break,
+            }
+        }
+        let end = (end + CONTEXT).min(changes.len());
+
+        let old_start = changes[..start].iter().filter(|(change, _)| *change != Change::Insert).count();
+        let new_start = changes[..start].iter().filter(|(change, _)| *change != Change::Delete).count();
+        let old_count = changes[start..end].iter().filter(|(change, _)| *change != Change::Insert).count();
+        let new_count = changes[start..end].iter().filter(|(change, _)| *change != Change::Delete).count();
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start + 1, old_count, new_start + 1, new_count));
+
+        for (change, line) in &changes[start..end] {
+            let marker = match change {
+                Change::Equal => ' ',
+                Change::Delete => '-',
+                Change::Insert => '+',
+            };
+            out.push_str(&format!("{}{}\n", marker, line));
+        }
+
+        index = end;
+    }
+
+    out
+}
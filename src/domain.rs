@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use core::fmt::Display;
+
+use crate::ast::{DomainDecl, DomainKind, Item, Program, Span, Type};
+use crate::checker::{self, Types};
+use crate::constant::{Constant, Constants};
+use crate::module::Modules;
+use crate::suggest;
+
+/// A field that a domain declaration may set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    pub ty: Type,
+    pub required: bool,
+    /// Values a string field is restricted to, any value when empty
+    pub allowed: &'static [&'static str],
+    /// Inclusive bounds of an integer field
+    pub range: Option<(i64, i64)>,
+}
+
+impl FieldSchema {
+    pub const fn required(name: &'static str, ty: Type) -> FieldSchema {
+        FieldSchema { name, ty, required: true, allowed: &[], range: None }
+    }
+
+    pub const fn optional(name: &'static str, ty: Type) -> FieldSchema {
+        FieldSchema { name, ty, required: false, allowed: &[], range: None }
+    }
+
+    pub const fn one_of(mut self, allowed: &'static [&'static str]) -> FieldSchema {
+        self.allowed = allowed;
+        self
+    }
+
+    pub const fn between(mut self, min: i64, max: i64) -> FieldSchema {
+        self.range = Some((min, max));
+        self
+    }
+}
+
+static USER_FIELDS: [FieldSchema; 2] = [
+    FieldSchema::required("name", Type::String),
+    FieldSchema::optional("email", Type::String),
+];
+
+static REPO_FIELDS: [FieldSchema; 2] = [
+    FieldSchema::required("url", Type::String),
+    FieldSchema::optional("branch", Type::String),
+];
+
+static MANIFEST_FIELDS: [FieldSchema; 4] = [
+    FieldSchema::required("repo", Type::Domain(DomainKind::Repo)),
+    FieldSchema::required("path", Type::String),
+    FieldSchema::optional("image", Type::String),
+    FieldSchema::optional("replicas", Type::Integer).between(1, 100),
+];
+
+static DEPLOYMENT_FIELDS: [FieldSchema; 4] = [
+    FieldSchema::required("manifest", Type::Domain(DomainKind::Manifest)),
+    FieldSchema::required("repo", Type::Domain(DomainKind::Repo)),
+    FieldSchema::required("environment", Type::String).one_of(&["dev", "staging", "prod"]),
+    FieldSchema::optional("owner", Type::Domain(DomainKind::User)),
+];
+
+static CI_CONFIG_FIELDS: [FieldSchema; 4] = [
+    FieldSchema::required("repo", Type::Domain(DomainKind::Repo)),
+    FieldSchema::optional("deployment", Type::Domain(DomainKind::Deployment)),
+    FieldSchema::optional("branch", Type::String),
+    FieldSchema::optional("owner", Type::Domain(DomainKind::User)),
+];
+
+/// `env` names an environment variable, `file` a secrets file holding `key`, the declaration name by default.
+static SECRET_FIELDS: [FieldSchema; 3] = [
+    FieldSchema::optional("env", Type::String),
+    FieldSchema::optional("file", Type::String),
+    FieldSchema::optional("key", Type::String),
+];
+
+/// Built-in fields of declarations of the given kind.
+pub fn schema(kind: DomainKind) -> &'static [FieldSchema] {
+    match kind {
+        DomainKind::User => &USER_FIELDS,
+        DomainKind::Repo => &REPO_FIELDS,
+        DomainKind::CiConfig => &CI_CONFIG_FIELDS,
+        DomainKind::Deployment => &DEPLOYMENT_FIELDS,
+        DomainKind::Manifest => &MANIFEST_FIELDS,
+        DomainKind::Secret => &SECRET_FIELDS,
+    }
+}
+
+/// Fields accepted by each domain kind, starting from the built-in ones and extensible by embedders.
+#[derive(Debug, Clone)]
+pub struct Schemas {
+    kinds: HashMap<DomainKind, Vec<FieldSchema>>,
+}
+
+impl Default for Schemas {
+    fn default() -> Self {
+        Schemas::builtin()
+    }
+}
+
+impl Schemas {
+    pub fn builtin() -> Self {
+        let kinds = [DomainKind::User, DomainKind::Repo, DomainKind::CiConfig, DomainKind::Deployment, DomainKind::Manifest, DomainKind::Secret]
+            .iter()
+            .map(|&kind| (kind, schema(kind).to_vec()))
+            .collect();
+        Schemas { kinds }
+    }
+
+    pub fn fields(&self, kind: DomainKind) -> &[FieldSchema] {
+        &self.kinds[&kind]
+    }
+
+    pub fn field(&self, kind: DomainKind, name: &str) -> Option<&FieldSchema> {
+        self.fields(kind).iter().find(|f| f.name == name)
+    }
+
+    /// Adds a field to a kind, replacing the field of the same name if there is one.
+    pub fn add_field(&mut self, kind: DomainKind, field: FieldSchema) -> &mut Self {
+        let fields = self.kinds.get_mut(&kind).expect("every kind has a schema");
+        match fields.iter_mut().find(|f| f.name == field.name) {
+            Some(existing) => *existing = field,
+            None => fields.push(field),
+        }
+        self
+    }
+
+    pub fn remove_field(&mut self, kind: DomainKind, name: &str) -> &mut Self {
+        self.kinds.get_mut(&kind).expect("every kind has a schema").retain(|f| f.name != name);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Checks the domain declarations of a file against `schemas`.
+///
+/// Fields must be known to the schema of their kind and hold a value of the declared type, required fields must be set.
+/// Allowed values and ranges are checked on values known at compile time.
+pub fn validate(program: &Program, schemas: &Schemas, types: &Types, constants: &Constants) -> Vec<SchemaError> {
+    let mut errors = vec![];
+
+    for item in &program.items {
+        if let Item::Domain(domain) = item {
+            validate_domain(domain, schemas, types, constants, &mut errors);
+        }
+    }
+
+    errors
+}
+
+/// Validates every loaded file, `types` and `constants` being computed over the same files.
+pub fn validate_modules(modules: &Modules, schemas: &Schemas, types: &Types, constants: &Constants) -> Vec<SchemaError> {
+    modules.order.iter().flat_map(|&file| validate(&modules.files[file].program, schemas, types, constants)).collect()
+}
+
+fn validate_domain(domain: &DomainDecl, schemas: &Schemas, types: &Types, constants: &Constants, errors: &mut Vec<SchemaError>) {
+    let fields = schemas.fields(domain.kind);
+    let missing: Vec<&str> = fields.iter().filter(|f| f.required && domain.field(f.name).is_none()).map(|f| f.name).collect();
+
+    for (i, field) in domain.fields.iter().enumerate() {
+        if domain.fields[..i].iter().any(|f| f.name == field.name) {
+            errors.push(SchemaError { message: format!("Field {} is set more than once", field.name), span: field.name_span });
+            continue;
+        }
+
+        let schema = match schemas.field(domain.kind, &field.name) {
+            Some(schema) => schema,
+            None => {
+                // A misspelled required field is the likeliest typo, otherwise any field of the kind
+                let suggestion = suggest::closest(&field.name, missing.iter().copied())
+                    .or_else(|| suggest::closest(&field.name, fields.iter().map(|f| f.name)));
+                let message = match suggestion {
+                    Some(suggestion) => format!("{} has no field {}, did you mean {}?", domain.kind, field.name, suggestion),
+                    None => format!("{} has no field {}", domain.kind, field.name),
+                };
+                errors.push(SchemaError { message, span: field.name_span });
+                continue;
+            }
+        };
+
+        // Values of unknown type were already reported by the type checker
+        if let Some(ty) = types.expressions.get(&field.value.span) {
+            if *ty != schema.ty {
+                errors.push(SchemaError { message: checker::mismatch(&format!("Field {}", field.name), &schema.ty, ty), span: field.value.span });
+                continue;
+            }
+        }
+
+        match constants.value(&field.value) {
+            Some(Constant::String(value)) if !schema.allowed.is_empty() && !schema.allowed.contains(&value.as_str()) => {
+                let mut message = format!("Field {} must be one of {} but got \"{}\"", field.name, schema.allowed.join(", "), value);
+                if let Some(suggestion) = suggest::closest(value, schema.allowed.iter().copied()) {
+                    message.push_str(&format!(", did you mean \"{}\"?", suggestion));
+                }
+                errors.push(SchemaError { message, span: field.value.span });
+            }
+            Some(Constant::Integer(value)) => {
+                if let Some((min, max)) = schema.range {
+                    if *value < min || *value > max {
+                        let message = format!("Field {} must be between {} and {} but got {}", field.name, min, max, value);
+                        errors.push(SchemaError { message, span: field.value.span });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !missing.is_empty() {
+        errors.push(SchemaError { message: format!("Missing fields {} in {} {}", missing.join(", "), domain.kind, domain.name), span: domain.name_span });
+    }
+}
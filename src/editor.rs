@@ -0,0 +1,309 @@
+//! Line editing for the REPL, with emacs-style keys and a history kept across sessions.
+//!
+//! The terminal is switched to raw mode with `stty` only while a line is read. When stdin isn't a terminal, lines are
+//! read as they come, without editing or echo.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Lines kept in the history file.
+const HISTORY_SIZE: usize = 1000;
+
+/// What reading a line gave.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Line(String),
+    /// Ctrl-C, the line being edited is dropped
+    Interrupted,
+    /// Ctrl-D on an empty line, or the end of stdin
+    Eof,
+}
+
+pub struct Editor {
+    history: Vec<String>,
+    /// Where the history is saved, `None` to keep it in memory
+    history_file: Option<PathBuf>,
+    terminal: bool,
+}
+
+impl Editor {
+    /// An editor whose history starts with the lines of `history_file`, to which new lines are appended.
+    pub fn new(history_file: Option<PathBuf>) -> Editor {
+        let history = history_file.as_ref().and_then(|file| fs::read_to_string(file).ok()).map_or_else(Vec::new, |text| text.lines().map(str::to_string).collect());
+        Editor { history, history_file, terminal: io::stdin().is_terminal() && io::stdout().is_terminal() }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.terminal
+    }
+
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Input> {
+        if !self.terminal {
+            let mut line = String::new();
+            if io::stdin().lock().read_line(&mut line)? == 0 {
+                return Ok(Input::Eof);
+            }
+            return Ok(Input::Line(line.trim_end_matches(&['\n', '\r'][..]).to_string()));
+        }
+
+        let _raw = RawMode::enable()?;
+        self.edit(prompt, &mut io::stdin().lock(), &mut io::stdout().lock())
+    }
+
+    /// Edits a line with the key presses read from `keys`, drawing it on `screen` after `prompt`.
+    pub fn edit(&mut self, prompt: &str, keys: &mut impl Read, screen: &mut impl Write) -> io::Result<Input> {
+        let mut line = LineState { prompt, chars: vec![], cursor: 0, history: self.history.len(), draft: vec![] };
+        line.redraw(screen)?;
+
+        loop {
+            let key = match read_key(keys)? {
+                Some(key) => key,
+                None => return Ok(Input::Eof),
+            };
+
+            match key {
+                Key::Enter => {
+                    write!(screen, "\r\n")?;
+                    screen.flush()?;
+                    return Ok(Input::Line(line.chars.iter().collect()));
+                }
+                Key::Control('c') => {
+                    write!(screen, "^C\r\n")?;
+                    screen.flush()?;
+                    return Ok(Input::Interrupted);
+                }
+                Key::Control('d') if line.chars.is_empty() => {
+                    write!(screen, "\r\n")?;
+                    screen.flush()?;
+                    return Ok(Input::Eof);
+                }
+                Key::Control('d') | Key::Delete => {
+                    if line.cursor < line.chars.len() {
+                        line.chars.remove(line.cursor);
+                    }
+                }
+                Key::Backspace | Key::Control('h') => {
+                    if line.cursor > 0 {
+                        line.cursor -= 1;
+                        line.chars.remove(line.cursor);
+                    }
+                }
+                Key::Left | Key::Control('b') => line.cursor = line.cursor.saturating_sub(1),
+                Key::Right | Key::Control('f') => line.cursor = (line.cursor + 1).min(line.chars.len()),
+                Key::Home | Key::Control('a') => line.cursor = 0,
+                Key::End | Key::Control('e') => line.cursor = line.chars.len(),
+                Key::Control('k') => line.chars.truncate(line.cursor),
+                Key::Control('u') => {
+                    line.chars.drain(..line.cursor);
+                    line.cursor = 0;
+                }
+                Key::Control('w') => {
+                    let mut start = line.cursor;
+                    while start > 0 && line.chars[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    while start > 0 && !line.chars[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    line.chars.drain(start..line.cursor);
+                    line.cursor = start;
+                }
+                Key::Control('l') => write!(screen, "\x1b[2J\x1b[H")?,
+                Key::Up | Key::Control('p') => line.recall(&self.history, -1),
+                Key::Down | Key::Control('n') => line.recall(&self.history, 1),
+                Key::Char(c) => {
+                    line.chars.insert(line.cursor, c);
+                    line.cursor += 1;
+                }
+                Key::Control(_) | Key::Unknown => continue,
+            }
+            line.redraw(screen)?;
+        }
+    }
+
+    /// Adds a line to the history, unless it's blank or repeats the previous one.
+    pub fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(String::as_str) == Some(line) {
+            return;
+        }
+        self.history.push(line.to_string());
+
+        let file = match &self.history_file {
+            Some(file) => file,
+            None => return,
+        };
+        // The file is rewritten once it's twice as long as needed, appending is enough otherwise
+        let result = if self.history.len() > 2 * HISTORY_SIZE {
+            self.history.drain(..self.history.len() - HISTORY_SIZE);
+            fs::write(file, self.history.iter().map(|line| format!("{}\n", line)).collect::<String>())
+        } else {
+            OpenOptions::new().create(true).append(true).open(file).and_then(|mut f| writeln!(f, "{}", line))
+        };
+        // History is a convenience, a read-only home directory shouldn't get in the way
+        if result.is_err() {
+            self.history_file = None;
+        }
+    }
+}
+
+/// The line being edited.
+struct LineState<'p> {
+    prompt: &'p str,
+    chars: Vec<char>,
+    cursor: usize,
+    /// Index of the history entry shown, the length of the history for the line being typed
+    history: usize,
+    /// The line being typed, kept while browsing the history
+    draft: Vec<char>,
+}
+
+impl LineState<'_> {
+    fn redraw(&self, out: &mut impl Write) -> io::Result<()> {
+        let line: String = self.chars.iter().collect();
+        write!(out, "\r{}{}\x1b[K", self.prompt, line)?;
+        if self.cursor < self.chars.len() {
+            write!(out, "\x1b[{}D", self.chars.len() - self.cursor)?;
+        }
+        out.flush()
+    }
+
+    /// Moves `step` entries through the history, back to the draft past the last one.
+    fn recall(&mut self, history: &[String], step: isize) {
+        let target = self.history as isize + step;
+        if target < 0 || target > history.len() as isize {
+            return;
+        }
+
+        if self.history == history.len() {
+            self.draft = self.chars.clone();
+        }
+        self.history = target as usize;
+        self.chars = match history.get(self.history) {
+            Some(entry) => entry.chars().collect(),
+            None => self.draft.clone(),
+        };
+        self.cursor = self.chars.len();
+    }
+}
+
+enum Key {
+    Char(char),
+    /// A control character, by the letter typed with Ctrl
+    Control(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Unknown,
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Reads a key press, `None` at the end of input.
+fn read_key(input: &mut impl Read) -> io::Result<Option<Key>> {
+    let byte = match read_byte(input)? {
+        Some(byte) => byte,
+        None => return Ok(None),
+    };
+
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f => Key::Backspace,
+        0x1b => read_escape(input)?,
+        1..=26 => Key::Control((b'a' + byte - 1) as char),
+        0..=0x1f => Key::Unknown,
+        _ => {
+            // The leading byte tells how many continuation bytes follow
+            let length = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..length {
+                match read_byte(input)? {
+                    Some(byte) => bytes.push(byte),
+                    None => break,
+                }
+            }
+            std::str::from_utf8(&bytes).ok().and_then(|text| text.chars().next()).map_or(Key::Unknown, Key::Char)
+        }
+    };
+    Ok(Some(key))
+}
+
+/// Reads the rest of an escape sequence sent for a special key.
+fn read_escape(input: &mut impl Read) -> io::Result<Key> {
+    let kind = read_byte(input)?;
+    if kind != Some(b'[') && kind != Some(b'O') {
+        return Ok(Key::Unknown);
+    }
+
+    let mut parameter = vec![];
+    loop {
+        let byte = match read_byte(input)? {
+            Some(byte) => byte,
+            None => return Ok(Key::Unknown),
+        };
+        match byte {
+            b'0'..=b'9' | b';' => parameter.push(byte),
+            b'A' => return Ok(Key::Up),
+            b'B' => return Ok(Key::Down),
+            b'C' => return Ok(Key::Right),
+            b'D' => return Ok(Key::Left),
+            b'H' => return Ok(Key::Home),
+            b'F' => return Ok(Key::End),
+            b'~' => {
+                return Ok(match &parameter[..] {
+                    b"1" | b"7" => Key::Home,
+                    b"4" | b"8" => Key::End,
+                    b"3" => Key::Delete,
+                    _ => Key::Unknown,
+                })
+            }
+            _ => return Ok(Key::Unknown),
+        }
+    }
+}
+
+/// Raw mode of the terminal, the previous settings being restored when dropped.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "-ixon", "-icrnl", "min", "1", "time", "0"])?;
+        Ok(RawMode { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[self.saved.as_str()]);
+    }
+}
+
+/// Runs `stty` on the terminal of stdin, returning what it prints.
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).stderr(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed to set up the terminal"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
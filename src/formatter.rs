@@ -0,0 +1,567 @@
+use crate::ast::{Block, CommandDecl, ComplexDecl, DomainDecl, DomainField, Expr, ExprKind, FuncDecl, Item, Program, Span, Stmt, StmtKind, UnaryOp};
+use crate::lexer::Lexer;
+use crate::parser::{ParseError, Parser};
+use crate::token;
+use crate::token::Token;
+
+const INDENT: &str = "    ";
+const MAX_WIDTH: usize = 100;
+
+// Precedence of expressions that aren't binary operators, see `BinaryOp::precedence`
+const ASSIGN_PRECEDENCE: u8 = 0;
+const UNARY_PRECEDENCE: u8 = 9;
+const PRIMARY_PRECEDENCE: u8 = 10;
+
+/// Formats a Shanty source file into the canonical layout.
+///
+/// Comments are kept: the ones on their own lines stay in front of the statement that follows them,
+/// the ones at the end of a line stay after the token they follow, a `{`, a `}` or the end of a statement.
+/// Formatting already formatted source returns it unchanged.
+pub fn format_source(source: &str) -> Result<String, ParseError> {
+    let tokens = Lexer::new(source).all_tokens();
+    let mut comments = vec![];
+    let mut previous: Option<&Token> = None;
+    for token in &tokens {
+        if token.token_type == token::COMMENT {
+            let anchor = previous.filter(|previous| previous.line == token.line).cloned();
+            comments.push(Comment { token: token.clone(), anchor });
+        } else {
+            previous = Some(token);
+        }
+    }
+    let program = Parser::new(tokens).parse_program()?;
+
+    let mut formatter = Formatter { comments, next_comment: 0, out: String::new() };
+    formatter.program(&program);
+    Ok(formatter.out)
+}
+
+/// Formats a program that doesn't come from source text, such as the output of a rewriting pass.
+pub fn format_program(program: &Program) -> String {
+    let mut formatter = Formatter { comments: vec![], next_comment: 0, out: String::new() };
+    formatter.program(program);
+    formatter.out
+}
+
+fn indentation(indent: usize) -> String {
+    INDENT.repeat(indent)
+}
+
+/// Column right after `text` if it's written starting at `column`.
+fn end_column(text: &str, column: usize) -> usize {
+    match text.rfind('\n') {
+        Some(index) => text.len() - index - 1,
+        None => column + text.len(),
+    }
+}
+
+fn visibility(public: bool) -> String {
+    if public {
+        return format!("{} ", token::PUB);
+    }
+
+    String::new()
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match &expr.kind {
+        ExprKind::Assign { .. } => ASSIGN_PRECEDENCE,
+        ExprKind::Binary { op, .. } => op.precedence(),
+        ExprKind::Unary { .. } => UNARY_PRECEDENCE,
+        _ => PRIMARY_PRECEDENCE,
+    }
+}
+
+/// `- -x` can't be written as `--x`, which lexes as a decrement.
+fn is_double_negation(op: UnaryOp, operand: &Expr) -> bool {
+    op == UnaryOp::Negate && matches!(operand.kind, ExprKind::Unary { op: UnaryOp::Negate, .. })
+}
+
+/// A comment with the token it follows on its line, `None` for a comment on its own line.
+struct Comment {
+    token: Token,
+    anchor: Option<Token>,
+}
+
+impl Comment {
+    /// Whether the comment follows a token starting before `position`, a line and column.
+    fn follows_before(&self, position: (usize, usize)) -> bool {
+        self.anchor.as_ref().is_some_and(|anchor| (anchor.line, anchor.column) < position)
+    }
+}
+
+fn comment_text(comment: &Token) -> String {
+    if comment.value.is_empty() || comment.value.starts_with(' ') || comment.value.starts_with('/') {
+        return format!("//{}", comment.value);
+    }
+
+    format!("// {}", comment.value)
+}
+
+struct Formatter {
+    comments: Vec<Comment>,
+    next_comment: usize,
+    out: String,
+}
+
+impl Formatter {
+    fn program(&mut self, program: &Program) {
+        let mut last_line = None;
+        let mut previous_is_declaration = false;
+
+        for item in &program.items {
+            let is_declaration = item.declared_name().is_some();
+            let span = item.span();
+
+            // Declarations are always set apart from their neighbours by a blank line
+            let force_blank = self.leading_comments(span.line, 0, &mut last_line, is_declaration || previous_is_declaration);
+            self.separate(span.line, last_line, force_blank);
+
+            match item {
+                Item::Import(import) => self.out.push_str(&format!("{} \"{}\";\n", token::IMPORT, import.path)),
+                Item::Func(func) => self.func(func),
+                Item::Complex(complex) => self.complex(complex),
+                Item::Domain(domain) => self.domain(domain),
+                Item::Command(command) => self.command(command),
+                Item::Stmt(stmt) => self.stmt(stmt, 0),
+            }
+
+            self.trailing_comments((span.end_line, span.end_column));
+            last_line = Some(span.end_line);
+            previous_is_declaration = is_declaration;
+        }
+
+        self.leading_comments(usize::MAX, 0, &mut last_line, previous_is_declaration);
+    }
+
+    /// Writes a blank line if the source had one before `line` or if `force` is set.
+    /// Nothing is written in front of the first element of a block.
+    fn separate(&mut self, line: usize, last_line: Option<usize>, force: bool) {
+        if let Some(previous) = last_line {
+            if force || line > previous + 1 {
+                self.out.push('\n');
+            }
+        }
+    }
+
+    /// Writes the comments that start before `line` on their own lines.
+    /// Returns whether a forced blank line is still owed to the element that follows them.
+    fn leading_comments(&mut self, line: usize, indent: usize, last_line: &mut Option<usize>, force_blank: bool) -> bool {
+        let mut force_blank = force_blank;
+
+        while let Some(Comment { token: comment, .. }) = self.comments.get(self.next_comment) {
+            if comment.line >= line {
+                break;
+            }
+
+            let comment_line = comment.line;
+            let text = comment_text(comment);
+            self.separate(comment_line, *last_line, force_blank);
+            self.out.push_str(&format!("{}{}\n", indentation(indent), text));
+
+            force_blank = false;
+            *last_line = Some(comment_line);
+            self.next_comment += 1;
+        }
+
+        force_blank
+    }
+
+    /// Appends to the last written line the comments following a token of an element that ends at `end`, a line and
+    /// column, and those on their own lines within it. Returns whether there were any.
+    fn trailing_comments(&mut self, end: (usize, usize)) -> bool {
+        let start = self.next_comment;
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            let within = match comment.anchor {
+                Some(_) => comment.follows_before(end),
+                None => comment.token.line < end.0,
+            };
+            if !within {
+                break;
+            }
+            self.append_comment();
+        }
+        self.next_comment > start
+    }
+
+    /// Appends to the line of an opening `{` the comment following it, `before` being the position of the first
+    /// element inside the braces.
+    fn brace_comment(&mut self, before: (usize, usize)) {
+        if let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.anchor.as_ref().is_some_and(|anchor| anchor.token_type == token::L_BRACE) && comment.follows_before(before) {
+                self.append_comment();
+            }
+        }
+    }
+
+    /// Appends the next comment to the last written line, keeping the line open if it was.
+    fn append_comment(&mut self) {
+        let text = comment_text(&self.comments[self.next_comment].token);
+        let newline = self.out.ends_with('\n');
+        if newline {
+            self.out.pop();
+        }
+        self.out.push_str(&format!(" {}", text));
+        if newline {
+            self.out.push('\n');
+        }
+        self.next_comment += 1;
+    }
+
+    fn has_comment_before(&self, line: usize) -> bool {
+        self.comments.get(self.next_comment).is_some_and(|c| c.token.line < line)
+    }
+
+    fn func(&mut self, func: &FuncDecl) {
+        let prefix = format!("{}{} {} {}(", visibility(func.public), token::FUNC, func.return_type, func.name);
+        let params: Vec<String> = func.params.iter().map(|p| format!("{} {}", p.ty, p.name)).collect();
+        self.params(&prefix, &params);
+
+        self.block(&func.body, 0);
+        self.out.push('\n');
+    }
+
+    fn complex(&mut self, complex: &ComplexDecl) {
+        self.out.push_str(&format!("{}{} {} ", visibility(complex.public), token::COMPLEX, complex.name));
+        if complex.fields.is_empty() && !self.has_comment_before(complex.span.end_line) {
+            self.out.push_str("{}\n");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.brace_comment(complex.fields.first().map_or((complex.span.end_line, complex.span.end_column), |field| (field.span.line, field.span.column)));
+        let mut last_line = None;
+
+        for field in &complex.fields {
+            let force_blank = self.leading_comments(field.span.line, 1, &mut last_line, false);
+            self.separate(field.span.line, last_line, force_blank);
+            self.out.push_str(&format!("{}{}: {};\n", INDENT, field.name, field.ty));
+            self.trailing_comments((field.span.end_line, field.span.end_column));
+            last_line = Some(field.span.end_line);
+        }
+
+        self.leading_comments(complex.span.end_line, 1, &mut last_line, false);
+        self.out.push_str("}\n");
+    }
+
+    fn domain(&mut self, domain: &DomainDecl) {
+        self.out.push_str(&format!("{}{} {} ", visibility(domain.public), domain.kind, domain.name));
+        self.fields(&domain.fields, domain.span);
+    }
+
+    fn command(&mut self, command: &CommandDecl) {
+        let prefix = format!("{}{} {}(", visibility(command.public), token::COMMAND, command.name);
+        let params: Vec<String> = command.params.iter().map(|p| format!("{} {}", p.ty, p.name)).collect();
+        self.params(&prefix, &params);
+        self.fields(&command.fields, command.span);
+    }
+
+    /// Writes the parameter list after `prefix` on one line, or one parameter per line when it doesn't fit.
+    fn params(&mut self, prefix: &str, params: &[String]) {
+        let flat = format!("{}{}) ", prefix, params.join(", "));
+
+        if params.is_empty() || flat.len() < MAX_WIDTH {
+            self.out.push_str(&flat);
+        } else {
+            self.out.push_str(prefix);
+            self.out.push('\n');
+            self.out.push_str(&params.iter().map(|p| format!("{}{}", INDENT, p)).collect::<Vec<_>>().join(",\n"));
+            self.out.push_str("\n) ");
+        }
+    }
+
+    /// Writes the `{ name = value; ... }` body of a domain or command declaration spanning `span`.
+    fn fields(&mut self, fields: &[DomainField], span: Span) {
+        if fields.is_empty() && !self.has_comment_before(span.end_line) {
+            self.out.push_str("{}\n");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.brace_comment(fields.first().map_or((span.end_line, span.end_column), |field| (field.span.line, field.span.column)));
+        let mut last_line = None;
+
+        for field in fields {
+            let force_blank = self.leading_comments(field.span.line, 1, &mut last_line, false);
+            self.separate(field.span.line, last_line, force_blank);
+            let head = format!("{}{} = ", INDENT, field.name);
+            let value = self.expr(&field.value, 1, head.len(), 1);
+            self.out.push_str(&format!("{}{};\n", head, value));
+            self.trailing_comments((field.span.end_line, field.span.end_column));
+            last_line = Some(field.span.end_line);
+        }
+
+        self.leading_comments(span.end_line, 1, &mut last_line, false);
+        self.out.push_str("}\n");
+    }
+
+    /// Writes `{`, the statements of the block and `}` without a final new line, the current line holds the block header.
+    /// Returns whether a comment follows the `}`, which then has to end its line.
+    fn block(&mut self, block: &Block, indent: usize) -> bool {
+        if block.stmts.is_empty() && !self.has_comment_before(block.span.end_line) {
+            self.out.push_str("{}");
+            return self.trailing_comments((block.span.end_line, block.span.end_column));
+        }
+
+        self.out.push_str("{\n");
+        self.brace_comment(block.stmts.first().map_or((block.span.end_line, block.span.end_column), |stmt| (stmt.span.line, stmt.span.column)));
+        let mut last_line = None;
+
+        for stmt in &block.stmts {
+            let force_blank = self.leading_comments(stmt.span.line, indent + 1, &mut last_line, false);
+            self.separate(stmt.span.line, last_line, force_blank);
+            self.stmt(stmt, indent + 1);
+            self.trailing_comments((stmt.span.end_line, stmt.span.end_column));
+            last_line = Some(stmt.span.end_line);
+        }
+
+        self.leading_comments(block.span.end_line, indent + 1, &mut last_line, false);
+        self.out.push_str(&indentation(indent));
+        self.out.push('}');
+        self.trailing_comments((block.span.end_line, block.span.end_column))
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, indent: usize) {
+        let prefix = indentation(indent);
+        self.out.push_str(&prefix);
+
+        match &stmt.kind {
+            StmtKind::VarDecl { name, ty, value, .. } => {
+                let declaration = match ty {
+                    Some(ty) => format!("{} {}", ty, name),
+                    None => format!("{} {}", token::VAR, name),
+                };
+
+                match value {
+                    Some(value) => {
+                        let head = format!("{} = ", declaration);
+                        let value = self.expr(value, indent, prefix.len() + head.len(), 1);
+                        self.out.push_str(&format!("{}{};", head, value));
+                    }
+                    None => self.out.push_str(&format!("{};", declaration)),
+                }
+            }
+            StmtKind::If { .. } => self.if_stmt(stmt, indent),
+            StmtKind::While { condition, body } => {
+                let condition = self.expr(condition, indent, prefix.len() + 7, 3);
+                self.out.push_str(&format!("{} ({}) ", token::WHILE, condition));
+                self.block(body, indent);
+            }
+            StmtKind::Return(value) => match value {
+                Some(value) => {
+                    let value = self.expr(value, indent, prefix.len() + 7, 1);
+                    self.out.push_str(&format!("{} {};", token::RETURN, value));
+                }
+                None => self.out.push_str(&format!("{};", token::RETURN)),
+            },
+            StmtKind::Block(block) => {
+                self.block(block, indent);
+            }
+            StmtKind::Expr(expr) => {
+                let expr = self.expr(expr, indent, prefix.len(), 1);
+                self.out.push_str(&format!("{};", expr));
+            }
+        }
+
+        self.out.push('\n');
+    }
+
+    /// Writes an `if` with its `else if` chain, continuing on the current line.
+    fn if_stmt(&mut self, stmt: &Stmt, indent: usize) {
+        if let StmtKind::If { condition, then_branch, else_branch } = &stmt.kind {
+            let column = end_column(&self.out, 0) + 4;
+            let condition = self.expr(condition, indent, column, 3);
+            self.out.push_str(&format!("{} ({}) ", token::IF, condition));
+            let commented = self.block(then_branch, indent);
+
+            if let Some(else_branch) = else_branch {
+                // A comment after the `}` pushes `else` to the next line
+                if commented {
+                    self.out.push_str(&format!("\n{}{} ", indentation(indent), token::ELSE));
+                } else {
+                    self.out.push_str(&format!(" {} ", token::ELSE));
+                }
+                match &else_branch.kind {
+                    StmtKind::Block(block) => {
+                        self.block(block, indent);
+                    }
+                    _ => self.if_stmt(else_branch, indent),
+                }
+            }
+        }
+    }
+
+    /// Formats an expression starting at `column`, followed by `suffix` characters on the same line.
+    /// Expressions that don't fit into `MAX_WIDTH` get their call arguments wrapped one per line, and chains of binary
+    /// operators of the same precedence a line per operator.
+    fn expr(&self, expr: &Expr, indent: usize, column: usize, suffix: usize) -> String {
+        let flat = self.flat(expr);
+        if column + flat.len() + suffix <= MAX_WIDTH {
+            return flat;
+        }
+
+        match &expr.kind {
+            ExprKind::Call { callee, args } if !args.is_empty() => {
+                let mut text = format!("{}(\n", self.operand(callee, PRIMARY_PRECEDENCE, indent, column, 0));
+                let arg_indent = indentation(indent + 1);
+
+                for (i, arg) in args.iter().enumerate() {
+                    let comma = if i + 1 < args.len() { "," } else { "" };
+                    let arg = self.expr(arg, indent + 1, arg_indent.len(), comma.len());
+                    text.push_str(&format!("{}{}{}\n", arg_indent, arg, comma));
+                }
+
+                text.push_str(&format!("{})", indentation(indent)));
+                text
+            }
+            ExprKind::Binary { op, .. } => {
+                // Operators are left associative, so the chain is made of the left operands of the same precedence
+                let mut chain = vec![];
+                let mut first = expr;
+                while let ExprKind::Binary { op: link, left, right } = &first.kind {
+                    if link.precedence() != op.precedence() {
+                        break;
+                    }
+                    chain.push((link, &**right));
+                    first = left;
+                }
+
+                let mut text = self.operand(first, op.precedence(), indent, column, 0);
+                let operand_indent = indentation(indent + 1);
+                for (i, (link, operand)) in chain.iter().rev().enumerate() {
+                    let suffix = if i + 1 == chain.len() { suffix } else { 0 };
+                    let head = format!("{}{} ", operand_indent, link.as_str());
+                    let operand = self.operand(operand, op.precedence() + 1, indent + 1, head.len(), suffix);
+                    text.push_str(&format!("\n{}{}", head, operand));
+                }
+                text
+            }
+            ExprKind::List(values) if !values.is_empty() => {
+                let mut text = "[\n".to_string();
+                let value_indent = indentation(indent + 1);
+
+                for (i, value) in values.iter().enumerate() {
+                    let comma = if i + 1 < values.len() { "," } else { "" };
+                    let value = self.expr(value, indent + 1, value_indent.len(), comma.len());
+                    text.push_str(&format!("{}{}{}\n", value_indent, value, comma));
+                }
+
+                text.push_str(&format!("{}]", indentation(indent)));
+                text
+            }
+            ExprKind::Map(entries) if !entries.is_empty() => {
+                let mut text = "[\n".to_string();
+                let entry_indent = indentation(indent + 1);
+
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    let comma = if i + 1 < entries.len() { "," } else { "" };
+                    let head = format!("{}{}: ", entry_indent, self.flat(key));
+                    let value = self.expr(value, indent + 1, head.len(), comma.len());
+                    text.push_str(&format!("{}{}{}\n", head, value, comma));
+                }
+
+                text.push_str(&format!("{}]", indentation(indent)));
+                text
+            }
+            ExprKind::ComplexLiteral { name, fields, .. } if !fields.is_empty() => {
+                let mut text = format!("{} {{\n", name);
+                let field_indent = indentation(indent + 1);
+
+                for (i, field) in fields.iter().enumerate() {
+                    let comma = if i + 1 < fields.len() { "," } else { "" };
+                    let head = format!("{}{}: ", field_indent, field.name);
+                    let value = self.expr(&field.value, indent + 1, head.len(), comma.len());
+                    text.push_str(&format!("{}{}{}\n", head, value, comma));
+                }
+
+                text.push_str(&format!("{}}}", indentation(indent)));
+                text
+            }
+            ExprKind::Field { object, field, .. } => {
+                format!("{}.{}", self.operand(object, PRIMARY_PRECEDENCE, indent, column, field.len() + 1 + suffix), field)
+            }
+            ExprKind::Assign { target, value } => {
+                let target = self.flat(target);
+                let value = self.expr(value, indent, column + target.len() + 3, suffix);
+                format!("{} = {}", target, value)
+            }
+            ExprKind::Unary { op, operand } => {
+                format!("{}{}", op.as_str(), self.unary_operand(*op, operand, indent, column + 1, suffix))
+            }
+            _ => flat,
+        }
+    }
+
+    /// Formats `expr` as an operand of an operator binding with `min_precedence`, adding parentheses when needed.
+    fn operand(&self, expr: &Expr, min_precedence: u8, indent: usize, column: usize, suffix: usize) -> String {
+        if precedence(expr) < min_precedence {
+            return format!("({})", self.expr(expr, indent, column + 1, suffix + 1));
+        }
+
+        self.expr(expr, indent, column, suffix)
+    }
+
+    fn unary_operand(&self, op: UnaryOp, operand: &Expr, indent: usize, column: usize, suffix: usize) -> String {
+        if is_double_negation(op, operand) {
+            return format!("({})", self.expr(operand, indent, column + 1, suffix + 1));
+        }
+
+        self.operand(operand, UNARY_PRECEDENCE, indent, column, suffix)
+    }
+
+    /// Formats an expression on a single line.
+    fn flat(&self, expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Integer(value) => value.to_string(),
+            ExprKind::String(value) => format!("\"{}\"", value),
+            ExprKind::Rune(value) => format!("'{}'", value),
+            ExprKind::Boolean(value) => value.to_string(),
+            ExprKind::Identifier(name) => name.clone(),
+            ExprKind::Unary { op, operand } => {
+                if is_double_negation(*op, operand) {
+                    return format!("{}({})", op.as_str(), self.flat(operand));
+                }
+
+                format!("{}{}", op.as_str(), self.flat_operand(operand, UNARY_PRECEDENCE))
+            }
+            ExprKind::Postfix { op, operand } => format!("{}{}", self.flat(operand), op.as_str()),
+            ExprKind::Binary { op, left, right } => format!(
+                "{} {} {}",
+                self.flat_operand(left, op.precedence()),
+                op.as_str(),
+                self.flat_operand(right, op.precedence() + 1)
+            ),
+            ExprKind::Assign { target, value } => format!("{} = {}", self.flat(target), self.flat(value)),
+            ExprKind::Call { callee, args } => {
+                let args: Vec<String> = args.iter().map(|arg| self.flat(arg)).collect();
+                format!("{}({})", self.flat_operand(callee, PRIMARY_PRECEDENCE), args.join(", "))
+            }
+            ExprKind::Field { object, field, .. } => format!("{}.{}", self.flat_operand(object, PRIMARY_PRECEDENCE), field),
+            ExprKind::ComplexLiteral { name, fields, .. } => {
+                if fields.is_empty() {
+                    return format!("{} {{}}", name);
+                }
+
+                let fields: Vec<String> = fields.iter().map(|f| format!("{}: {}", f.name, self.flat(&f.value))).collect();
+                format!("{} {{ {} }}", name, fields.join(", "))
+            }
+            ExprKind::List(values) => {
+                let values: Vec<String> = values.iter().map(|value| self.flat(value)).collect();
+                format!("[{}]", values.join(", "))
+            }
+            ExprKind::Map(entries) if entries.is_empty() => "[:]".to_string(),
+            ExprKind::Map(entries) => {
+                let entries: Vec<String> = entries.iter().map(|(key, value)| format!("{}: {}", self.flat(key), self.flat(value))).collect();
+                format!("[{}]", entries.join(", "))
+            }
+        }
+    }
+
+    fn flat_operand(&self, expr: &Expr, min_precedence: u8) -> String {
+        if precedence(expr) < min_precedence {
+            return format!("({})", self.flat(expr));
+        }
+
+        self.flat(expr)
+    }
+}
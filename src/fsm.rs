@@ -38,10 +38,10 @@ impl FSM<'_> {
             length = i;
         }
 
-        return RunResult {
-            recognized: self.accepting_states.iter().any(|&i| i == current_state),
+        RunResult {
+            recognized: self.accepting_states.contains(&current_state),
             value: input[0..length + 1].to_string(),
             state: current_state,
-        };
+        }
     }
 }
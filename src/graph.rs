@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use core::fmt::Display;
+
+use crate::ast::{DomainDecl, DomainKind, Expr, ExprKind, Item, Program, Span};
+use crate::module::Modules;
+use crate::resolve::{Resolution, SymbolKind};
+use crate::visit::{self, Visitor};
+
+pub type NodeId = usize;
+
+/// A domain declaration in the dependency graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub kind: DomainKind,
+    pub name: String,
+    /// Span of the declared name
+    pub span: Span,
+}
+
+/// Which domain declarations refer to which, through the values of their fields.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    pub nodes: Vec<Node>,
+    /// Declarations each node depends on, in the order its fields refer to them
+    pub dependencies: Vec<Vec<NodeId>>,
+    /// Span of each dependency, parallel to `dependencies`
+    pub spans: Vec<Vec<Span>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleError {
+    pub message: String,
+    /// The reference closing the cycle
+    pub span: Span,
+    /// Declarations on the cycle, the first one repeated at the end
+    pub path: Vec<NodeId>,
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Builds the dependency graph of the domain declarations of a single file.
+pub fn build(program: &Program, resolution: &Resolution) -> DependencyGraph {
+    build_programs(&[program], resolution)
+}
+
+/// Builds the dependency graph of the domain declarations of every loaded file.
+pub fn build_modules(modules: &Modules, resolution: &Resolution) -> DependencyGraph {
+    let programs: Vec<&Program> = modules.order.iter().map(|&file| &modules.files[file].program).collect();
+    build_programs(&programs, resolution)
+}
+
+fn build_programs(programs: &[&Program], resolution: &Resolution) -> DependencyGraph {
+    let domains: Vec<&DomainDecl> = programs
+        .iter()
+        .flat_map(|program| program.items.iter())
+        .filter_map(|item| match item {
+            Item::Domain(domain) => Some(domain),
+            _ => None,
+        })
+        .collect();
+
+    let mut graph = DependencyGraph::default();
+    let mut ids = HashMap::new();
+    for domain in &domains {
+        ids.insert(domain.name_span, graph.nodes.len());
+        graph.nodes.push(Node { kind: domain.kind, name: domain.name.clone(), span: domain.name_span });
+    }
+
+    for domain in &domains {
+        let mut references = References { resolution, found: vec![] };
+        references.visit_domain(domain);
+
+        let mut dependencies = vec![];
+        let mut spans = vec![];
+        for (declaration, span) in references.found {
+            if let Some(&id) = ids.get(&declaration) {
+                if !dependencies.contains(&id) {
+                    dependencies.push(id);
+                    spans.push(span);
+                }
+            }
+        }
+        graph.dependencies.push(dependencies);
+        graph.spans.push(spans);
+    }
+
+    graph
+}
+
+impl DependencyGraph {
+    /// Every cycle found by a depth-first search, each reported once at the reference closing it.
+    pub fn cycles(&self) -> Vec<CycleError> {
+        self.search().cycles
+    }
+
+    /// Declarations ordered so that each one comes after everything it depends on.
+    /// Ties keep declaration order, and a cycle makes the order impossible.
+    pub fn topological_order(&self) -> Result<Vec<NodeId>, Vec<CycleError>> {
+        let search = self.search();
+        if search.cycles.is_empty() {
+            Ok(search.order)
+        } else {
+            Err(search.cycles)
+        }
+    }
+
+    fn search(&self) -> Search<'_> {
+        let mut search = Search { graph: self, state: vec![State::New; self.nodes.len()], stack: vec![], order: vec![], cycles: vec![] };
+        for node in 0..self.nodes.len() {
+            search.visit(node);
+        }
+        search
+    }
+
+    /// `kind name -> kind name -> ...` description of a path through the graph.
+    pub fn describe(&self, path: &[NodeId]) -> String {
+        path.iter().map(|&id| format!("{} {}", self.nodes[id].kind, self.nodes[id].name)).collect::<Vec<String>>().join(" -> ")
+    }
+
+    /// Graphviz description of the graph, edges pointing from a declaration to what it depends on.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n");
+
+        for (id, node) in self.nodes.iter().enumerate() {
+            dot.push_str(&format!("    n{} [label=\"{} {}\"];\n", id, node.kind, node.name));
+        }
+        for (id, dependencies) in self.dependencies.iter().enumerate() {
+            for dependency in dependencies {
+                dot.push_str(&format!("    n{} -> n{};\n", id, dependency));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    New,
+    /// On the stack of the search, a reference back to it closes a cycle
+    Active,
+    Done,
+}
+
+struct Search<'g> {
+    graph: &'g DependencyGraph,
+    state: Vec<State>,
+    stack: Vec<NodeId>,
+    order: Vec<NodeId>,
+    cycles: Vec<CycleError>,
+}
+
+impl Search<'_> {
+    fn visit(&mut self, node: NodeId) {
+        if self.state[node] != State::New {
+            return;
+        }
+
+        self.state[node] = State::Active;
+        self.stack.push(node);
+
+        for (i, &dependency) in self.graph.dependencies[node].iter().enumerate() {
+            match self.state[dependency] {
+                State::New => self.visit(dependency),
+                State::Active => {
+                    let start = self.stack.iter().position(|&n| n == dependency).expect("active nodes are on the stack");
+                    let mut path = self.stack[start..].to_vec();
+                    path.push(dependency);
+
+                    let message = format!("Dependency cycle: {}", self.graph.describe(&path));
+                    self.cycles.push(CycleError { message, span: self.graph.spans[node][i], path });
+                }
+                State::Done => {}
+            }
+        }
+
+        self.stack.pop();
+        self.state[node] = State::Done;
+        self.order.push(node);
+    }
+}
+
+/// Collects the domain declarations referred to from an expression, with the span of each reference.
+struct References<'r> {
+    resolution: &'r Resolution,
+    found: Vec<(Span, Span)>,
+}
+
+impl<'ast> Visitor<'ast> for References<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let ExprKind::Identifier(_) = &expr.kind {
+            if let Some(symbol) = self.resolution.definition(expr.span) {
+                if let SymbolKind::Domain(_) = symbol.kind {
+                    self.found.push((symbol.span, expr.span));
+                }
+            }
+        }
+
+        visit::walk_expr(self, expr)
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+
+use core::fmt::Display;
+
+use crate::ast::{BinaryOp, Block, Expr, ExprKind, Item, Program, Span, Stmt, StmtKind};
+use crate::module::Modules;
+use crate::resolve::Resolution;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitError {
+    pub message: String,
+    /// The read of the variable
+    pub span: Span,
+    /// Declaration of the variable
+    pub declared: Span,
+}
+
+impl Display for InitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Reports reads of variables declared without initializer on paths where no value was assigned to them yet.
+///
+/// The top-level statements are checked first, starting with nothing assigned. Functions run after them, so each
+/// function body starts with the top-level variables the statements definitely assigned.
+pub fn check(program: &Program, resolution: &Resolution) -> Vec<InitError> {
+    let mut flow = Flow { resolution, declared: HashSet::new(), errors: vec![] };
+
+    let mut top_level = Some(HashSet::new());
+    for item in &program.items {
+        if let Item::Stmt(stmt) = item {
+            flow.stmt(stmt, &mut top_level);
+        }
+    }
+
+    // Past a top-level return nothing runs, functions included
+    let globals = std::mem::take(&mut flow.declared);
+    let top_level = top_level.unwrap_or_else(|| globals.clone());
+    for item in &program.items {
+        if let Item::Func(func) = item {
+            flow.declared = globals.clone();
+            flow.block(&func.body, &mut Some(top_level.clone()));
+        }
+    }
+
+    flow.errors.sort_by_key(|e| (e.span.file, e.span.line, e.span.column));
+    flow.errors
+}
+
+/// Checks every loaded file, `resolution` being the result of `resolve_modules` on the same files.
+pub fn check_modules(modules: &Modules, resolution: &Resolution) -> Vec<InitError> {
+    modules.order.iter().flat_map(|&file| check(&modules.files[file].program, resolution)).collect()
+}
+
+/// Variables definitely assigned at a point of the program, by declaration span.
+/// `None` past a `return`, where nothing runs and so everything counts as assigned.
+type Assigned = Option<HashSet<Span>>;
+
+fn merge(a: Assigned, b: Assigned) -> Assigned {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.intersection(&b).copied().collect()),
+        (Some(a), None) | (None, Some(a)) => Some(a),
+        (None, None) => None,
+    }
+}
+
+struct Flow<'r> {
+    resolution: &'r Resolution,
+    /// Variables of the body being checked that were declared without initializer
+    declared: HashSet<Span>,
+    errors: Vec<InitError>,
+}
+
+impl Flow<'_> {
+    fn block(&mut self, block: &Block, assigned: &mut Assigned) {
+        for stmt in &block.stmts {
+            self.stmt(stmt, assigned);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, assigned: &mut Assigned) {
+        match &stmt.kind {
+            StmtKind::VarDecl { name_span, value, .. } => match value {
+                Some(value) => self.expr(value, assigned),
+                None => {
+                    self.declared.insert(*name_span);
+                }
+            },
+            StmtKind::If { condition, then_branch, else_branch } => {
+                self.expr(condition, assigned);
+
+                let mut then_assigned = assigned.clone();
+                self.block(then_branch, &mut then_assigned);
+                let mut else_assigned = assigned.clone();
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch, &mut else_assigned);
+                }
+
+                *assigned = merge(then_assigned, else_assigned);
+            }
+            StmtKind::While { condition, body } => {
+                self.expr(condition, assigned);
+
+                // The body might not run at all, and only a return leaves `while (true)`
+                let mut body_assigned = assigned.clone();
+                self.block(body, &mut body_assigned);
+                if matches!(condition.kind, ExprKind::Boolean(true)) {
+                    *assigned = None;
+                }
+            }
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expr(value, assigned);
+                }
+                *assigned = None;
+            }
+            StmtKind::Block(block) => self.block(block, assigned),
+            StmtKind::Expr(expr) => self.expr(expr, assigned),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, assigned: &mut Assigned) {
+        match &expr.kind {
+            ExprKind::Integer(_) | ExprKind::String(_) | ExprKind::Rune(_) | ExprKind::Boolean(_) => {}
+            ExprKind::Identifier(name) => self.read(name, expr.span, assigned),
+            ExprKind::Unary { operand, .. } | ExprKind::Postfix { operand, .. } => self.expr(operand, assigned),
+            ExprKind::Binary { op, left, right } => {
+                self.expr(left, assigned);
+
+                // The right operand of `&&` and `||` might not be evaluated
+                if matches!(op, BinaryOp::And | BinaryOp::Or) {
+                    let mut right_assigned = assigned.clone();
+                    self.expr(right, &mut right_assigned);
+                } else {
+                    self.expr(right, assigned);
+                }
+            }
+            ExprKind::Assign { target, value } => {
+                self.expr(value, assigned);
+                match &target.kind {
+                    ExprKind::Identifier(_) => {
+                        let declaration = self.resolution.definition(target.span).map(|symbol| symbol.span);
+                        if let (Some(assigned), Some(declaration)) = (assigned.as_mut(), declaration) {
+                            assigned.insert(declaration);
+                        }
+                    }
+                    _ => self.expr(target, assigned),
+                }
+            }
+            ExprKind::Call { callee, args } => {
+                self.expr(callee, assigned);
+                for arg in args {
+                    self.expr(arg, assigned);
+                }
+            }
+            ExprKind::Field { object, .. } => self.expr(object, assigned),
+            ExprKind::ComplexLiteral { fields, .. } => {
+                for field in fields {
+                    self.expr(&field.value, assigned);
+                }
+            }
+            ExprKind::List(values) => {
+                for value in values {
+                    self.expr(value, assigned);
+                }
+            }
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.expr(key, assigned);
+                    self.expr(value, assigned);
+                }
+            }
+        }
+    }
+
+    fn read(&mut self, name: &str, span: Span, assigned: &mut Assigned) {
+        let declaration = match self.resolution.definition(span) {
+            Some(symbol) if self.declared.contains(&symbol.span) => symbol.span,
+            _ => return,
+        };
+
+        if let Some(assigned) = assigned {
+            if assigned.insert(declaration) {
+                // Inserted so that later reads on the same path aren't reported again
+                self.errors.push(InitError { message: format!("{} might not be assigned when it's read here", name), span, declared: declaration });
+            }
+        }
+    }
+}
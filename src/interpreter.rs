@@ -0,0 +1,673 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use core::fmt::Display;
+
+use crate::ast::{BinaryOp, Block, CommandDecl, ComplexDecl, DomainDecl, DomainField, DomainKind, Expr, ExprKind, FuncDecl, Item, PostfixOp, Program, Span, Stmt, StmtKind};
+use crate::command::{self, Invocation, Runner};
+use crate::debug::{self, Hook, StackFrame};
+use crate::limits::{Budget, Limits};
+use crate::module::Modules;
+use crate::resolve::{Resolution, SymbolId};
+use crate::secret;
+use crate::stdlib;
+use crate::value::{self, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    /// The expression or statement that failed
+    pub span: Span,
+    /// Spans of the calls that led to the failure, innermost first
+    pub calls: Vec<Span>,
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Runs a single file: its top-level statements in order, then `main` if it declares one.
+/// Returns the value returned by `main`, void without one. Commands run as local processes.
+pub fn run(program: &Program, resolution: &Resolution) -> Result<Value, RuntimeError> {
+    run_with(program, resolution, &mut command::Local::default())
+}
+
+/// Like `run`, with commands carried out by `runner`.
+pub fn run_with(program: &Program, resolution: &Resolution, runner: &mut dyn Runner) -> Result<Value, RuntimeError> {
+    run_limited(program, resolution, runner, Limits::default())
+}
+
+/// Like `run_with`, failing once the program goes over one of `limits`.
+pub fn run_limited(program: &Program, resolution: &Resolution, runner: &mut dyn Runner, limits: Limits) -> Result<Value, RuntimeError> {
+    let mut interpreter = Interpreter::new(resolution, runner);
+    interpreter.budget = Budget::new(limits);
+    interpreter.declare(program);
+    interpreter.execute(&program.items)?;
+    interpreter.call_main(program)
+}
+
+/// Runs every loaded file, imported files first, then `main` of the root file.
+/// `resolution` is the result of `resolve_modules` on the same files.
+pub fn run_modules(modules: &Modules, resolution: &Resolution) -> Result<Value, RuntimeError> {
+    run_modules_with(modules, resolution, &mut command::Local::default())
+}
+
+/// Like `run_modules`, with commands carried out by `runner`.
+pub fn run_modules_with(modules: &Modules, resolution: &Resolution, runner: &mut dyn Runner) -> Result<Value, RuntimeError> {
+    run_modules_limited(modules, resolution, runner, Limits::default())
+}
+
+/// Like `run_modules_with`, failing once the program goes over one of `limits`.
+pub fn run_modules_limited(modules: &Modules, resolution: &Resolution, runner: &mut dyn Runner, limits: Limits) -> Result<Value, RuntimeError> {
+    let mut interpreter = Interpreter::new(resolution, runner);
+    interpreter.budget = Budget::new(limits);
+    for &file in &modules.order {
+        interpreter.declare(&modules.files[file].program);
+    }
+    for &file in &modules.order {
+        interpreter.execute(&modules.files[file].program.items)?;
+    }
+    interpreter.call_main(&modules.files[0].program)
+}
+
+/// Like `run_modules_with`, stopping where `hook` asks to before statements.
+pub fn debug_modules_with(modules: &Modules, resolution: &Resolution, runner: &mut dyn Runner, hook: &mut dyn Hook) -> Result<Value, RuntimeError> {
+    let mut interpreter = Interpreter::new(resolution, runner);
+    interpreter.hook = Some(hook);
+    for &file in &modules.order {
+        interpreter.declare(&modules.files[file].program);
+    }
+    for &file in &modules.order {
+        interpreter.execute(&modules.files[file].program.items)?;
+    }
+    interpreter.call_main(&modules.files[0].program)
+}
+
+/// What a REPL session keeps from one input to the next: the values of its globals and domain declarations, and the
+/// imported files already run. Declarations are known by path and span, which stay the same as inputs are appended to
+/// the root file while symbol ids and file ids may change.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    globals: HashMap<(PathBuf, Span), Option<Value>>,
+    domain_values: HashMap<(PathBuf, Span), Value>,
+    imported: HashSet<PathBuf>,
+}
+
+/// Runs the items of the root file from index `first` on, for a REPL whose inputs make up the root file, on top of what
+/// `session` kept of the earlier ones. Imported files run the first time they're imported and `main` isn't called.
+/// Returns the value of the last item of the root file when it's an expression statement, void otherwise.
+///
+/// Values assigned before a failure are kept, like the commands that ran, but the variables the failed input declared
+/// are forgotten with it.
+pub fn run_session_with(modules: &Modules, resolution: &Resolution, runner: &mut dyn Runner, session: &mut Session, first: usize) -> Result<Value, RuntimeError> {
+    let mut interpreter = Interpreter::new(resolution, runner);
+    for &file in &modules.order {
+        interpreter.declare(&modules.files[file].program);
+    }
+
+    let place = |span: Span| (modules.path(span.file).to_path_buf(), Span { file: 0, ..span });
+    let ids: HashMap<(PathBuf, Span), SymbolId> = interpreter.declarations.iter().map(|(&span, &id)| (place(span), id)).collect();
+    interpreter.globals = session.globals.iter().filter_map(|(place, value)| Some((*ids.get(place)?, value.clone()))).collect();
+    interpreter.domain_values = session.domain_values.iter().filter_map(|(place, value)| Some((*ids.get(place)?, value.clone()))).collect();
+
+    let result = run_input(&mut interpreter, modules, &mut session.imported, first);
+
+    let root = modules.path(0);
+    let kept = |place: &(PathBuf, Span), known: bool| result.is_ok() || place.0 != root || known;
+    for (&id, value) in &interpreter.globals {
+        let place = place(resolution.symbols[id].span);
+        if kept(&place, session.globals.contains_key(&place)) {
+            session.globals.insert(place, value.clone());
+        }
+    }
+    for (&id, value) in &interpreter.domain_values {
+        let place = place(resolution.symbols[id].span);
+        if kept(&place, session.domain_values.contains_key(&place)) {
+            session.domain_values.insert(place, value.clone());
+        }
+    }
+    result
+}
+
+fn run_input<'ast>(interpreter: &mut Interpreter<'ast, '_>, modules: &'ast Modules, imported: &mut HashSet<PathBuf>, first: usize) -> Result<Value, RuntimeError> {
+    // The root file is last in the order
+    for &file in modules.order.iter().filter(|&&file| file != 0) {
+        if !imported.contains(modules.path(file)) {
+            interpreter.execute(&modules.files[file].program.items)?;
+            imported.insert(modules.path(file).to_path_buf());
+        }
+    }
+
+    let items = modules.files[0].program.items.get(first..).unwrap_or_default();
+    match items.split_last() {
+        Some((Item::Stmt(Stmt { kind: StmtKind::Expr(expr), .. }), rest)) => {
+            interpreter.execute(rest)?;
+            interpreter.eval(expr)
+        }
+        _ => {
+            interpreter.execute(items)?;
+            Ok(Value::Void)
+        }
+    }
+}
+
+/// What a statement does to the rest of its block.
+enum Flow {
+    Normal,
+    Return(Value),
+}
+
+/// Variables of one function call. Symbols are unique per declaration, so a call needs no nested scopes.
+struct Frame<'ast> {
+    /// Function or command called
+    name: &'ast str,
+    locals: HashMap<SymbolId, Option<Value>>,
+    /// Span of the call expression, `None` for `main`
+    call: Option<Span>,
+    /// Statement being run
+    position: Span,
+}
+
+struct Interpreter<'ast, 'r> {
+    resolution: &'ast Resolution,
+    runner: &'r mut dyn Runner,
+    /// Span of a declared name to its symbol
+    declarations: HashMap<Span, SymbolId>,
+    functions: HashMap<SymbolId, &'ast FuncDecl>,
+    commands: HashMap<SymbolId, &'ast CommandDecl>,
+    complexes: HashMap<SymbolId, &'ast ComplexDecl>,
+    domains: HashMap<SymbolId, &'ast DomainDecl>,
+    /// Domain declarations are evaluated the first time they're used
+    domain_values: HashMap<SymbolId, Value>,
+    evaluating: HashSet<SymbolId>,
+    /// `None` until the variable is given a value
+    globals: HashMap<SymbolId, Option<Value>>,
+    frames: Vec<Frame<'ast>>,
+    /// Top-level statement being run
+    position: Span,
+    hook: Option<&'r mut dyn Hook>,
+    budget: Budget,
+}
+
+impl<'ast, 'r> Interpreter<'ast, 'r> {
+    fn new(resolution: &'ast Resolution, runner: &'r mut dyn Runner) -> Self {
+        let declarations = resolution.symbols.iter().enumerate().map(|(id, symbol)| (symbol.span, id)).collect();
+        Interpreter {
+            resolution,
+            runner,
+            declarations,
+            functions: HashMap::new(),
+            commands: HashMap::new(),
+            complexes: HashMap::new(),
+            domains: HashMap::new(),
+            domain_values: HashMap::new(),
+            evaluating: HashSet::new(),
+            globals: HashMap::new(),
+            frames: vec![],
+            position: Span::default(),
+            hook: None,
+            budget: Budget::new(Limits::default()),
+        }
+    }
+
+    fn declare(&mut self, program: &'ast Program) {
+        for item in &program.items {
+            match item {
+                Item::Func(func) => {
+                    if let Some(&id) = self.declarations.get(&func.name_span) {
+                        self.functions.insert(id, func);
+                    }
+                }
+                Item::Command(command) => {
+                    if let Some(&id) = self.declarations.get(&command.name_span) {
+                        self.commands.insert(id, command);
+                    }
+                }
+                Item::Complex(complex) => {
+                    if let Some(&id) = self.declarations.get(&complex.name_span) {
+                        self.complexes.insert(id, complex);
+                    }
+                }
+                Item::Domain(domain) => {
+                    if let Some(&id) = self.declarations.get(&domain.name_span) {
+                        self.domains.insert(id, domain);
+                    }
+                }
+                Item::Import(_) | Item::Stmt(_) => {}
+            }
+        }
+    }
+
+    fn execute(&mut self, items: &'ast [Item]) -> Result<(), RuntimeError> {
+        for item in items {
+            match item {
+                Item::Stmt(stmt) => {
+                    if let Flow::Return(_) = self.exec_stmt(stmt)? {
+                        return Err(self.error("return is only allowed inside a function", stmt.span));
+                    }
+                }
+                // Evaluated now so that their errors show up even if nothing uses them
+                Item::Domain(domain) => {
+                    if let Some(&id) = self.declarations.get(&domain.name_span) {
+                        self.domain_value(id, domain.name_span)?;
+                    }
+                }
+                Item::Import(_) | Item::Func(_) | Item::Command(_) | Item::Complex(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn call_main(&mut self, program: &'ast Program) -> Result<Value, RuntimeError> {
+        let main = program.items.iter().find_map(|item| match item {
+            Item::Func(func) if func.name == "main" => Some(func),
+            _ => None,
+        });
+
+        match main {
+            Some(main) => self.call(main, vec![], None),
+            None => Ok(Value::Void),
+        }
+    }
+
+    fn error(&self, message: &str, span: Span) -> RuntimeError {
+        let calls = self.frames.iter().rev().filter_map(|frame| frame.call).collect();
+        RuntimeError { message: message.to_string(), span, calls }
+    }
+
+    /// Charges the heap budget for `value`, just built by the expression at `span`.
+    fn allocate(&mut self, value: Value, span: Span) -> Result<Value, RuntimeError> {
+        match self.budget.allocate(&value) {
+            Ok(()) => Ok(value),
+            Err(message) => Err(self.error(&message, span)),
+        }
+    }
+
+    /// Charges the budget for a call made at `span`, `None` for `main` which isn't counted.
+    fn enter(&mut self, call: Option<Span>) -> Result<(), RuntimeError> {
+        match call {
+            Some(span) => self.budget.enter().map_err(|message| self.error(&message, span)),
+            None => Ok(()),
+        }
+    }
+
+    fn leave(&mut self, call: Option<Span>) {
+        if call.is_some() {
+            self.budget.leave();
+        }
+    }
+
+    fn call(&mut self, func: &'ast FuncDecl, args: Vec<Value>, call: Option<Span>) -> Result<Value, RuntimeError> {
+        if args.len() != func.params.len() {
+            return Err(self.error(&format!("Function {} expects {} arguments but got {}", func.name, func.params.len(), args.len()), call.unwrap_or(func.name_span)));
+        }
+
+        self.enter(call)?;
+        let mut locals = HashMap::new();
+        for (param, arg) in func.params.iter().zip(args) {
+            if let Some(&id) = self.declarations.get(&param.name_span) {
+                locals.insert(id, Some(arg));
+            }
+        }
+
+        self.frames.push(Frame { name: &func.name, locals, call, position: func.name_span });
+        let flow = self.exec_block(&func.body);
+        self.frames.pop();
+        self.leave(call);
+
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            Flow::Normal => Ok(Value::Void),
+        }
+    }
+
+    /// Evaluates the fields of `command` with its parameters bound to `args`, then runs the resulting invocation.
+    fn run_command(&mut self, command: &'ast CommandDecl, args: Vec<Value>, call: Span) -> Result<Value, RuntimeError> {
+        if args.len() != command.params.len() {
+            return Err(self.error(&format!("Command {} expects {} arguments but got {}", command.name, command.params.len(), args.len()), call));
+        }
+
+        self.enter(Some(call))?;
+        let mut locals = HashMap::new();
+        for (param, arg) in command.params.iter().zip(args) {
+            if let Some(&id) = self.declarations.get(&param.name_span) {
+                locals.insert(id, Some(arg));
+            }
+        }
+
+        self.frames.push(Frame { name: &command.name, locals, call: Some(call), position: command.name_span });
+        let settings = self.eval_fields(&command.fields).and_then(|fields| self.allocate(Value::Complex { name: command.name.clone(), fields }, command.span));
+        self.frames.pop();
+        self.leave(Some(call));
+
+        let settings = settings?;
+        let invocation = Invocation::from_settings(&command.name, &settings).map_err(|message| self.error(&message, call))?;
+        let outcome = self.runner.run(&invocation).map_err(|message| self.error(&message, call))?;
+        self.allocate(outcome.to_value(), call)
+    }
+
+    fn eval_fields(&mut self, fields: &'ast [DomainField]) -> Result<Vec<(String, Value)>, RuntimeError> {
+        let mut values = Vec::with_capacity(fields.len());
+        for field in fields {
+            values.push((field.name.clone(), self.eval(&field.value)?));
+        }
+        Ok(values)
+    }
+
+    fn exec_block(&mut self, block: &'ast Block) -> Result<Flow, RuntimeError> {
+        for stmt in &block.stmts {
+            if let Flow::Return(value) = self.exec_stmt(stmt)? {
+                return Ok(Flow::Return(value));
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec_stmt(&mut self, stmt: &'ast Stmt) -> Result<Flow, RuntimeError> {
+        if !matches!(stmt.kind, StmtKind::Block(_)) {
+            match self.frames.last_mut() {
+                Some(frame) => frame.position = stmt.span,
+                None => self.position = stmt.span,
+            }
+            if self.hook.is_some() {
+                self.pause(stmt.span)?;
+            }
+        }
+
+        match &stmt.kind {
+            StmtKind::VarDecl { name_span, value, .. } => {
+                let value = match value {
+                    Some(value) => Some(self.eval(value)?),
+                    None => None,
+                };
+                if let Some(&id) = self.declarations.get(name_span) {
+                    match self.frames.last_mut() {
+                        Some(frame) => frame.locals.insert(id, value),
+                        None => self.globals.insert(id, value),
+                    };
+                }
+                Ok(Flow::Normal)
+            }
+            StmtKind::If { condition, then_branch, else_branch } => {
+                if self.condition(condition)? {
+                    self.exec_block(then_branch)
+                } else if let Some(else_branch) = else_branch {
+                    self.exec_stmt(else_branch)
+                } else {
+                    Ok(Flow::Normal)
+                }
+            }
+            StmtKind::While { condition, body } => {
+                while self.condition(condition)? {
+                    if let Flow::Return(value) = self.exec_block(body)? {
+                        return Ok(Flow::Return(value));
+                    }
+                    self.budget.step().map_err(|message| self.error(&message, stmt.span))?;
+                }
+                Ok(Flow::Normal)
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Void,
+                };
+                Ok(Flow::Return(value))
+            }
+            StmtKind::Block(block) => self.exec_block(block),
+            StmtKind::Expr(expr) => {
+                self.eval(expr)?;
+                Ok(Flow::Normal)
+            }
+        }
+    }
+
+    /// Lets the hook stop the program before the statement at `span`.
+    fn pause(&mut self, span: Span) -> Result<(), RuntimeError> {
+        let depth = self.frames.len();
+        let reason = match self.hook.as_mut().and_then(|hook| hook.should_stop(span, depth)) {
+            Some(reason) => reason,
+            None => return Ok(()),
+        };
+
+        let mut stack: Vec<StackFrame> =
+            self.frames.iter().rev().map(|frame| StackFrame { name: frame.name.to_string(), span: frame.position, locals: self.variables(&frame.locals) }).collect();
+        stack.push(StackFrame { name: debug::TOP_LEVEL.to_string(), span: self.position, locals: vec![] });
+        let globals = self.variables(&self.globals);
+
+        let resumed = self.hook.as_mut().expect("hook").stopped(reason, &stack, &globals);
+        resumed.map_err(|message| self.error(&message, span))
+    }
+
+    /// Names and values of variables, in declaration order.
+    fn variables(&self, variables: &HashMap<SymbolId, Option<Value>>) -> Vec<(String, Option<Value>)> {
+        let mut ids: Vec<&SymbolId> = variables.keys().collect();
+        ids.sort_by_key(|&&id| {
+            let span = self.resolution.symbols[id].span;
+            (span.file, span.line, span.column)
+        });
+        ids.into_iter().map(|id| (self.resolution.symbols[*id].name.clone(), variables[id].clone())).collect()
+    }
+
+    fn condition(&mut self, condition: &'ast Expr) -> Result<bool, RuntimeError> {
+        match self.eval(condition)? {
+            Value::Boolean(value) => Ok(value),
+            other => Err(self.error(&format!("Condition expects boolean but got {}", other.type_name()), condition.span)),
+        }
+    }
+
+    fn eval(&mut self, expr: &'ast Expr) -> Result<Value, RuntimeError> {
+        match &expr.kind {
+            ExprKind::Integer(value) => Ok(Value::Integer(*value)),
+            ExprKind::String(value) => Ok(Value::String(value.clone())),
+            ExprKind::Rune(value) => Ok(Value::Rune(*value)),
+            ExprKind::Boolean(value) => Ok(Value::Boolean(*value)),
+            ExprKind::Identifier(name) => self.read(name, expr.span),
+            ExprKind::Unary { op, operand } => {
+                let operand = self.eval(operand)?;
+                operand.unary(*op).map_err(|message| self.error(&message, expr.span))
+            }
+            ExprKind::Postfix { op, operand } => {
+                let old = self.eval(operand)?;
+                let step = match op {
+                    PostfixOp::Increment => BinaryOp::Plus,
+                    PostfixOp::Decrement => BinaryOp::Minus,
+                };
+                let new = match old {
+                    Value::Integer(_) => old.binary(step, &Value::Integer(1)).map_err(|message| self.error(&message, expr.span))?,
+                    _ => return Err(self.error(&format!("Operator {} can't be applied to {}", op.as_str(), old.type_name()), expr.span)),
+                };
+                self.assign(operand, new)?;
+                Ok(old)
+            }
+            ExprKind::Binary { op: op @ (BinaryOp::And | BinaryOp::Or), left, right } => {
+                let left = self.eval(left)?;
+                match (op, &left) {
+                    (BinaryOp::And, Value::Boolean(false)) | (BinaryOp::Or, Value::Boolean(true)) => Ok(left),
+                    _ => {
+                        let right = self.eval(right)?;
+                        let value = left.binary(*op, &right).map_err(|message| self.error(&message, expr.span))?;
+                        self.allocate(value, expr.span)
+                    }
+                }
+            }
+            ExprKind::Binary { op, left, right } => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                let value = left.binary(*op, &right).map_err(|message| self.error(&message, expr.span))?;
+                self.allocate(value, expr.span)
+            }
+            ExprKind::Assign { target, value } => {
+                let value = self.eval(value)?;
+                self.assign(target, value.clone())?;
+                Ok(value)
+            }
+            ExprKind::Call { callee, args } => {
+                let (name, id) = match &callee.kind {
+                    ExprKind::Identifier(name) => (name, self.resolution.uses.get(&callee.span).copied()),
+                    _ => return Err(self.error("Only functions can be called", callee.span)),
+                };
+                let func = id.and_then(|id| self.functions.get(&id)).copied();
+                let command = id.and_then(|id| self.commands.get(&id)).copied();
+                if let (None, Some(builtin)) = (id, stdlib::get(name)) {
+                    let mut values = Vec::with_capacity(args.len());
+                    for arg in args {
+                        values.push(self.eval(arg)?);
+                    }
+                    let value = builtin.call(&values).map_err(|message| self.error(&message, expr.span))?;
+                    return self.allocate(value, expr.span);
+                }
+                if func.is_none() && command.is_none() {
+                    return Err(self.error(&format!("{} is not a function", name), callee.span));
+                }
+
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                match (func, command) {
+                    (Some(func), _) => self.call(func, values, Some(expr.span)),
+                    (_, Some(command)) => self.run_command(command, values, expr.span),
+                    (None, None) => unreachable!(),
+                }
+            }
+            ExprKind::Field { object, field, field_span } => {
+                let object = self.eval(object)?;
+                match object.field(field) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(self.error(&format!("{} has no field {}", object.type_name(), field), *field_span)),
+                }
+            }
+            ExprKind::ComplexLiteral { name, name_span, fields } => {
+                let mut values = Vec::with_capacity(fields.len());
+                for field in fields {
+                    values.push((field.name.clone(), self.eval(&field.value)?));
+                }
+
+                // Fields are kept in declaration order so that equal values compare equal
+                let decl = self.resolution.uses.get(name_span).and_then(|id| self.complexes.get(id));
+                if let Some(decl) = decl {
+                    values.sort_by_key(|(field, _)| decl.fields.iter().position(|f| &f.name == field));
+                }
+                self.allocate(Value::Complex { name: name.clone(), fields: values }, expr.span)
+            }
+            ExprKind::List(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.eval(element)?);
+                }
+                self.allocate(Value::List(values), expr.span)
+            }
+            ExprKind::Map(entries) => {
+                let mut values = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    let key = self.eval(key)?;
+                    let value = self.eval(value)?;
+                    value::put(&mut values, key, value);
+                }
+                self.allocate(Value::Map(values), expr.span)
+            }
+        }
+    }
+
+    fn read(&mut self, name: &str, span: Span) -> Result<Value, RuntimeError> {
+        let id = match self.resolution.uses.get(&span) {
+            Some(&id) => id,
+            None if stdlib::get(name).is_some() => return Err(self.error(&format!("Builtin {} can only be called", name), span)),
+            None => return Err(self.error(&format!("Undefined name {}", name), span)),
+        };
+
+        if self.domains.contains_key(&id) {
+            return self.domain_value(id, span);
+        }
+        if self.functions.contains_key(&id) {
+            return Err(self.error(&format!("Function {} can only be called", name), span));
+        }
+        if self.commands.contains_key(&id) {
+            return Err(self.error(&format!("Command {} can only be called", name), span));
+        }
+
+        match self.slot(id) {
+            Some(Some(value)) => Ok(value.clone()),
+            Some(None) => Err(self.error(&format!("{} is read before it's given a value", name), span)),
+            None => Err(self.error(&format!("{} is read before it's declared", name), span)),
+        }
+    }
+
+    fn slot(&mut self, id: SymbolId) -> Option<&mut Option<Value>> {
+        match self.frames.last_mut().filter(|frame| frame.locals.contains_key(&id)) {
+            Some(frame) => frame.locals.get_mut(&id),
+            None => self.globals.get_mut(&id),
+        }
+    }
+
+    /// Stores `value` into a variable or a field of one, `target` being an identifier or a chain of field accesses.
+    fn assign(&mut self, target: &'ast Expr, value: Value) -> Result<(), RuntimeError> {
+        let mut path = vec![];
+        let mut root = target;
+        while let ExprKind::Field { object, field, field_span } = &root.kind {
+            path.push((field.as_str(), *field_span));
+            root = object;
+        }
+        path.reverse();
+
+        let (name, id) = match &root.kind {
+            ExprKind::Identifier(name) => match self.resolution.uses.get(&root.span) {
+                Some(&id) if !self.domains.contains_key(&id) && !self.functions.contains_key(&id) && !self.commands.contains_key(&id) => (name, id),
+                _ => return Err(self.error(&format!("{} can't be assigned to", name), root.span)),
+            },
+            _ => return Err(self.error("Only variables and their fields can be assigned to", target.span)),
+        };
+
+        let stored = match self.slot(id) {
+            Some(slot) => store(slot, name, &path, root.span, value),
+            None => Err((format!("{} is assigned before it's declared", name), root.span)),
+        };
+        stored.map_err(|(message, span)| self.error(&message, span))
+    }
+
+    fn domain_value(&mut self, id: SymbolId, span: Span) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.domain_values.get(&id) {
+            return Ok(value.clone());
+        }
+
+        let domain = self.domains[&id];
+        if !self.evaluating.insert(id) {
+            return Err(self.error(&format!("{} {} depends on itself", domain.kind, domain.name), span));
+        }
+
+        let fields = self.eval_fields(&domain.fields);
+        self.evaluating.remove(&id);
+        let fields = fields?;
+
+        let mut value = self.allocate(Value::Complex { name: domain.kind.keyword().to_string(), fields }, domain.span)?;
+        if domain.kind == DomainKind::Secret {
+            value = secret::load(&domain.name, &value, self.runner).map_err(|message| self.error(&message, domain.name_span))?;
+        }
+        self.domain_values.insert(id, value.clone());
+        Ok(value)
+    }
+}
+
+/// Stores `value` into `slot`, or into the field at the end of `path` within it, `path` being in access order.
+pub(crate) fn store(slot: &mut Option<Value>, name: &str, path: &[(&str, Span)], span: Span, value: Value) -> Result<(), (String, Span)> {
+    if path.is_empty() {
+        *slot = Some(value);
+        return Ok(());
+    }
+
+    let mut current = match slot {
+        Some(current) => current,
+        None => return Err((format!("{} is read before it's given a value", name), span)),
+    };
+    for &(field, field_span) in path {
+        let type_name = current.type_name().to_string();
+        current = match current.field_mut(field) {
+            Some(next) => next,
+            None => return Err((format!("{} has no field {}", type_name, field), field_span)),
+        };
+    }
+    *current = value;
+    Ok(())
+}
@@ -1,7 +1,6 @@
 use crate::token;
 use crate::char_utils;
 use crate::number_fsm;
-use substring::Substring;
 use crate::token::Token;
 
 #[derive(Debug)]
@@ -28,6 +27,10 @@ fn strip_final_newline(s: String) -> String {
 }
 
 impl Lexer {
+    pub fn new(input: &str) -> Self {
+        Lexer { input: input.to_string(), position: 0, line: 0, column: 0 }
+    }
+
     pub fn all_tokens(& mut self) -> Vec<Token> {
         self.input = strip_final_newline(self.input.clone());
         self.position = 0;
@@ -43,10 +46,12 @@ impl Lexer {
 
         tokens.push(token);
 
-        return tokens;
+        tokens
     }
 
     fn next_token(&mut self) -> Token {
+        self.skip_whitespaces_and_new_lines();
+
         if self.position >= self.input.len() {
             return Token {
                 token_type: token::EOF.to_string(),
@@ -56,9 +61,11 @@ impl Lexer {
             };
         }
 
-        self.skip_whitespaces_and_new_lines();
+        let character = self.input[self.position..].chars().next().expect("character before the end of the input");
 
-        let character = self.input.as_bytes()[self.position] as char;
+        if character == '/' && self.input.as_bytes().get(self.position + 1) == Some(&b'/') {
+            return self.recognize_comment();
+        }
 
         if char_utils::is_letter(character) {
             return self.recognize_identifier();
@@ -92,7 +99,11 @@ impl Lexer {
             return self.recognize_bracket();
         }
 
-        panic!("Unknown character {} at {}:{}", character, self.line, self.column)
+        // Reported by the parser, lexing goes on after the character
+        let token = Token { token_type: token::UNKNOWN.to_string(), value: character.to_string(), line: self.line, column: self.column };
+        self.position += character.len_utf8();
+        self.column += 1;
+        token
     }
 
     fn skip_whitespaces_and_new_lines(&mut self) {
@@ -108,24 +119,21 @@ impl Lexer {
     }
 
     fn recognize_identifier(& mut self) -> Token {
-        let mut identifier = "".to_string();
         let line = self.line;
         let column = self.column;
-        let mut position = self.position;
-    
-        while position < self.input.len() {
-            let character = self.input.as_bytes()[position] as char;
-      
+        let start = self.position;
+        let mut end = self.input.len();
+
+        for (offset, character) in self.input[start..].char_indices() {
             if !(char_utils::is_letter(character) || char_utils::is_digit(character) || character == '_') {
-            break;
+                end = start + offset;
+                break;
             }
-        
-            identifier += &character.to_string();
-            position += 1;
         }
-    
-        self.position += identifier.len();
-        self.column += identifier.len();
+
+        let identifier = self.input[start..end].to_string();
+        self.position = end;
+        self.column += identifier.chars().count();
 
         if char_utils::is_identifier_reserved(&identifier) {
             return Token {
@@ -144,7 +152,7 @@ impl Lexer {
             }
         }
     
-        return Token {
+        Token {
             token_type: token::IDENTIFIER.to_string(),
             value: identifier,
             line,
@@ -157,10 +165,8 @@ impl Lexer {
         let column = self.column;
 
         let fsm = number_fsm::NumberFSM::new();
-        let fsm_input = self.input.substring(self.position, self.input.len() - 1);
-
-        let run_result = fsm.run(fsm_input);
-        return if run_result.recognized {
+        let run_result = fsm.run(&self.input[self.position..]);
+        if run_result.recognized {
             self.position += run_result.value.len();
             self.column += run_result.value.len();
 
@@ -171,36 +177,59 @@ impl Lexer {
                 column,
             }
         } else {
+            self.position += run_result.value.len();
+            self.column += run_result.value.len();
+
             Token {
                 token_type: token::UNKNOWN.to_string(),
-                value: "".to_string(),
+                value: run_result.value,
                 line,
                 column,
             }
         }
     }
 
+    fn recognize_comment(& mut self) -> Token {
+        let line = self.line;
+        let column = self.column;
+        let start = self.position;
+
+        while self.position < self.input.len() && !char_utils::is_new_line(self.input.as_bytes()[self.position] as char) {
+            self.position += 1;
+        }
+
+        // Comment text without the leading '//' and trailing whitespace
+        let value = self.input[start + 2..self.position].trim_end().to_string();
+        self.column += self.input[start..self.position].chars().count();
+
+        Token {
+            token_type: token::COMMENT.to_string(),
+            value,
+            line,
+            column,
+        }
+    }
+
     fn recognize_string(& mut self) -> Token {
-        let mut string_literal = "\"".to_string();
         let line = self.line;
         let column = self.column;
-        let mut position = self.position + 1;
-    
-        while position < self.input.len()  {
-            let character = self.input.as_bytes()[position] as char;
-            string_literal += &character.to_string();
+        let start = self.position;
+        let mut end = self.input.len();
+        let mut closed = false;
+
+        for (offset, character) in self.input[start + 1..].char_indices() {
             if character == '"' || char_utils::is_new_line(character) {
+                end = start + 1 + offset + character.len_utf8();
+                closed = character == '"';
                 break;
             }
-        
-            position += 1;
         }
-        
 
-        self.position += string_literal.len();
-        self.column += string_literal.len();
+        let string_literal = &self.input[start..end];
+        self.position = end;
+        self.column += string_literal.chars().count();
 
-        return if string_literal.as_bytes()[string_literal.len() - 1] as char != '"' {
+        if !closed {
             Token {
                 token_type: token::UNKNOWN.to_string(),
                 value: "".to_string(),
@@ -221,13 +250,14 @@ impl Lexer {
         let line = self.line;
         let column = self.column;
         let position = self.position;
-        let char_after_quote = self.input.as_bytes()[self.position + 1] as char;
-        let char_that_must_be_quote = self.input.as_bytes()[self.position + 2] as char;
+        let mut characters = self.input[position + 1..].chars();
+        let char_after_quote = characters.next().unwrap_or(' ');
+        let char_that_must_be_quote = characters.next().unwrap_or(' ');
 
-        self.position += 3;
+        self.position = (position + 1 + char_after_quote.len_utf8() + char_that_must_be_quote.len_utf8()).min(self.input.len());
         self.column += 3;
 
-        return if self.input.as_bytes()[position + 2] as char != '\'' {
+        if char_that_must_be_quote != '\'' {
             Token {
                 token_type: token::UNKNOWN.to_string(),
                 value: format!("{}{}{}", '\'', char_after_quote, char_that_must_be_quote),
@@ -259,7 +289,7 @@ impl Lexer {
             return self.recognize_logical_operator();
         }
 
-        panic!("Unknown operator type {}", character)
+        unreachable!("{} is an operator of no known type", character)
     }
 
     fn recognize_comparison_operator(& mut self) -> Token {
@@ -288,7 +318,7 @@ impl Lexer {
 
         match character {
             '>' => {
-                return if is_lookahead_equal_symbol {
+                if is_lookahead_equal_symbol {
                     Token {
                         token_type: token::RT_EQ.to_string(),
                         value: token::RT_EQ.to_string(),
@@ -305,7 +335,7 @@ impl Lexer {
                 }
             },
             '<' => {
-                return if is_lookahead_equal_symbol {
+                if is_lookahead_equal_symbol {
                     Token {
                         token_type: token::LT_EQ.to_string(),
                         value: token::LT_EQ.to_string(),
@@ -322,7 +352,7 @@ impl Lexer {
                 }
             },
             '=' => {
-                return if is_lookahead_equal_symbol {
+                if is_lookahead_equal_symbol {
                     Token {
                         token_type: token::EQ.to_string(),
                         value: token::EQ.to_string(),
@@ -339,7 +369,7 @@ impl Lexer {
                 }
             },
             '!' => {
-                return if is_lookahead_equal_symbol {
+                if is_lookahead_equal_symbol {
                     Token {
                         token_type: token::NEQ.to_string(),
                         value: token::NEQ.to_string(),
@@ -356,7 +386,7 @@ impl Lexer {
                 }
             },
             _ => {
-                panic!("Unknown comparison op {} at {}:{}", character, line, column);
+                unreachable!("{} is not a comparison operator", character)
             }
         }
     }
@@ -388,7 +418,7 @@ impl Lexer {
 
         match character {
             '+' => {
-                return if is_lookahead_plus_symbol {
+                if is_lookahead_plus_symbol {
                     Token {
                         token_type: token::POST_INCREMENT.to_string(),
                         value: token::POST_INCREMENT.to_string(),
@@ -405,7 +435,7 @@ impl Lexer {
                 }
             },
             '-' => {
-                return if is_lookahead_minus_symbol {
+                if is_lookahead_minus_symbol {
                     Token {
                         token_type: token::POST_DECREMENT.to_string(),
                         value: token::POST_DECREMENT.to_string(),
@@ -422,7 +452,7 @@ impl Lexer {
                 }
             },
             '*' => {
-                return Token {
+                Token {
                     token_type: token::TIMES.to_string(),
                     value: token::TIMES.to_string(),
                     line,
@@ -430,7 +460,7 @@ impl Lexer {
                 }
             },
             '/' => {
-                return Token {
+                Token {
                     token_type: token::DIV.to_string(),
                     value: token::DIV.to_string(),
                     line,
@@ -439,7 +469,7 @@ impl Lexer {
             },
             '.' => {
                 // Not really an arithmetic op, but fit here so well
-                return Token {
+                Token {
                     token_type: token::DOT.to_string(),
                     value: token::DOT.to_string(),
                     line,
@@ -447,7 +477,7 @@ impl Lexer {
                 }
             },
             _ => {
-                panic!("Unknown arithmetic op {} at {}:{}", character, line, column);
+                unreachable!("{} is not an arithmetic operator", character)
             }
         }
     }
@@ -478,7 +508,7 @@ impl Lexer {
 
         match character {
             '&' => {
-                return if is_lookahead_amp_symbol {
+                if is_lookahead_amp_symbol {
                     Token {
                         token_type: token::AND.to_string(),
                         value: token::AND.to_string(),
@@ -495,7 +525,7 @@ impl Lexer {
                 }
             },
             '|' => {
-                return if is_lookahead_pipe_symbol {
+                if is_lookahead_pipe_symbol {
                     Token {
                         token_type: token::OR.to_string(),
                         value: token::OR.to_string(),
@@ -504,15 +534,15 @@ impl Lexer {
                     }
                 } else {
                     Token {
-                        token_type: token::UNKNOWN.to_string(),
-                        value: format!("{}{}", character, lookahead),
+                        token_type: token::PIPE.to_string(),
+                        value: token::PIPE.to_string(),
                         line,
                         column,
                     }
                 }
             },
             _ => {
-                panic!("Unknown logical op {} at {}:{}", character, line, column);
+                unreachable!("{} is not a logical operator", character)
             }
         }
     }
@@ -535,7 +565,7 @@ impl Lexer {
             }
         }
 
-        return Token {
+        Token {
             token_type: token::R_PARENT.to_string(),
             value: token::R_PARENT.to_string(),
             line,
@@ -561,7 +591,16 @@ impl Lexer {
             }
         }
 
-        return Token {
+        if character == ':' {
+            return Token {
+                token_type: token::COLON.to_string(),
+                value: token::COLON.to_string(),
+                line,
+                column,
+            }
+        }
+
+        Token {
             token_type: token::COMMA.to_string(),
             value: token::COMMA.to_string(),
             line,
@@ -587,7 +626,25 @@ impl Lexer {
             }
         }
 
-        return Token {
+        if character == '[' {
+            return Token {
+                token_type: token::L_BRACKET.to_string(),
+                value: token::L_BRACKET.to_string(),
+                line,
+                column,
+            }
+        }
+
+        if character == ']' {
+            return Token {
+                token_type: token::R_BRACKET.to_string(),
+                value: token::R_BRACKET.to_string(),
+                line,
+                column,
+            }
+        }
+
+        Token {
             token_type: token::R_BRACE.to_string(),
             value: token::R_BRACE.to_string(),
             line,
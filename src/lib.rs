@@ -0,0 +1,34 @@
+pub mod token;
+pub mod char_utils;
+pub mod fsm;
+pub mod lexer;
+pub mod number_fsm;
+pub mod ast;
+pub mod parser;
+pub mod formatter;
+pub mod diff;
+pub mod checker;
+pub mod cfg;
+pub mod constant;
+pub mod init;
+pub mod domain;
+pub mod graph;
+pub mod lint;
+pub mod json;
+pub mod diagnostic;
+pub mod value;
+pub mod command;
+pub mod secret;
+pub mod stdlib;
+pub mod debug;
+pub mod dap;
+pub mod limits;
+pub mod interpreter;
+pub mod bytecode;
+pub mod compiler;
+pub mod vm;
+pub mod module;
+pub mod visit;
+pub mod suggest;
+pub mod resolve;
+pub mod editor;
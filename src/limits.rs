@@ -0,0 +1,96 @@
+//! Limits on the resources a program may use, so that an untrusted file can't loop, recurse or allocate forever.
+//!
+//! Both backends charge the same `Budget` at the same points, so a program stops with the same error whichever runs it:
+//! - a step is a call or a loop iteration, the only ways a program runs longer than its text,
+//! - the call depth counts the calls in progress, not `main` or the top-level statements,
+//! - the heap counts every value built by a list, map or complex literal, an operator, a builtin or a command, in full,
+//!   whether or not it's still alive,
+//! - the time is checked on each step, a single command being bounded by its own `timeout` instead.
+
+use std::time::{Duration, Instant};
+
+use crate::value::Value;
+
+/// Call depth allowed by `Limits::default`.
+pub const DEFAULT_DEPTH: usize = 1000;
+
+/// Call depth never exceeded, whatever the limits. The interpreter recurses on the native stack, which must have room
+/// for this many calls: the `shanty` binary runs programs on a stack sized for it.
+pub const MAX_DEPTH: usize = 10_000;
+
+/// Limits of a run, `None` for no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub steps: Option<u64>,
+    /// Capped at `MAX_DEPTH`, `None` meaning `MAX_DEPTH`
+    pub depth: Option<usize>,
+    /// In bytes
+    pub heap: Option<usize>,
+    pub time: Option<Duration>,
+}
+
+impl Limits {
+    /// No limit at all, but for the call depth which stays at most `MAX_DEPTH`.
+    pub fn none() -> Limits {
+        Limits { steps: None, depth: None, heap: None, time: None }
+    }
+}
+
+impl Default for Limits {
+    /// Only the call depth is limited, deeper recursion being more likely a bug than intended.
+    fn default() -> Limits {
+        Limits { depth: Some(DEFAULT_DEPTH), ..Limits::none() }
+    }
+}
+
+/// What a run used so far, checked against its limits.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    limits: Limits,
+    steps: u64,
+    depth: usize,
+    heap: usize,
+    start: Instant,
+}
+
+impl Budget {
+    pub fn new(mut limits: Limits) -> Budget {
+        limits.depth = Some(limits.depth.map_or(MAX_DEPTH, |depth| depth.min(MAX_DEPTH)));
+        Budget { limits, steps: 0, depth: 0, heap: 0, start: Instant::now() }
+    }
+
+    /// Counts a loop iteration.
+    pub fn step(&mut self) -> Result<(), String> {
+        self.steps += 1;
+        if let Some(steps) = self.limits.steps.filter(|&steps| self.steps > steps) {
+            return Err(format!("Step limit of {} exceeded", steps));
+        }
+        match self.limits.time {
+            Some(time) if self.start.elapsed() > time => Err(format!("Time limit of {}ms exceeded", time.as_millis())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts a call starting, which is also a step. `leave` must be called when it returns.
+    pub fn enter(&mut self) -> Result<(), String> {
+        self.step()?;
+        if let Some(depth) = self.limits.depth.filter(|&depth| self.depth >= depth) {
+            return Err(format!("Call depth limit of {} exceeded", depth));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Counts the memory held by a value just built.
+    pub fn allocate(&mut self, value: &Value) -> Result<(), String> {
+        self.heap = self.heap.saturating_add(value.heap_size());
+        match self.limits.heap {
+            Some(heap) if self.heap > heap => Err(format!("Heap limit of {} bytes exceeded", heap)),
+            _ => Ok(()),
+        }
+    }
+}
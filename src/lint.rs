@@ -0,0 +1,375 @@
+//! Style and safety checks that don't make a program invalid.
+//!
+//! Every rule has an id and a default severity. A project can change severities or turn rules off in a
+//! `.shantylint` file of `rule-id = error|warning|off` lines, and a `// lint:disable rule-id...` comment
+//! turns rules off for the line it's on and the line after it.
+
+use std::collections::HashMap;
+
+use core::fmt::Display;
+
+use crate::ast::{BinaryOp, DomainField, Expr, ExprKind, FieldInit, FuncDecl, Item, Program, Span, Stmt, StmtKind, UnaryOp};
+use crate::diagnostic::Severity;
+use crate::lexer::Lexer;
+use crate::resolve::Resolution;
+use crate::token;
+use crate::visit::{self, Visitor};
+
+pub const CONFIG_FILE: &str = ".shantylint";
+
+const DISABLE_DIRECTIVE: &str = "lint:disable";
+
+pub struct Rule {
+    pub id: &'static str,
+    pub severity: Severity,
+    pub description: &'static str,
+}
+
+pub const UNUSED_VARIABLE: &str = "unused-variable";
+pub const UNUSED_FUNCTION: &str = "unused-function";
+pub const EMPTY_WHILE: &str = "empty-while";
+pub const BOOLEAN_COMPARISON: &str = "boolean-comparison";
+pub const HARDCODED_SECRET: &str = "hardcoded-secret";
+pub const DANGEROUS_COMMAND: &str = "dangerous-command";
+
+pub static RULES: [Rule; 6] = [
+    Rule { id: UNUSED_VARIABLE, severity: Severity::Warning, description: "Variable is declared but never used" },
+    Rule { id: UNUSED_FUNCTION, severity: Severity::Warning, description: "Function that isn't pub is never called" },
+    Rule { id: EMPTY_WHILE, severity: Severity::Warning, description: "while loop with an empty body" },
+    Rule { id: BOOLEAN_COMPARISON, severity: Severity::Warning, description: "Comparison with true or false" },
+    Rule { id: HARDCODED_SECRET, severity: Severity::Error, description: "Secret written in a string literal" },
+    Rule { id: DANGEROUS_COMMAND, severity: Severity::Error, description: "Command deleting the root directory" },
+];
+
+pub fn rule(id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|rule| rule.id == id)
+}
+
+/// Severities set by a project, overriding the defaults of the rules.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// `None` turns a rule off
+    severities: HashMap<&'static str, Option<Severity>>,
+}
+
+impl Config {
+    /// Parses `rule-id = error|warning|off` lines, `#` starting a comment.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (id, value) = match line.split_once('=') {
+                Some((id, value)) => (id.trim(), value.trim()),
+                None => return Err(format!("Expected rule-id = error|warning|off at line {}", i + 1)),
+            };
+            let rule = rule(id).ok_or_else(|| format!("Unknown lint rule {} at line {}", id, i + 1))?;
+            let severity = match value {
+                "error" => Some(Severity::Error),
+                "warning" => Some(Severity::Warning),
+                "off" => None,
+                _ => return Err(format!("Expected error, warning or off for {} at line {}", id, i + 1)),
+            };
+            config.severities.insert(rule.id, severity);
+        }
+
+        Ok(config)
+    }
+
+    /// Severity of a rule, `None` when it's turned off.
+    pub fn severity(&self, rule: &Rule) -> Option<Severity> {
+        self.severities.get(rule.id).copied().unwrap_or(Some(rule.severity))
+    }
+}
+
+/// Replacing the text of `span` with `replacement` resolves a finding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub span: Span,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    pub fix: Option<Fix>,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Runs every enabled rule on a program parsed from `source`.
+pub fn lint(source: &str, program: &Program, resolution: &Resolution, config: &Config) -> Vec<Finding> {
+    let mut linter = Linter { source, resolution, found: vec![] };
+    linter.visit_program(program);
+
+    let disabled = disabled_lines(source);
+    let mut findings: Vec<Finding> = linter
+        .found
+        .into_iter()
+        .filter_map(|(id, message, span, fix)| {
+            let rule = rule(id).expect("findings come from known rules");
+            let severity = config.severity(rule)?;
+            let disabled_here = disabled.get(&span.line).is_some_and(|ids| ids.iter().any(|disabled| disabled == id));
+            if disabled_here {
+                return None;
+            }
+            Some(Finding { rule: id, severity, message, span, fix })
+        })
+        .collect();
+
+    findings.sort_by_key(|f| (f.span.line, f.span.column));
+    findings
+}
+
+/// Rules turned off by comment directives, by line.
+fn disabled_lines(source: &str) -> HashMap<usize, Vec<String>> {
+    let mut disabled: HashMap<usize, Vec<String>> = HashMap::new();
+
+    for comment in Lexer::new(source).all_tokens().iter().filter(|t| t.token_type == token::COMMENT) {
+        let text = comment.value.trim();
+        if let Some(ids) = text.strip_prefix(DISABLE_DIRECTIVE) {
+            let ids: Vec<String> = ids.split(|c: char| c == ',' || c.is_whitespace()).filter(|id| !id.is_empty()).map(String::from).collect();
+            disabled.entry(comment.line).or_default().extend(ids.iter().cloned());
+            disabled.entry(comment.line + 1).or_default().extend(ids);
+        }
+    }
+
+    disabled
+}
+
+/// Applies the fixes of `findings` to `source`, skipping fixes that overlap an earlier one.
+pub fn apply_fixes(source: &str, findings: &[Finding]) -> String {
+    let mut edits: Vec<(usize, usize, &str)> = findings
+        .iter()
+        .filter_map(|finding| finding.fix.as_ref())
+        .filter_map(|fix| Some((offset(source, fix.span.line, fix.span.column)?, offset(source, fix.span.end_line, fix.span.end_column)?, fix.replacement.as_str())))
+        .collect();
+    edits.sort_by_key(|&(start, _, _)| start);
+
+    let mut fixed = String::new();
+    let mut position = 0;
+    for (start, end, replacement) in edits {
+        if start < position {
+            continue;
+        }
+        fixed.push_str(&source[position..start]);
+        fixed.push_str(replacement);
+        position = end;
+    }
+    fixed.push_str(&source[position..]);
+    fixed
+}
+
+/// Byte offset of a 1-based line and column.
+fn offset(source: &str, line: usize, column: usize) -> Option<usize> {
+    let mut start = 0;
+    for _ in 1..line {
+        start += source[start..].find('\n')? + 1;
+    }
+    let offset = start + column - 1;
+    if offset <= source.len() {
+        Some(offset)
+    } else {
+        None
+    }
+}
+
+fn text(source: &str, span: Span) -> Option<&str> {
+    Some(&source[offset(source, span.line, span.column)?..offset(source, span.end_line, span.end_column)?])
+}
+
+/// Whether the whole of `text` is enclosed in a pair of parentheses, as in `(a < b)` but not `(a) < (b)`.
+fn parenthesized(text: &str) -> bool {
+    let tokens = Lexer::new(text).all_tokens();
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.token_type.as_str() {
+            token::L_PARENT => depth += 1,
+            token::R_PARENT => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            // The parenthesis opening the text closes at its last token, eof aside
+            return i > 0 && i + 2 == tokens.len();
+        }
+    }
+    false
+}
+
+fn looks_like_secret_name(name: &str) -> bool {
+    let name = name.to_lowercase().replace('_', "");
+    ["password", "passwd", "secret", "token", "apikey", "privatekey"].iter().any(|word| name.contains(word))
+}
+
+fn looks_like_secret_value(value: &str) -> bool {
+    ["ghp_", "github_pat_", "AKIA", "sk_live_", "xoxb-", "xoxp-"].iter().any(|prefix| value.starts_with(prefix)) || value.contains("PRIVATE KEY-----")
+}
+
+/// Whether the string deletes the root directory recursively, as in `rm -rf /`, `rm -r -f /*` or
+/// `rm --recursive --force /`.
+fn deletes_root(value: &str) -> bool {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    words.iter().enumerate().any(|(i, &word)| word == "rm" && rm_deletes_root(&words[i + 1..]))
+}
+
+/// Whether the arguments of an `rm` force a recursive deletion of `/` or `/*`, up to the end of its command.
+fn rm_deletes_root(arguments: &[&str]) -> bool {
+    let (mut recursive, mut force, mut root) = (false, false, false);
+    let mut options = true;
+
+    for &word in arguments {
+        if matches!(word, ";" | "&&" | "||" | "|") {
+            break;
+        }
+        // A `;` ending the word ends the command after it
+        let argument = word.strip_suffix(';').unwrap_or(word);
+        match argument {
+            "--" if options => options = false,
+            "--recursive" if options => recursive = true,
+            "--force" if options => force = true,
+            // Other long options, such as --no-preserve-root, don't change what's deleted
+            _ if options && argument.starts_with("--") => {}
+            _ if options && argument.len() > 1 && argument.starts_with('-') => {
+                recursive |= argument.contains(['r', 'R']);
+                force |= argument.contains('f');
+            }
+            "/" | "/*" => root = true,
+            _ => {}
+        }
+        if argument.len() < word.len() {
+            break;
+        }
+    }
+
+    recursive && force && root
+}
+
+type Found = (&'static str, String, Span, Option<Fix>);
+
+struct Linter<'a> {
+    source: &'a str,
+    resolution: &'a Resolution,
+    found: Vec<Found>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, rule: &'static str, message: String, span: Span, fix: Option<Fix>) {
+        self.found.push((rule, message, span, fix));
+    }
+
+    fn is_unused(&self, span: Span) -> bool {
+        self.resolution.symbol_at(span).is_some_and(|symbol| self.resolution.references(symbol).is_empty())
+    }
+
+    /// Functions that aren't `pub` and never called, `main` being called by the runtime.
+    fn check_func(&mut self, func: &FuncDecl) {
+        if !func.public && func.name != "main" && self.is_unused(func.name_span) {
+            self.report(UNUSED_FUNCTION, format!("Function {} is never called", func.name), func.name_span, None);
+        }
+    }
+
+    /// Reports a string literal stored under a name that suggests a secret, or that looks like a known kind of token.
+    fn check_secret(&mut self, name: &str, value: &Expr) {
+        if let ExprKind::String(literal) = &value.kind {
+            if !literal.is_empty() && looks_like_secret_name(name) && !looks_like_secret_value(literal) {
+                self.report(HARDCODED_SECRET, format!("{} is set to a hard-coded secret, load it as a secret instead", name), value.span, None);
+            }
+        }
+    }
+
+    /// `x == true` is `x` and `x == false` is `!x`, `!=` the other way around.
+    fn check_boolean_comparison(&mut self, expr: &Expr, op: BinaryOp, left: &Expr, right: &Expr) {
+        let (operand, literal) = match (&left.kind, &right.kind) {
+            (_, ExprKind::Boolean(literal)) => (left, *literal),
+            (ExprKind::Boolean(literal), _) => (right, *literal),
+            _ => return,
+        };
+        let keeps = match op {
+            BinaryOp::Eq => literal,
+            BinaryOp::Neq => !literal,
+            _ => return,
+        };
+
+        let fix = text(self.source, operand.span).map(|operand_text| {
+            let replacement = if keeps {
+                operand_text.to_string()
+            } else if matches!(operand.kind, ExprKind::Binary { .. } | ExprKind::Assign { .. }) && !parenthesized(operand_text) {
+                format!("{}({})", UnaryOp::Not.as_str(), operand_text)
+            } else {
+                format!("{}{}", UnaryOp::Not.as_str(), operand_text)
+            };
+            Fix { span: expr.span, replacement }
+        });
+        let message = format!("Comparing with {} is redundant", literal);
+        self.report(BOOLEAN_COMPARISON, message, expr.span, fix);
+    }
+}
+
+impl<'ast> Visitor<'ast> for Linter<'_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        if let Item::Func(func) = item {
+            self.check_func(func);
+        }
+        visit::walk_item(self, item)
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        match &stmt.kind {
+            StmtKind::VarDecl { name, name_span, value, .. } => {
+                if self.is_unused(*name_span) {
+                    self.report(UNUSED_VARIABLE, format!("Variable {} is never used", name), *name_span, None);
+                }
+                if let Some(value) = value {
+                    self.check_secret(name, value);
+                }
+            }
+            StmtKind::While { body, .. } if body.stmts.is_empty() => {
+                self.report(EMPTY_WHILE, "while loop has an empty body".to_string(), stmt.span, None);
+            }
+            _ => {}
+        }
+
+        visit::walk_stmt(self, stmt)
+    }
+
+    fn visit_domain_field(&mut self, field: &'ast DomainField) {
+        self.check_secret(&field.name, &field.value);
+        visit::walk_domain_field(self, field)
+    }
+
+    fn visit_field_init(&mut self, field: &'ast FieldInit) {
+        self.check_secret(&field.name, &field.value);
+        visit::walk_field_init(self, field)
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::String(value) if looks_like_secret_value(value) => {
+                self.report(HARDCODED_SECRET, "String looks like a hard-coded token, load it as a secret instead".to_string(), expr.span, None);
+            }
+            ExprKind::String(value) if deletes_root(value) => {
+                self.report(DANGEROUS_COMMAND, "Command deletes the root directory".to_string(), expr.span, None);
+            }
+            ExprKind::Binary { op, left, right } => self.check_boolean_comparison(expr, *op, left, right),
+            ExprKind::Assign { target, value } => {
+                if let ExprKind::Identifier(name) | ExprKind::Field { field: name, .. } = &target.kind {
+                    self.check_secret(name, value);
+                }
+            }
+            _ => {}
+        }
+
+        visit::walk_expr(self, expr)
+    }
+}
@@ -1,11 +1,26 @@
-mod token;
-mod char_utils;
-mod fsm;
-mod lexer;
-mod number_fsm;
+use std::env;
+use std::process;
+use std::thread;
+
+mod cli;
+mod repl;
+
+/// Stack of the thread running the command. The interpreter recurses on it for each call of the program, this leaves
+/// room for `limits::MAX_DEPTH` calls with twice the stack a simple recursive function takes in a debug build.
+const STACK_SIZE: usize = 512 * 1024 * 1024;
 
 fn main() {
-    let mut lexer = lexer::Lexer { input: "rune \"lolz\"\n\"lulz\" 'w'= ++ && ;, -12.5 {}\n".to_string(), position: 0, line: 0, column: 0 };
-    let tokens = lexer.all_tokens();
-    println!("Tokens are: {:#?}", tokens);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = thread::Builder::new().stack_size(STACK_SIZE).spawn(move || match args.split_first() {
+        Some((command, args)) => cli::run(command, args),
+        None => cli::run("repl", &[]),
+    });
+    match command.map(|command| command.join()) {
+        Ok(Ok(code)) => process::exit(code),
+        Ok(Err(_)) => process::exit(101),
+        Err(e) => {
+            eprintln!("Can't start: {}", e);
+            process::exit(1)
+        }
+    }
 }
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use core::fmt::Display;
+
+use crate::ast::{FileId, Item, Program, Span};
+use crate::parser;
+
+/// Finds and reads the files named by `import` declarations.
+pub trait FileResolver {
+    /// Path of the file that `importer` refers to as `import_path`.
+    fn resolve(&self, importer: &Path, import_path: &str) -> PathBuf;
+
+    fn read(&self, path: &Path) -> io::Result<String>;
+}
+
+/// Resolves imports relative to the directory of the importing file.
+pub struct FsResolver;
+
+impl FileResolver for FsResolver {
+    fn resolve(&self, importer: &Path, import_path: &str) -> PathBuf {
+        importer.parent().unwrap_or_else(|| Path::new("")).join(import_path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub source: String,
+    pub program: Program,
+    /// Files imported by this one, in declaration order
+    pub imports: Vec<FileId>,
+}
+
+/// All files reachable from a root file through imports, indexed by `FileId`. The root file is file 0.
+#[derive(Debug)]
+pub struct Modules {
+    pub files: Vec<SourceFile>,
+    /// Every file appears after the files it imports
+    pub order: Vec<FileId>,
+}
+
+impl Modules {
+    pub fn path(&self, file: FileId) -> &Path {
+        &self.files[file].path
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleError {
+    pub message: String,
+    /// Where the error happened, `None` when the root file itself can't be read
+    pub span: Option<Span>,
+    /// Whether the file was read but couldn't be parsed
+    pub syntax: bool,
+}
+
+impl Display for ModuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self.span {
+            Some(span) => write!(f, "{} at {}", self.message, span),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Loads `root` and everything it imports from the file system.
+pub fn load(root: &Path) -> Result<Modules, (Modules, Vec<ModuleError>)> {
+    load_with(root, &FsResolver)
+}
+
+/// Loads `root` and everything it imports through `resolver`.
+/// On errors, the files that could be loaded are returned alongside so that the errors can name them.
+pub fn load_with(root: &Path, resolver: &dyn FileResolver) -> Result<Modules, (Modules, Vec<ModuleError>)> {
+    let mut loader = Loader { resolver, files: vec![], ids: HashMap::new(), done: vec![], stack: vec![], order: vec![], errors: vec![] };
+    loader.visit(normalize(root), None);
+
+    let modules = Modules { files: loader.files, order: loader.order };
+    if loader.errors.is_empty() {
+        return Ok(modules);
+    }
+
+    Err((modules, loader.errors))
+}
+
+/// Removes `.` and `..` components so that a file reached through different relative paths gets a single id.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+struct Loader<'a> {
+    resolver: &'a dyn FileResolver,
+    files: Vec<SourceFile>,
+    ids: HashMap<PathBuf, FileId>,
+    done: Vec<bool>,
+    /// Files whose imports are being loaded, a file imported while on the stack closes a cycle
+    stack: Vec<FileId>,
+    order: Vec<FileId>,
+    errors: Vec<ModuleError>,
+}
+
+impl Loader<'_> {
+    fn visit(&mut self, path: PathBuf, import_span: Option<Span>) -> Option<FileId> {
+        if let Some(&id) = self.ids.get(&path) {
+            if !self.done[id] {
+                self.report_cycle(id, import_span);
+            }
+            return Some(id);
+        }
+
+        let source = match self.resolver.read(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                self.errors.push(ModuleError { message: format!("Can't read {}: {}", path.display(), e), span: import_span, syntax: false });
+                return None;
+            }
+        };
+
+        let id = self.files.len();
+        let program = match parser::parse_file(&source, id) {
+            Ok(program) => program,
+            Err(e) => {
+                let span = Span::new(e.line, e.column, e.line, e.column).in_file(id);
+                self.errors.push(ModuleError { message: e.message, span: Some(span), syntax: true });
+                Program { items: vec![] }
+            }
+        };
+
+        let imports: Vec<(String, Span)> = program
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Import(import) => Some((import.path.clone(), import.path_span)),
+                _ => None,
+            })
+            .collect();
+
+        self.files.push(SourceFile { path: path.clone(), source, program, imports: vec![] });
+        self.ids.insert(path.clone(), id);
+        self.done.push(false);
+        self.stack.push(id);
+
+        for (import_path, span) in imports {
+            let resolved = normalize(&self.resolver.resolve(&path, &import_path));
+            if let Some(imported) = self.visit(resolved, Some(span)) {
+                self.files[id].imports.push(imported);
+            }
+        }
+
+        self.stack.pop();
+        self.done[id] = true;
+        self.order.push(id);
+        Some(id)
+    }
+
+    fn report_cycle(&mut self, id: FileId, import_span: Option<Span>) {
+        let start = self.stack.iter().position(|&f| f == id).unwrap_or(0);
+        let mut cycle: Vec<String> = self.stack[start..].iter().map(|&f| self.files[f].path.display().to_string()).collect();
+        cycle.push(self.files[id].path.display().to_string());
+
+        self.errors.push(ModuleError { message: format!("Import cycle: {}", cycle.join(" -> ")), span: import_span, syntax: false });
+    }
+}
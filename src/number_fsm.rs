@@ -36,11 +36,10 @@ fn next_state(current_state: i8, character: char) -> i8 {
         }
     }
 
-    if current_state == States::BeginNumberWithFractionalPart as i8 {
-        if char_utils::is_digit(character) {
+    if current_state == States::BeginNumberWithFractionalPart as i8
+        && char_utils::is_digit(character) {
             return States::NumberWithFractionalPart as i8;
         }
-    }
 
     if current_state == States::NumberWithFractionalPart as i8 {
         if char_utils::is_digit(character) {
@@ -62,11 +61,10 @@ fn next_state(current_state: i8, character: char) -> i8 {
         }
     }
 
-    if current_state == States::BeginNumberWithSignedExponent as i8 {
-        if char_utils::is_digit(character) {
+    if current_state == States::BeginNumberWithSignedExponent as i8
+        && char_utils::is_digit(character) {
             return States::NumberWithExponent as i8;
         }
-    }
 
     States::NoNextState as i8
 }
@@ -0,0 +1,642 @@
+use core::fmt::Display;
+
+use crate::ast::{
+    BinaryOp, Block, CommandDecl, ComplexDecl, FileId, DomainDecl, DomainField, DomainKind, Expr, ExprKind, FieldDecl, FieldInit, FuncDecl, ImportDecl, Item, Param, PostfixOp,
+    Program, Span, Stmt, StmtKind, Type, UnaryOp,
+};
+use crate::char_utils;
+use crate::lexer::Lexer;
+use crate::token;
+use crate::token::Token;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}:{}", self.message, self.line, self.column)
+    }
+}
+
+/// Lexes and parses a whole Shanty source file.
+pub fn parse(source: &str) -> Result<Program, ParseError> {
+    parse_file(source, 0)
+}
+
+/// Like `parse`, with spans pointing into file `file`.
+pub fn parse_file(source: &str, file: FileId) -> Result<Program, ParseError> {
+    let mut lexer = Lexer::new(source);
+    Parser::for_file(lexer.all_tokens(), file).parse_program()
+}
+
+/// Span of a single token. Literal values don't include their quotes, so those are added back.
+pub fn token_span(token: &Token) -> Span {
+    let length = match token.token_type.as_str() {
+        token::STRING_LITERAL | token::COMMENT => token.value.len() + 2,
+        token::RUNE_LITERAL => 3,
+        _ => token.value.len(),
+    };
+
+    Span::new(token.line, token.column, token.line, token.column + length)
+}
+
+/// Variables and fields can be assigned to.
+fn is_assignable(expr: &Expr) -> bool {
+    matches!(expr.kind, ExprKind::Identifier(_) | ExprKind::Field { .. })
+}
+
+fn describe(token: &Token) -> String {
+    match token.token_type.as_str() {
+        token::EOF => "end of input".to_string(),
+        token::STRING_LITERAL => format!("\"{}\"", token.value),
+        token::RUNE_LITERAL => format!("'{}'", token.value),
+        _ => format!("'{}'", token.value),
+    }
+}
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    file: FileId,
+}
+
+impl Parser {
+    /// Comments are dropped, the parser only sees significant tokens.
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Parser::for_file(tokens, 0)
+    }
+
+    pub fn for_file(tokens: Vec<Token>, file: FileId) -> Self {
+        let tokens = tokens.into_iter().filter(|t| t.token_type != token::COMMENT).collect();
+        Parser { tokens, position: 0, file }
+    }
+
+    pub fn parse_program(&mut self) -> Result<Program, ParseError> {
+        if let Some(unknown) = self.tokens.iter().find(|t| t.token_type == token::UNKNOWN) {
+            let message = match unknown.value.chars().next() {
+                None => "Unterminated string literal".to_string(),
+                Some('\'') => format!("Invalid rune literal {}", unknown.value),
+                Some(c) if c.is_ascii_digit() => format!("Invalid number {}", unknown.value),
+                Some(c) => format!("Unknown character {}", c),
+            };
+            return Err(ParseError { message, line: unknown.line, column: unknown.column });
+        }
+
+        let mut items = vec![];
+
+        while !self.check(token::EOF) {
+            items.push(self.parse_item()?);
+        }
+
+        Ok(Program { items })
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position.min(self.tokens.len() - 1)]
+    }
+
+    fn peek_type(&self) -> &str {
+        &self.peek().token_type
+    }
+
+    /// Type of the token `offset` tokens after the current one.
+    fn peek_type_at(&self, offset: usize) -> &str {
+        &self.tokens[(self.position + offset).min(self.tokens.len() - 1)].token_type
+    }
+
+    fn check(&self, token_type: &str) -> bool {
+        self.peek_type() == token_type
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if token.token_type != token::EOF {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn span(&self, token: &Token) -> Span {
+        token_span(token).in_file(self.file)
+    }
+
+    fn advance_span(&mut self) -> Span {
+        let token = self.advance();
+        self.span(&token)
+    }
+
+    fn expect_span(&mut self, token_type: &str, what: &str) -> Result<Span, ParseError> {
+        let token = self.expect(token_type, what)?;
+        Ok(self.span(&token))
+    }
+
+    fn expect(&mut self, token_type: &str, what: &str) -> Result<Token, ParseError> {
+        if self.check(token_type) {
+            return Ok(self.advance());
+        }
+
+        Err(self.error_at_current(&format!("Expected {}", what)))
+    }
+
+    fn error_at_current(&self, expected: &str) -> ParseError {
+        let token = self.peek();
+        ParseError {
+            message: format!("{} but found {}", expected, describe(token)),
+            line: token.line,
+            column: token.column,
+        }
+    }
+
+    fn error_at(&self, message: &str, span: Span) -> ParseError {
+        ParseError { message: message.to_string(), line: span.line, column: span.column }
+    }
+
+    fn parse_item(&mut self) -> Result<Item, ParseError> {
+        if self.check(token::IMPORT) {
+            return Ok(Item::Import(self.parse_import()?));
+        }
+
+        let public = self.check(token::PUB);
+        let start = self.span(self.peek());
+        if public {
+            self.advance();
+        }
+
+        if self.check(token::FUNC) {
+            let mut func = self.parse_func(public)?;
+            func.span = start.to(func.span);
+            return Ok(Item::Func(func));
+        }
+
+        if self.check(token::COMPLEX) {
+            let mut complex = self.parse_complex(public)?;
+            complex.span = start.to(complex.span);
+            return Ok(Item::Complex(complex));
+        }
+
+        // `command build(...) { ... }`, while `command result = build();` is a variable declaration
+        if self.check(token::COMMAND) && self.peek_type_at(2) == token::L_PARENT {
+            let mut command = self.parse_command(public)?;
+            command.span = start.to(command.span);
+            return Ok(Item::Command(command));
+        }
+
+        // `repo backend { ... }`, while `repo backend = ...;` is a variable declaration
+        if DomainKind::from_keyword(self.peek_type()).is_some() && self.peek_type_at(2) == token::L_BRACE {
+            let mut domain = self.parse_domain(public)?;
+            domain.span = start.to(domain.span);
+            return Ok(Item::Domain(domain));
+        }
+
+        if public {
+            return Err(self.error_at_current("Expected a function, command, complex type or domain declaration after pub"));
+        }
+
+        Ok(Item::Stmt(self.parse_statement()?))
+    }
+
+    fn parse_import(&mut self) -> Result<ImportDecl, ParseError> {
+        let start = self.advance_span();
+        let path = self.expect(token::STRING_LITERAL, "imported file path")?;
+        let end = self.expect_span(token::SEMI, "';'")?;
+        Ok(ImportDecl { path: path.value.clone(), path_span: self.span(&path), span: start.to(end) })
+    }
+
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        if self.check(token::IDENTIFIER) {
+            return Ok(Type::Complex(self.advance().value));
+        }
+
+        // `list<element>` and `map<key, value>`
+        if self.check(token::LIST) || self.check(token::MAP) {
+            let list = self.check(token::LIST);
+            self.advance();
+            self.expect(token::LT, "'<'")?;
+            let first = self.parse_element_type()?;
+            let ty = if list {
+                Type::List(Box::new(first))
+            } else {
+                self.expect(token::COMMA, "','")?;
+                Type::Map(Box::new(first), Box::new(self.parse_element_type()?))
+            };
+            self.expect(token::RT, "'>'")?;
+            return Ok(ty);
+        }
+
+        match Type::from_keyword(self.peek_type()) {
+            Some(ty) => {
+                self.advance();
+                Ok(ty)
+            }
+            None => Err(self.error_at_current("Expected a type")),
+        }
+    }
+
+    fn parse_element_type(&mut self) -> Result<Type, ParseError> {
+        let span = self.span(self.peek());
+        let ty = self.parse_type()?;
+        if ty == Type::Void {
+            return Err(self.error_at("Lists and maps can't hold void", span));
+        }
+        Ok(ty)
+    }
+
+    fn parse_complex(&mut self, public: bool) -> Result<ComplexDecl, ParseError> {
+        let start = self.advance_span();
+        let name = self.expect(token::IDENTIFIER, "complex type name")?;
+        self.expect(token::L_BRACE, "'{'")?;
+
+        let mut fields = vec![];
+        while !self.check(token::R_BRACE) && !self.check(token::EOF) {
+            let field_name = self.expect(token::IDENTIFIER, "field name")?;
+            self.expect(token::COLON, "':'")?;
+            let ty_span = self.span(self.peek());
+            let ty = self.parse_type()?;
+            if ty == Type::Void {
+                return Err(self.error_at("Fields can't be of type void", ty_span));
+            }
+            let end = self.expect_span(token::SEMI, "';'")?;
+            fields.push(FieldDecl { name: field_name.value.clone(), ty, span: self.span(&field_name).to(end) });
+        }
+
+        let end = self.expect_span(token::R_BRACE, "'}'")?;
+        Ok(ComplexDecl { public, name: name.value.clone(), name_span: self.span(&name), fields, span: start.to(end) })
+    }
+
+    fn parse_func(&mut self, public: bool) -> Result<FuncDecl, ParseError> {
+        let start = self.advance_span();
+        let return_type = self.parse_type()?;
+        let name = self.expect(token::IDENTIFIER, "function name")?;
+        self.expect(token::L_PARENT, "'('")?;
+
+        let params = self.parse_params()?;
+        let body = self.parse_block()?;
+
+        Ok(FuncDecl {
+            public,
+            name: name.value.clone(),
+            name_span: self.span(&name),
+            params,
+            return_type,
+            span: start.to(body.span),
+            body,
+        })
+    }
+
+    /// Parameters up to and including the closing parenthesis.
+    fn parse_params(&mut self) -> Result<Vec<Param>, ParseError> {
+        let mut params = vec![];
+        if !self.check(token::R_PARENT) {
+            loop {
+                let param_start = self.span(self.peek());
+                let ty = self.parse_type()?;
+                if ty == Type::Void {
+                    return Err(self.error_at("Parameters can't be of type void", param_start));
+                }
+                let param_name = self.expect(token::IDENTIFIER, "parameter name")?;
+                let name_span = self.span(&param_name);
+                params.push(Param { name: param_name.value.clone(), name_span, ty, span: param_start.to(name_span) });
+
+                if !self.check(token::COMMA) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+
+        self.expect(token::R_PARENT, "')'")?;
+        Ok(params)
+    }
+
+    fn parse_command(&mut self, public: bool) -> Result<CommandDecl, ParseError> {
+        let start = self.advance_span();
+        let name = self.expect(token::IDENTIFIER, "command name")?;
+        self.expect(token::L_PARENT, "'('")?;
+        let params = self.parse_params()?;
+        let (fields, end) = self.parse_fields()?;
+        Ok(CommandDecl { public, name: name.value.clone(), name_span: self.span(&name), params, fields, span: start.to(end) })
+    }
+
+    fn parse_domain(&mut self, public: bool) -> Result<DomainDecl, ParseError> {
+        let keyword = self.advance();
+        let kind = DomainKind::from_keyword(&keyword.token_type).expect("domain keyword");
+        let name = self.expect(token::IDENTIFIER, &format!("{} name", kind))?;
+        let (fields, end) = self.parse_fields()?;
+        Ok(DomainDecl { public, kind, name: name.value.clone(), name_span: self.span(&name), fields, span: self.span(&keyword).to(end) })
+    }
+
+    /// `{ name = value; ... }` of a domain or command declaration, with the span of the closing brace.
+    fn parse_fields(&mut self) -> Result<(Vec<DomainField>, Span), ParseError> {
+        self.expect(token::L_BRACE, "'{'")?;
+
+        let mut fields = vec![];
+        while !self.check(token::R_BRACE) && !self.check(token::EOF) {
+            // Fields are often named after the kind they refer to, e.g. `repo = backend;`
+            if !self.check(token::IDENTIFIER) && DomainKind::from_keyword(self.peek_type()).is_none() {
+                return Err(self.error_at_current("Expected field name"));
+            }
+
+            let field_name = self.advance();
+            self.expect(token::ASSIGN, "'='")?;
+            let value = self.parse_expression()?;
+            let end = self.expect_span(token::SEMI, "';'")?;
+            let name_span = self.span(&field_name);
+            fields.push(DomainField { name: field_name.value, name_span, value, span: name_span.to(end) });
+        }
+
+        let end = self.expect_span(token::R_BRACE, "'}'")?;
+        Ok((fields, end))
+    }
+
+    fn parse_block(&mut self) -> Result<Block, ParseError> {
+        let start = self.expect_span(token::L_BRACE, "'{'")?;
+        let mut stmts = vec![];
+
+        while !self.check(token::R_BRACE) && !self.check(token::EOF) {
+            stmts.push(self.parse_statement()?);
+        }
+
+        let end = self.expect_span(token::R_BRACE, "'}'")?;
+        Ok(Block { stmts, span: start.to(end) })
+    }
+
+    fn parse_statement(&mut self) -> Result<Stmt, ParseError> {
+        match self.peek_type() {
+            token::INTEGER | token::RUNE | token::STRING | token::BOOLEAN | token::VAR => self.parse_var_decl(),
+            token::USER | token::REPO | token::CI_CONFIG | token::DEPLOYMENT | token::MANIFEST | token::SECRET | token::COMMAND | token::LIST | token::MAP => self.parse_var_decl(),
+            // `Name variable` declares a variable of a complex type
+            token::IDENTIFIER if self.peek_type_at(1) == token::IDENTIFIER => self.parse_var_decl(),
+            token::IF => self.parse_if(),
+            token::WHILE => self.parse_while(),
+            token::RETURN => self.parse_return(),
+            token::L_BRACE => {
+                let block = self.parse_block()?;
+                Ok(Stmt { span: block.span, kind: StmtKind::Block(block) })
+            }
+            _ => {
+                let expr = self.parse_expression()?;
+                let end = self.expect_span(token::SEMI, "';'")?;
+                Ok(Stmt { span: expr.span.to(end), kind: StmtKind::Expr(expr) })
+            }
+        }
+    }
+
+    fn parse_var_decl(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.span(self.peek());
+        let ty = if self.check(token::VAR) {
+            self.advance();
+            None
+        } else {
+            Some(self.parse_type()?)
+        };
+
+        let name = self.expect(token::IDENTIFIER, "variable name")?;
+        let mut value = None;
+        if self.check(token::ASSIGN) {
+            self.advance();
+            value = Some(self.parse_expression()?);
+        }
+
+        let end = self.expect_span(token::SEMI, "';'")?;
+        Ok(Stmt {
+            kind: StmtKind::VarDecl { name: name.value.clone(), name_span: self.span(&name), ty, value },
+            span: start.to(end),
+        })
+    }
+
+    fn parse_condition(&mut self) -> Result<Expr, ParseError> {
+        self.expect(token::L_PARENT, "'('")?;
+        let condition = self.parse_expression()?;
+        self.expect(token::R_PARENT, "')'")?;
+        Ok(condition)
+    }
+
+    fn parse_if(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.advance_span();
+        let condition = self.parse_condition()?;
+        let then_branch = self.parse_block()?;
+        let mut span = start.to(then_branch.span);
+
+        let mut else_branch = None;
+        if self.check(token::ELSE) {
+            self.advance();
+            let branch = if self.check(token::IF) {
+                self.parse_if()?
+            } else {
+                let block = self.parse_block()?;
+                Stmt { span: block.span, kind: StmtKind::Block(block) }
+            };
+            span = span.to(branch.span);
+            else_branch = Some(Box::new(branch));
+        }
+
+        Ok(Stmt { kind: StmtKind::If { condition, then_branch, else_branch }, span })
+    }
+
+    fn parse_while(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.advance_span();
+        let condition = self.parse_condition()?;
+        let body = self.parse_block()?;
+        Ok(Stmt { span: start.to(body.span), kind: StmtKind::While { condition, body } })
+    }
+
+    fn parse_return(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.advance_span();
+        let mut value = None;
+        if !self.check(token::SEMI) {
+            value = Some(self.parse_expression()?);
+        }
+
+        let end = self.expect_span(token::SEMI, "';'")?;
+        Ok(Stmt { kind: StmtKind::Return(value), span: start.to(end) })
+    }
+
+    pub fn parse_expression(&mut self) -> Result<Expr, ParseError> {
+        self.parse_assignment()
+    }
+
+    fn parse_assignment(&mut self) -> Result<Expr, ParseError> {
+        let target = self.parse_binary(1)?;
+
+        if !self.check(token::ASSIGN) {
+            return Ok(target);
+        }
+
+        if !is_assignable(&target) {
+            return Err(self.error_at("Invalid assignment target", target.span));
+        }
+
+        self.advance();
+        let value = self.parse_assignment()?;
+        Ok(Expr { span: target.span.to(value.span), kind: ExprKind::Assign { target: Box::new(target), value: Box::new(value) } })
+    }
+
+    /// Precedence climbing over `BinaryOp::precedence`, only operators binding at least as tight as `min_precedence` are consumed.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, ParseError> {
+        let mut left = self.parse_unary()?;
+
+        while let Some(op) = BinaryOp::from_token(self.peek_type()) {
+            if op.precedence() < min_precedence {
+                break;
+            }
+
+            self.advance();
+            let right = self.parse_binary(op.precedence() + 1)?;
+            left = Expr { span: left.span.to(right.span), kind: ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) } };
+        }
+
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        let op = match self.peek_type() {
+            token::NOT => UnaryOp::Not,
+            token::MINUS => UnaryOp::Negate,
+            _ => return self.parse_postfix(),
+        };
+
+        let start = self.advance_span();
+        let operand = self.parse_unary()?;
+        Ok(Expr { span: start.to(operand.span), kind: ExprKind::Unary { op, operand: Box::new(operand) } })
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_primary()?;
+
+        loop {
+            match self.peek_type() {
+                token::L_PARENT => {
+                    self.advance();
+                    let mut args = vec![];
+                    if !self.check(token::R_PARENT) {
+                        loop {
+                            args.push(self.parse_expression()?);
+                            if !self.check(token::COMMA) {
+                                break;
+                            }
+                            self.advance();
+                        }
+                    }
+
+                    let end = self.expect_span(token::R_PARENT, "')'")?;
+                    expr = Expr { span: expr.span.to(end), kind: ExprKind::Call { callee: Box::new(expr), args } };
+                }
+                token::POST_INCREMENT | token::POST_DECREMENT => {
+                    let token = self.advance();
+                    if !is_assignable(&expr) {
+                        return Err(self.error_at(&format!("Only variables can be used with {}", token.value), self.span(&token)));
+                    }
+
+                    let op = if token.token_type == token::POST_INCREMENT { PostfixOp::Increment } else { PostfixOp::Decrement };
+                    expr = Expr { span: expr.span.to(self.span(&token)), kind: ExprKind::Postfix { op, operand: Box::new(expr) } };
+                }
+                token::DOT => {
+                    self.advance();
+                    let field = self.expect(token::IDENTIFIER, "field name")?;
+                    let field_span = self.span(&field);
+                    expr = Expr { span: expr.span.to(field_span), kind: ExprKind::Field { object: Box::new(expr), field: field.value, field_span } };
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    /// Whether an identifier starts a complex literal: `Name {}` or `Name { field: ...`.
+    fn at_complex_literal(&self) -> bool {
+        self.peek_type_at(1) == token::L_BRACE
+            && (self.peek_type_at(2) == token::R_BRACE
+                || (self.peek_type_at(2) == token::IDENTIFIER && self.peek_type_at(3) == token::COLON))
+    }
+
+    fn parse_complex_literal(&mut self) -> Result<Expr, ParseError> {
+        let name = self.advance();
+        self.expect(token::L_BRACE, "'{'")?;
+
+        let mut fields = vec![];
+        if !self.check(token::R_BRACE) {
+            loop {
+                let field_name = self.expect(token::IDENTIFIER, "field name")?;
+                self.expect(token::COLON, "':'")?;
+                let value = self.parse_expression()?;
+                fields.push(FieldInit { name: field_name.value.clone(), name_span: self.span(&field_name), value });
+
+                if !self.check(token::COMMA) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+
+        let end = self.expect_span(token::R_BRACE, "'}'")?;
+        let name_span = self.span(&name);
+        Ok(Expr { span: name_span.to(end), kind: ExprKind::ComplexLiteral { name: name.value, name_span, fields } })
+    }
+
+    /// `[value, ...]` or `[key: value, ...]`, `[:]` being the empty map.
+    fn parse_collection_literal(&mut self) -> Result<Expr, ParseError> {
+        let start = self.advance_span();
+
+        if self.check(token::COLON) {
+            self.advance();
+            let end = self.expect_span(token::R_BRACKET, "']'")?;
+            return Ok(Expr { kind: ExprKind::Map(vec![]), span: start.to(end) });
+        }
+
+        // The first element decides between a list and a map
+        let mut values = vec![];
+        let mut entries = vec![];
+        if !self.check(token::R_BRACKET) {
+            loop {
+                let value = self.parse_expression()?;
+                if values.is_empty() && (!entries.is_empty() || self.check(token::COLON)) {
+                    self.expect(token::COLON, "':'")?;
+                    entries.push((value, self.parse_expression()?));
+                } else {
+                    values.push(value);
+                }
+
+                if !self.check(token::COMMA) {
+                    break;
+                }
+                self.advance();
+            }
+        }
+
+        let end = self.expect_span(token::R_BRACKET, "']'")?;
+        let kind = if entries.is_empty() { ExprKind::List(values) } else { ExprKind::Map(entries) };
+        Ok(Expr { kind, span: start.to(end) })
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        let token = self.peek().clone();
+        let span = self.span(&token);
+
+        let kind = match token.token_type.as_str() {
+            token::INTEGER_LITERAL => match token.value.parse::<i64>() {
+                Ok(value) => ExprKind::Integer(value),
+                Err(_) => return Err(self.error_at(&format!("Invalid integer literal {}", token.value), span)),
+            },
+            token::STRING_LITERAL => ExprKind::String(token.value.clone()),
+            token::RUNE_LITERAL => ExprKind::Rune(token.value.chars().next().unwrap_or('\0')),
+            token::BOOLEAN_LITERAL => ExprKind::Boolean(char_utils::return_boolean_value(&token.value)),
+            token::IDENTIFIER if self.at_complex_literal() => return self.parse_complex_literal(),
+            token::IDENTIFIER => ExprKind::Identifier(token.value.clone()),
+            token::L_BRACKET => return self.parse_collection_literal(),
+            token::L_PARENT => {
+                self.advance();
+                let mut expr = self.parse_expression()?;
+                let end = self.expect_span(token::R_PARENT, "')'")?;
+                expr.span = span.to(end);
+                return Ok(expr);
+            }
+            _ => return Err(self.error_at_current("Expected an expression")),
+        };
+
+        self.advance();
+        Ok(Expr { kind, span })
+    }
+}
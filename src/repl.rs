@@ -0,0 +1,250 @@
+//! `shanty repl`: reads inputs, runs each on top of the ones before it and prints the value of expressions.
+//!
+//! The inputs accepted so far make up the root file of a session. Each new input is checked with the earlier ones before
+//! it, so that it can use their declarations, but only the new input runs: the values of the globals and domain
+//! declarations, loaded secrets included, are kept from one input to the next.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use shanty::ast::{Item, Stmt, StmtKind};
+use shanty::checker;
+use shanty::command;
+use shanty::diagnostic::{Diagnostic, Severity};
+use shanty::editor::{Editor, Input};
+use shanty::interpreter;
+use shanty::lexer::Lexer;
+use shanty::module::{self, FileResolver, FsResolver, Modules};
+use shanty::parser;
+use shanty::resolve;
+use shanty::token;
+use shanty::value::Value;
+
+use crate::cli::{self, Output};
+
+/// Name of the root file made of the inputs, in diagnostics.
+const SESSION: &str = "<repl>";
+
+const HELP: &str = "Enter declarations, statements or expressions, the value of expressions is printed.
+Input continues on the next line while a (, [ or { is left open.
+
+    :tokens <code>    Print the tokens of code
+    :ast <code>       Print the syntax tree of code
+    :type <expr>      Print the type of an expression
+    :load <file>      Run a file as if its text was entered
+    :help             Print this help
+    :quit             Leave, like Ctrl-D";
+
+pub fn run(args: &[String], output: &Output) -> i32 {
+    if !args.is_empty() {
+        eprintln!("{}", cli::USAGE);
+        return 2;
+    }
+
+    let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(".shanty_history"));
+    let mut editor = Editor::new(history);
+    let mut session = Session::default();
+    if editor.is_terminal() {
+        println!("shanty repl, :help for help");
+    }
+
+    loop {
+        let input = match read_input(&mut editor) {
+            Ok(Some(input)) => input,
+            Ok(None) => return 0,
+            Err(e) => {
+                eprintln!("Can't read input: {}", e);
+                return 1;
+            }
+        };
+
+        let trimmed = input.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed.starts_with(':') {
+            if !session.meta(trimmed, output) {
+                return 0;
+            }
+            continue;
+        }
+        session.eval(&input, output);
+    }
+}
+
+/// Reads lines until the brackets they open are closed. `None` at the end of input.
+fn read_input(editor: &mut Editor) -> io::Result<Option<String>> {
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() { ">>> " } else { "... " };
+        match editor.read_line(prompt)? {
+            Input::Line(line) => {
+                editor.add_history(&line);
+                input.push_str(&line);
+                input.push('\n');
+            }
+            Input::Interrupted => input.clear(),
+            Input::Eof if input.is_empty() => return Ok(None),
+            Input::Eof => return Ok(Some(input)),
+        }
+
+        // Meta-commands are a single line
+        if !input.trim_start().starts_with(':') && depth(&input) > 0 {
+            continue;
+        }
+        if !input.is_empty() {
+            return Ok(Some(input));
+        }
+    }
+}
+
+/// Number of brackets left open in `text`.
+fn depth(text: &str) -> i64 {
+    Lexer::new(text)
+        .all_tokens()
+        .iter()
+        .map(|token| match token.token_type.as_str() {
+            token::L_PARENT | token::L_BRACE | token::L_BRACKET => 1,
+            token::R_PARENT | token::R_BRACE | token::R_BRACKET => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// An input as a complete item, with the semicolon it needs to parse added if it was left out.
+fn complete(input: &str) -> String {
+    let input = input.trim_end();
+    if parser::parse(input).is_err() {
+        let terminated = format!("{}\n;", input);
+        if parser::parse(&terminated).is_ok() {
+            return format!("{}\n", terminated);
+        }
+    }
+    format!("{}\n", input)
+}
+
+#[derive(Default)]
+struct Session {
+    /// The inputs accepted so far
+    source: String,
+    /// Number of items of the accepted inputs, the first of the next input
+    items: usize,
+    state: interpreter::Session,
+    runner: command::Local,
+}
+
+impl Session {
+    /// Loads the session with `input` after it, printing the errors that stop it from running.
+    fn load(&self, input: &str, output: &Output) -> Option<(Modules, String)> {
+        let source = format!("{}{}", self.source, complete(input));
+        let resolver = SessionResolver { source: &source };
+        let modules = match module::load_with(Path::new(SESSION), &resolver) {
+            Ok(modules) => modules,
+            Err((modules, errors)) => {
+                let diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+                output.emit(&diagnostics, &modules);
+                return None;
+            }
+        };
+
+        // Warnings about earlier inputs would be printed again with each input, so only errors are shown
+        let errors: Vec<Diagnostic> = cli::analyze(&modules).into_iter().filter(|d| d.severity == Severity::Error).collect();
+        if output.emit(&errors, &modules) != 0 {
+            return None;
+        }
+        Some((modules, source))
+    }
+
+    /// Runs `input` after the accepted ones, keeping it if it runs to completion.
+    fn eval(&mut self, input: &str, output: &Output) {
+        let (modules, source) = match self.load(input, output) {
+            Some(loaded) => loaded,
+            None => return,
+        };
+
+        let resolution = resolve::resolve_modules(&modules);
+        match interpreter::run_session_with(&modules, &resolution, &mut self.runner, &mut self.state, self.items) {
+            Ok(value) => {
+                self.items = modules.files[0].program.items.len();
+                self.source = source;
+                if value != Value::Void {
+                    println!("{}", value);
+                }
+            }
+            Err(error) => {
+                output.emit(&[Diagnostic::from(&error)], &modules);
+            }
+        }
+    }
+
+    /// Carries out a meta-command, returning false to leave.
+    fn meta(&mut self, line: &str, output: &Output) -> bool {
+        let (command, argument) = line.split_once(char::is_whitespace).map_or((line, ""), |(command, argument)| (command, argument.trim()));
+        match command {
+            ":tokens" => {
+                for token in Lexer::new(argument).all_tokens() {
+                    println!("{}:{} {} {:?}", token.line, token.column, token.token_type, token.value);
+                }
+            }
+            ":ast" => match parser::parse(&complete(argument)) {
+                Ok(program) => println!("{:#?}", program.items),
+                Err(error) => {
+                    output.emit(&[Diagnostic::from(&error)], &shanty::diagnostic::Source { name: SESSION, text: argument });
+                }
+            },
+            ":type" => self.print_type(argument, output),
+            ":load" => match fs::read_to_string(argument) {
+                Ok(text) => self.eval(&text, output),
+                Err(e) => eprintln!("Can't read {}: {}", argument, e),
+            },
+            ":help" => println!("{}", HELP),
+            ":quit" | ":q" => return false,
+            _ => eprintln!("Unknown command {}, :help lists them", command),
+        }
+        true
+    }
+
+    fn print_type(&self, input: &str, output: &Output) {
+        // The last item of the session would be taken for the expression otherwise
+        if input.trim().is_empty() {
+            eprintln!(":type expects an expression");
+            return;
+        }
+        let (modules, _) = match self.load(input, output) {
+            Some(loaded) => loaded,
+            None => return,
+        };
+
+        let expr = match modules.files[0].program.items.last() {
+            Some(Item::Stmt(Stmt { kind: StmtKind::Expr(expr), .. })) => expr,
+            _ => {
+                eprintln!(":type expects an expression");
+                return;
+            }
+        };
+        match checker::infer_modules(&modules).expressions.get(&expr.span) {
+            Some(ty) => println!("{}", ty),
+            None => println!("unknown"),
+        }
+    }
+}
+
+/// Serves the session as the root file, imports being read from the file system relative to the working directory.
+struct SessionResolver<'s> {
+    source: &'s str,
+}
+
+impl FileResolver for SessionResolver<'_> {
+    fn resolve(&self, importer: &Path, import_path: &str) -> PathBuf {
+        FsResolver.resolve(importer, import_path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        if path == Path::new(SESSION) {
+            return Ok(self.source.to_string());
+        }
+        FsResolver.read(path)
+    }
+}
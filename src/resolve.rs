@@ -0,0 +1,328 @@
+use std::collections::{HashMap, HashSet};
+
+use core::fmt::Display;
+
+use crate::ast::{Block, CommandDecl, DomainKind, Expr, ExprKind, FuncDecl, Item, Param, Program, Span, Stmt, StmtKind};
+use crate::module::Modules;
+use crate::stdlib;
+use crate::suggest;
+use crate::visit::{self, Visitor};
+
+pub type SymbolId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    Parameter,
+    Function,
+    Command,
+    Complex,
+    Domain(DomainKind),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Span of the declared name
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameError {
+    pub message: String,
+    pub span: Span,
+    /// Shadowing is only worth a warning, everything else is an error
+    pub warning: bool,
+    /// The earlier declaration involved in duplicate and shadowing reports
+    pub previous: Option<Span>,
+}
+
+impl Display for NameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Result of name resolution: every declared symbol and the declaration each identifier refers to.
+#[derive(Debug, Default)]
+pub struct Resolution {
+    pub symbols: Vec<Symbol>,
+    /// Span of an identifier use to the symbol it refers to
+    pub uses: HashMap<Span, SymbolId>,
+    pub errors: Vec<NameError>,
+}
+
+impl Resolution {
+    /// Declaration referred to by the identifier at `use_span`.
+    pub fn definition(&self, use_span: Span) -> Option<&Symbol> {
+        self.uses.get(&use_span).map(|&id| &self.symbols[id])
+    }
+
+    /// Symbol declared at `span`, or referred to from it.
+    pub fn symbol_at(&self, span: Span) -> Option<SymbolId> {
+        self.symbols.iter().position(|s| s.span == span).or_else(|| self.uses.get(&span).copied())
+    }
+
+    /// Spans of every use of a symbol, in source order.
+    pub fn references(&self, symbol: SymbolId) -> Vec<Span> {
+        let mut spans: Vec<Span> = self.uses.iter().filter(|(_, &id)| id == symbol).map(|(&span, _)| span).collect();
+        spans.sort_by_key(|s| (s.file, s.line, s.column));
+        spans
+    }
+}
+
+/// Binds identifier uses to their declarations in a single file.
+pub fn resolve(program: &Program) -> Resolution {
+    let mut resolver = Resolver::new();
+    resolver.visit_program(program);
+    resolver.finish()
+}
+
+/// Resolves every loaded file, each one seeing the `pub` declarations of the files it imports.
+pub fn resolve_modules(modules: &Modules) -> Resolution {
+    let mut resolution = Resolution::default();
+
+    for &file in &modules.order {
+        let mut resolver = Resolver::new();
+        resolver.symbols = std::mem::take(&mut resolution.symbols);
+
+        for &import in &modules.files[file].imports {
+            resolver.import(&modules.files[import].program);
+        }
+        resolver.visit_program(&modules.files[file].program);
+
+        let file_resolution = resolver.finish();
+        resolution.symbols = file_resolution.symbols;
+        resolution.uses.extend(file_resolution.uses);
+        resolution.errors.extend(file_resolution.errors);
+    }
+
+    resolution
+}
+
+struct Resolver {
+    symbols: Vec<Symbol>,
+    scopes: Vec<HashMap<String, SymbolId>>,
+    /// Complex types live in their own namespace
+    types: HashMap<String, SymbolId>,
+    /// Declarations of imported files that aren't `pub`, to explain why they can't be found
+    private: HashMap<String, Span>,
+    /// Symbols of the `pub` declarations of imported files
+    imported: HashSet<SymbolId>,
+    uses: HashMap<Span, SymbolId>,
+    errors: Vec<NameError>,
+}
+
+impl Resolver {
+    fn new() -> Self {
+        Resolver { symbols: vec![], scopes: vec![HashMap::new()], types: HashMap::new(), private: HashMap::new(), imported: HashSet::new(), uses: HashMap::new(), errors: vec![] }
+    }
+
+    fn finish(mut self) -> Resolution {
+        self.errors.sort_by_key(|e| (e.span.file, e.span.line, e.span.column));
+        Resolution { symbols: self.symbols, uses: self.uses, errors: self.errors }
+    }
+
+    fn import(&mut self, program: &Program) {
+        for item in &program.items {
+            let (name, public) = match item.declared_name() {
+                Some(declared) => declared,
+                None => continue,
+            };
+
+            if !public {
+                self.private.insert(name.to_string(), item.span());
+                continue;
+            }
+
+            // The imported file was resolved first, so its declarations already have symbols
+            let span = match item {
+                Item::Func(func) => func.name_span,
+                Item::Domain(domain) => domain.name_span,
+                Item::Command(command) => command.name_span,
+                Item::Complex(complex) => complex.name_span,
+                Item::Import(_) | Item::Stmt(_) => continue,
+            };
+            let id = match self.symbols.iter().position(|s| s.span == span) {
+                Some(id) => id,
+                None => continue,
+            };
+            let names = match item {
+                Item::Complex(_) => &mut self.types,
+                _ => &mut self.scopes[0],
+            };
+            // The first file to declare a name keeps it, a later one makes its uses ambiguous
+            if let Some(&first) = names.get(name) {
+                if first != id {
+                    let previous = self.symbols[first].span;
+                    self.errors.push(NameError { message: format!("Ambiguous import of {}", name), span, warning: false, previous: Some(previous) });
+                }
+                continue;
+            }
+            names.insert(name.to_string(), id);
+            self.imported.insert(id);
+        }
+    }
+
+    fn add_type(&mut self, name: &str, span: Span) {
+        let id = self.symbols.len();
+        self.symbols.push(Symbol { name: name.to_string(), kind: SymbolKind::Complex, span });
+        self.types.insert(name.to_string(), id);
+    }
+
+    fn add_symbol(&mut self, name: &str, kind: SymbolKind, span: Span) {
+        let id = self.symbols.len();
+        self.symbols.push(Symbol { name: name.to_string(), kind, span });
+        self.scopes.last_mut().expect("scope").insert(name.to_string(), id);
+    }
+
+    fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).copied()
+    }
+
+    fn declare(&mut self, name: &str, kind: SymbolKind, span: Span) {
+        if let Some(&existing) = self.scopes.last().expect("scope").get(name) {
+            // A function or command of the file replaces an imported one, duplicates within the file are reported by
+            // the type checker
+            if self.symbols[existing].kind == kind && matches!(kind, SymbolKind::Function | SymbolKind::Command) {
+                if self.imported.contains(&existing) {
+                    self.add_symbol(name, kind, span);
+                }
+                return;
+            }
+            let previous = self.symbols[existing].span;
+            self.errors.push(NameError { message: format!("{} is already declared in this scope", name), span, warning: false, previous: Some(previous) });
+            return;
+        }
+
+        if let Some(existing) = self.lookup(name) {
+            let previous = self.symbols[existing].span;
+            self.errors.push(NameError {
+                message: format!("{} shadows a declaration from an enclosing scope", name),
+                span,
+                warning: true,
+                previous: Some(previous),
+            });
+        }
+
+        self.add_symbol(name, kind, span);
+    }
+
+    fn use_name(&mut self, name: &str, span: Span) {
+        if let Some(id) = self.lookup(name) {
+            self.uses.insert(span, id);
+            return;
+        }
+
+        // Builtins are called by name, without a symbol
+        if stdlib::get(name).is_some() {
+            return;
+        }
+
+        if let Some(&declared) = self.private.get(name) {
+            self.errors.push(NameError {
+                message: format!("{} is not pub in the file that declares it", name),
+                span,
+                warning: false,
+                previous: Some(declared),
+            });
+            return;
+        }
+
+        let mut visible: Vec<&str> = self.scopes.iter().flat_map(|scope| scope.keys().map(|k| k.as_str())).collect();
+        visible.extend(stdlib::BUILTINS.iter().map(|builtin| builtin.name));
+        let message = match suggest::closest(name, visible) {
+            Some(suggestion) => format!("Undefined name {}, did you mean {}?", name, suggestion),
+            None => format!("Undefined name {}", name),
+        };
+        self.errors.push(NameError { message, span, warning: false, previous: None });
+    }
+
+    fn with_scope<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        self.scopes.push(HashMap::new());
+        f(self);
+        self.scopes.pop();
+    }
+}
+
+impl<'ast> Visitor<'ast> for Resolver {
+    /// Declarations are visible from anywhere in the file, top-level variables only after they're declared.
+    /// Function and command bodies are resolved last, so that they see every top-level variable.
+    fn visit_program(&mut self, program: &'ast Program) {
+        for item in &program.items {
+            match item {
+                Item::Func(func) => self.declare(&func.name, SymbolKind::Function, func.name_span),
+                Item::Domain(domain) => self.declare(&domain.name, SymbolKind::Domain(domain.kind), domain.name_span),
+                Item::Command(command) => self.declare(&command.name, SymbolKind::Command, command.name_span),
+                Item::Complex(complex) => {
+                    // Duplicate complex types are reported by the type checker
+                    self.add_type(&complex.name, complex.name_span);
+                }
+                Item::Import(_) | Item::Stmt(_) => {}
+            }
+        }
+
+        for item in &program.items {
+            if !matches!(item, Item::Func(_) | Item::Command(_)) {
+                self.visit_item(item);
+            }
+        }
+
+        for item in &program.items {
+            if matches!(item, Item::Func(_) | Item::Command(_)) {
+                self.visit_item(item);
+            }
+        }
+    }
+
+    fn visit_func(&mut self, func: &'ast FuncDecl) {
+        // Parameters and the body share a scope, a local can't redeclare a parameter
+        self.with_scope(|resolver| {
+            for param in &func.params {
+                resolver.visit_param(param);
+            }
+            visit::walk_block(resolver, &func.body);
+        });
+    }
+
+    fn visit_command(&mut self, command: &'ast CommandDecl) {
+        self.with_scope(|resolver| visit::walk_command(resolver, command));
+    }
+
+    fn visit_param(&mut self, param: &'ast Param) {
+        self.declare(&param.name, SymbolKind::Parameter, param.name_span);
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        self.with_scope(|resolver| visit::walk_block(resolver, block));
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        match &stmt.kind {
+            StmtKind::VarDecl { name, name_span, value, .. } => {
+                // The initializer can't refer to the variable it initializes
+                if let Some(value) = value {
+                    self.visit_expr(value);
+                }
+                self.declare(name, SymbolKind::Variable, *name_span);
+            }
+            _ => visit::walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Identifier(name) => self.use_name(name, expr.span),
+            ExprKind::ComplexLiteral { name, name_span, .. } => {
+                // Unknown types are reported by the type checker
+                if let Some(&id) = self.types.get(name) {
+                    self.uses.insert(*name_span, id);
+                }
+            }
+            _ => {}
+        }
+
+        visit::walk_expr(self, expr)
+    }
+}
@@ -0,0 +1,112 @@
+//! Secret values, loaded by `secret` declarations from an environment variable or a local secrets file.
+//!
+//! A secret remembers the loaded values it's made of, through concatenation with plain text, so that they can be masked
+//! in command output, plans and error messages.
+
+use std::env;
+use std::fs;
+
+use crate::command::Runner;
+use crate::value::Value;
+
+pub const ENV: &str = "env";
+pub const FILE: &str = "file";
+pub const KEY: &str = "key";
+
+/// Shown in place of a secret value.
+pub const MASK: &str = "****";
+
+/// Text holding secret values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Secret {
+    pub text: String,
+    /// Loaded values found in the text
+    pub values: Vec<String>,
+}
+
+impl Secret {
+    /// A secret that is a single loaded value.
+    pub fn new(value: String) -> Secret {
+        Secret { values: vec![value.clone()], text: value }
+    }
+
+    /// Concatenation of two parts, secret or plain, the result keeping the values of both.
+    pub fn concat(left: (&str, &[String]), right: (&str, &[String])) -> Secret {
+        let mut values = left.1.to_vec();
+        values.extend(right.1.iter().filter(|value| !left.1.contains(value)).cloned());
+        Secret { text: format!("{}{}", left.0, right.0), values }
+    }
+}
+
+/// Replaces every occurrence of `values` in `text` with `MASK`, longer values first so that none is partly shown.
+pub fn mask(text: &str, values: &[String]) -> String {
+    let mut values: Vec<&String> = values.iter().filter(|value| !value.is_empty()).collect();
+    values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+
+    let mut masked = text.to_string();
+    for value in values {
+        masked = masked.replace(value.as_str(), MASK);
+    }
+    masked
+}
+
+/// Where the value of a secret comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Env(String),
+    /// A key of a file of `key = value` lines, lines starting with `#` being comments
+    File { path: String, key: String },
+}
+
+impl Source {
+    /// Reads the fields evaluated from a secret declaration named `secret`.
+    pub fn from_settings(secret: &str, settings: &Value) -> Result<Source, String> {
+        let string = |field: &str| match settings.field(field) {
+            Some(Value::String(text)) => Ok(Some(text.clone())),
+            Some(other) => Err(format!("Field {} of secret {} expects string but got {}", field, secret, other.type_name())),
+            None => Ok(None),
+        };
+
+        match (string(ENV)?, string(FILE)?) {
+            (Some(_), Some(_)) => Err(format!("Secret {} sets both {} and {}", secret, ENV, FILE)),
+            (Some(_), None) if settings.field(KEY).is_some() => Err(format!("Secret {} sets {} without {}", secret, KEY, FILE)),
+            (Some(name), None) => Ok(Source::Env(name)),
+            (None, Some(path)) => Ok(Source::File { path, key: string(KEY)?.unwrap_or_else(|| secret.to_string()) }),
+            (None, None) => Err(format!("Secret {} sets neither {} nor {}", secret, ENV, FILE)),
+        }
+    }
+
+    /// Reads the value from the environment of the process or the file, relative to the working directory.
+    pub fn load(&self) -> Result<String, String> {
+        match self {
+            Source::Env(name) => env::var(name).map_err(|_| format!("Environment variable {} isn't set", name)),
+            Source::File { path, key } => {
+                let text = fs::read_to_string(path).map_err(|e| format!("Can't read secrets file {}: {}", path, e))?;
+                lookup(&text, key).map_err(|e| format!("{}: {}", path, e))?.ok_or_else(|| format!("Secrets file {} has no key {}", path, key))
+            }
+        }
+    }
+}
+
+/// Loads the secret declared as `name` with the fields evaluated into `settings`.
+pub fn load(name: &str, settings: &Value, runner: &mut dyn Runner) -> Result<Value, String> {
+    let source = Source::from_settings(name, settings)?;
+    Ok(Value::Secret(Secret::new(runner.secret(name, &source)?)))
+}
+
+/// Value of `key` in the `key = value` lines of a secrets file.
+fn lookup(text: &str, key: &str) -> Result<Option<String>, String> {
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((name, value)) if name.trim() == key => return Ok(Some(value.trim().to_string())),
+            Some(_) => {}
+            None => return Err(format!("Expected key = value at line {}", i + 1)),
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,553 @@
+//! Builtin functions, callable from any file unless a declaration of the same name hides them.
+//!
+//! The type checker reads their signatures, both backends call their implementations, and editors can show the
+//! signatures and doc strings as they are.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use crate::ast::Type;
+use crate::checker;
+use crate::value::{self, Value};
+
+/// A function provided by shanty rather than declared in source.
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    /// Overloads as written in source, e.g. `len(string text) -> integer`.
+    /// `T`, `K` and `V` stand for any type, the same one throughout a call
+    pub signatures: &'static [&'static str],
+    pub doc: &'static str,
+    function: fn(&[Value]) -> Result<Value, String>,
+}
+
+/// Parameters and return type of one overload of a builtin, type variables being complex types named `T`, `K` or `V`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub params: Vec<(String, Type)>,
+    pub ret: Type,
+}
+
+impl Signature {
+    fn parse(text: &str) -> Signature {
+        let (head, ret) = text.split_once(" -> ").expect("signature has a return type");
+        let open = head.find('(').expect("signature has parameters");
+        let params = split_top_level(&head[open + 1..head.len() - 1])
+            .into_iter()
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let (ty, name) = param.rsplit_once(' ').expect("parameter has a type and a name");
+                (name.to_string(), parse_type(ty))
+            })
+            .collect();
+        Signature { params, ret: parse_type(ret) }
+    }
+}
+
+impl Builtin {
+    pub fn signatures(&self) -> Vec<Signature> {
+        self.signatures.iter().map(|text| Signature::parse(text)).collect()
+    }
+
+    /// Type returned by a call with arguments of the given types, unknown ones matching any parameter.
+    /// Fails with a message and the index of the argument to blame, when a single one is.
+    pub fn check(&self, args: &[Option<Type>]) -> Result<Option<Type>, (String, Option<usize>)> {
+        let signatures = self.signatures();
+        let candidates: Vec<(&str, &Signature)> =
+            self.signatures.iter().zip(&signatures).filter(|(_, signature)| signature.params.len() == args.len()).map(|(text, signature)| (*text, signature)).collect();
+
+        if candidates.is_empty() {
+            let mut counts: Vec<usize> = signatures.iter().map(|signature| signature.params.len()).collect();
+            counts.sort_unstable();
+            counts.dedup();
+            let counts: Vec<String> = counts.iter().map(usize::to_string).collect();
+            return Err((format!("Builtin {} expects {} arguments but got {}", self.name, counts.join(" or "), args.len()), None));
+        }
+
+        for (_, signature) in &candidates {
+            let mut vars = HashMap::new();
+            if signature.params.iter().zip(args).all(|((_, param), arg)| arg.as_ref().is_none_or(|arg| bind(param, arg, &mut vars))) {
+                return Ok(substitute(&signature.ret, &vars));
+            }
+        }
+
+        if let [(_, signature)] = candidates[..] {
+            let mut vars = HashMap::new();
+            for (i, ((_, param), arg)) in signature.params.iter().zip(args).enumerate() {
+                if let Some(arg) = arg {
+                    if !bind(param, arg, &mut vars) {
+                        let expected = substitute(param, &vars).unwrap_or_else(|| param.clone());
+                        return Err((checker::mismatch(&format!("Argument {} of {}", i + 1, self.name), &expected, arg), Some(i)));
+                    }
+                }
+            }
+        }
+
+        let types: Vec<String> = args.iter().map(|arg| arg.as_ref().map_or("?".to_string(), Type::to_string)).collect();
+        let texts: Vec<&str> = candidates.iter().map(|(text, _)| *text).collect();
+        Err((format!("Builtin {} can't take ({}), it takes {}", self.name, types.join(", "), texts.join(" or ")), None))
+    }
+
+    /// Calls the builtin, failing with a message when the arguments are outside of what it accepts.
+    pub fn call(&self, args: &[Value]) -> Result<Value, String> {
+        (self.function)(args)
+    }
+}
+
+/// The builtin named `name`.
+pub fn get(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+fn is_variable(name: &str) -> bool {
+    matches!(name, "T" | "K" | "V")
+}
+
+/// Binds the type variables of `param` so that it matches `arg`, failing if a variable is already bound to another type.
+fn bind(param: &Type, arg: &Type, vars: &mut HashMap<String, Type>) -> bool {
+    match (param, arg) {
+        (Type::Complex(name), _) if is_variable(name) => match vars.get(name) {
+            Some(bound) => bound == arg,
+            None => {
+                vars.insert(name.clone(), arg.clone());
+                true
+            }
+        },
+        (Type::List(param), Type::List(arg)) => bind(param, arg, vars),
+        (Type::Map(param_key, param_value), Type::Map(arg_key, arg_value)) => bind(param_key, arg_key, vars) && bind(param_value, arg_value, vars),
+        _ => param == arg,
+    }
+}
+
+/// `ty` with its type variables replaced by their binding, `None` if one isn't bound.
+fn substitute(ty: &Type, vars: &HashMap<String, Type>) -> Option<Type> {
+    match ty {
+        Type::Complex(name) if is_variable(name) => vars.get(name).cloned(),
+        Type::List(element) => Some(Type::List(Box::new(substitute(element, vars)?))),
+        Type::Map(key, value) => Some(Type::Map(Box::new(substitute(key, vars)?), Box::new(substitute(value, vars)?))),
+        ty => Some(ty.clone()),
+    }
+}
+
+fn parse_type(text: &str) -> Type {
+    let text = text.trim();
+    if let Some(element) = text.strip_prefix("list<").and_then(|rest| rest.strip_suffix('>')) {
+        return Type::List(Box::new(parse_type(element)));
+    }
+    if let Some(entry) = text.strip_prefix("map<").and_then(|rest| rest.strip_suffix('>')) {
+        let types = split_top_level(entry);
+        return Type::Map(Box::new(parse_type(types[0])), Box::new(parse_type(types[1])));
+    }
+    Type::from_keyword(text).unwrap_or_else(|| Type::Complex(text.to_string()))
+}
+
+/// Splits at the commas that aren't within angle brackets.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(text[start..].trim());
+    parts
+}
+
+/// Error for arguments no overload accepts, which the type checker normally rules out.
+fn unsupported(name: &str, args: &[Value]) -> String {
+    let types: Vec<&str> = args.iter().map(Value::type_name).collect();
+    format!("Builtin {} can't take ({})", name, types.join(", "))
+}
+
+fn index(list: &[Value], index: i64) -> Result<usize, String> {
+    match usize::try_from(index) {
+        Ok(i) if i < list.len() => Ok(i),
+        _ => Err(format!("Index {} is out of bounds for a list of length {}", index, list.len())),
+    }
+}
+
+fn integer(value: usize) -> Value {
+    Value::Integer(value as i64)
+}
+
+pub static BUILTINS: [Builtin; 32] = [
+    Builtin {
+        name: "len",
+        signatures: &["len(string text) -> integer", "len(list<T> items) -> integer", "len(map<K, V> entries) -> integer"],
+        doc: "Number of runes in a string, elements in a list or entries in a map.",
+        function: |args| match args {
+            [Value::String(text)] => Ok(integer(text.chars().count())),
+            [Value::List(items)] => Ok(integer(items.len())),
+            [Value::Map(entries)] => Ok(integer(entries.len())),
+            _ => Err(unsupported("len", args)),
+        },
+    },
+    Builtin {
+        name: "split",
+        signatures: &["split(string text, string separator) -> list<string>"],
+        doc: "Parts of `text` between the occurrences of `separator`, or its runes when `separator` is empty.",
+        function: |args| match args {
+            [Value::String(text), Value::String(separator)] if separator.is_empty() => Ok(Value::List(text.chars().map(|c| Value::String(c.to_string())).collect())),
+            [Value::String(text), Value::String(separator)] => Ok(Value::List(text.split(separator.as_str()).map(|part| Value::String(part.to_string())).collect())),
+            _ => Err(unsupported("split", args)),
+        },
+    },
+    Builtin {
+        name: "join",
+        signatures: &["join(list<string> parts, string separator) -> string"],
+        doc: "The parts with `separator` between each of them.",
+        function: |args| match args {
+            [Value::List(parts), Value::String(separator)] => {
+                let mut texts = Vec::with_capacity(parts.len());
+                for part in parts {
+                    match part {
+                        Value::String(text) => texts.push(text.as_str()),
+                        other => return Err(format!("Builtin join expects a list of strings but got a {}", other.type_name())),
+                    }
+                }
+                Ok(Value::String(texts.join(separator)))
+            }
+            _ => Err(unsupported("join", args)),
+        },
+    },
+    Builtin {
+        name: "trim",
+        signatures: &["trim(string text) -> string"],
+        doc: "`text` without its leading and trailing whitespace.",
+        function: |args| match args {
+            [Value::String(text)] => Ok(Value::String(text.trim().to_string())),
+            _ => Err(unsupported("trim", args)),
+        },
+    },
+    Builtin {
+        name: "replace",
+        signatures: &["replace(string text, string from, string to) -> string"],
+        doc: "`text` with every occurrence of `from`, which can't be empty, replaced by `to`.",
+        function: |args| match args {
+            [Value::String(_), Value::String(from), Value::String(_)] if from.is_empty() => Err("Builtin replace can't replace an empty string".to_string()),
+            [Value::String(text), Value::String(from), Value::String(to)] => Ok(Value::String(text.replace(from.as_str(), to))),
+            _ => Err(unsupported("replace", args)),
+        },
+    },
+    Builtin {
+        name: "contains",
+        signatures: &["contains(string text, string part) -> boolean", "contains(list<T> items, T item) -> boolean", "contains(map<K, V> entries, K key) -> boolean"],
+        doc: "Whether a string contains `part`, a list contains `item` or a map has an entry for `key`.",
+        function: |args| match args {
+            [Value::String(text), Value::String(part)] => Ok(Value::Boolean(text.contains(part.as_str()))),
+            [Value::List(items), item] => Ok(Value::Boolean(items.contains(item))),
+            [Value::Map(entries), key] => Ok(Value::Boolean(entries.iter().any(|(k, _)| k == key))),
+            _ => Err(unsupported("contains", args)),
+        },
+    },
+    Builtin {
+        name: "starts_with",
+        signatures: &["starts_with(string text, string prefix) -> boolean"],
+        doc: "Whether `text` starts with `prefix`.",
+        function: |args| match args {
+            [Value::String(text), Value::String(prefix)] => Ok(Value::Boolean(text.starts_with(prefix.as_str()))),
+            _ => Err(unsupported("starts_with", args)),
+        },
+    },
+    Builtin {
+        name: "ends_with",
+        signatures: &["ends_with(string text, string suffix) -> boolean"],
+        doc: "Whether `text` ends with `suffix`.",
+        function: |args| match args {
+            [Value::String(text), Value::String(suffix)] => Ok(Value::Boolean(text.ends_with(suffix.as_str()))),
+            _ => Err(unsupported("ends_with", args)),
+        },
+    },
+    Builtin {
+        name: "to_upper",
+        signatures: &["to_upper(string text) -> string", "to_upper(rune letter) -> rune"],
+        doc: "Upper case of a string, or of a rune whose upper case is a single rune.",
+        function: |args| match args {
+            [Value::String(text)] => Ok(Value::String(text.to_uppercase())),
+            [Value::Rune(letter)] => Ok(Value::Rune(single(letter.to_uppercase()).unwrap_or(*letter))),
+            _ => Err(unsupported("to_upper", args)),
+        },
+    },
+    Builtin {
+        name: "to_lower",
+        signatures: &["to_lower(string text) -> string", "to_lower(rune letter) -> rune"],
+        doc: "Lower case of a string, or of a rune whose lower case is a single rune.",
+        function: |args| match args {
+            [Value::String(text)] => Ok(Value::String(text.to_lowercase())),
+            [Value::Rune(letter)] => Ok(Value::Rune(single(letter.to_lowercase()).unwrap_or(*letter))),
+            _ => Err(unsupported("to_lower", args)),
+        },
+    },
+    Builtin {
+        name: "is_digit",
+        signatures: &["is_digit(rune c) -> boolean"],
+        doc: "Whether `c` is one of the ASCII digits 0 to 9.",
+        function: |args| match args {
+            [Value::Rune(c)] => Ok(Value::Boolean(c.is_ascii_digit())),
+            _ => Err(unsupported("is_digit", args)),
+        },
+    },
+    Builtin {
+        name: "is_letter",
+        signatures: &["is_letter(rune c) -> boolean"],
+        doc: "Whether `c` is alphabetic, in any script.",
+        function: |args| match args {
+            [Value::Rune(c)] => Ok(Value::Boolean(c.is_alphabetic())),
+            _ => Err(unsupported("is_letter", args)),
+        },
+    },
+    Builtin {
+        name: "is_whitespace",
+        signatures: &["is_whitespace(rune c) -> boolean"],
+        doc: "Whether `c` is whitespace, in any script.",
+        function: |args| match args {
+            [Value::Rune(c)] => Ok(Value::Boolean(c.is_whitespace())),
+            _ => Err(unsupported("is_whitespace", args)),
+        },
+    },
+    Builtin {
+        name: "is_upper",
+        signatures: &["is_upper(rune c) -> boolean"],
+        doc: "Whether `c` is an upper case letter.",
+        function: |args| match args {
+            [Value::Rune(c)] => Ok(Value::Boolean(c.is_uppercase())),
+            _ => Err(unsupported("is_upper", args)),
+        },
+    },
+    Builtin {
+        name: "is_lower",
+        signatures: &["is_lower(rune c) -> boolean"],
+        doc: "Whether `c` is a lower case letter.",
+        function: |args| match args {
+            [Value::Rune(c)] => Ok(Value::Boolean(c.is_lowercase())),
+            _ => Err(unsupported("is_lower", args)),
+        },
+    },
+    Builtin {
+        name: "parse_int",
+        signatures: &["parse_int(string text) -> integer", "parse_int(string text, integer base) -> integer"],
+        doc: "The integer written in `text`, in base 10 or `base` from 2 to 36, with an optional sign and nothing around it.",
+        function: |args| match args {
+            [Value::String(text)] => parse_int(text, 10),
+            [Value::String(text), Value::Integer(base)] => parse_int(text, *base),
+            _ => Err(unsupported("parse_int", args)),
+        },
+    },
+    Builtin {
+        name: "format_int",
+        signatures: &["format_int(integer value, integer base) -> string"],
+        doc: "`value` written in `base` from 2 to 36, with lower case letters for digits past 9.",
+        function: |args| match args {
+            [Value::Integer(value), Value::Integer(base)] => format_int(*value, *base),
+            _ => Err(unsupported("format_int", args)),
+        },
+    },
+    Builtin {
+        name: "to_string",
+        signatures: &["to_string(integer value) -> string", "to_string(rune value) -> string", "to_string(boolean value) -> string"],
+        doc: "`value` as it's written in source, without quotes for a rune.",
+        function: |args| match args {
+            [value @ (Value::Integer(_) | Value::Rune(_) | Value::Boolean(_))] => Ok(Value::String(value.to_string())),
+            _ => Err(unsupported("to_string", args)),
+        },
+    },
+    Builtin {
+        name: "min",
+        signatures: &["min(integer a, integer b) -> integer"],
+        doc: "The smaller of `a` and `b`.",
+        function: |args| match args {
+            [Value::Integer(a), Value::Integer(b)] => Ok(Value::Integer(*a.min(b))),
+            _ => Err(unsupported("min", args)),
+        },
+    },
+    Builtin {
+        name: "max",
+        signatures: &["max(integer a, integer b) -> integer"],
+        doc: "The larger of `a` and `b`.",
+        function: |args| match args {
+            [Value::Integer(a), Value::Integer(b)] => Ok(Value::Integer(*a.max(b))),
+            _ => Err(unsupported("max", args)),
+        },
+    },
+    Builtin {
+        name: "abs",
+        signatures: &["abs(integer value) -> integer"],
+        doc: "The absolute value of `value`, which overflows for the smallest integer.",
+        function: |args| match args {
+            [Value::Integer(value)] => value.checked_abs().map(Value::Integer).ok_or_else(|| format!("abs({}) overflows integer", value)),
+            _ => Err(unsupported("abs", args)),
+        },
+    },
+    Builtin {
+        name: "append",
+        signatures: &["append(list<T> items, T item) -> list<T>"],
+        doc: "The list with `item` added at its end.",
+        function: |args| match args {
+            [Value::List(items), item] => {
+                let mut items = items.clone();
+                items.push(item.clone());
+                Ok(Value::List(items))
+            }
+            _ => Err(unsupported("append", args)),
+        },
+    },
+    Builtin {
+        name: "get",
+        signatures: &["get(list<T> items, integer index) -> T", "get(map<K, V> entries, K key) -> V"],
+        doc: "The element at `index`, counting from 0, or the value of `key`, failing when there is none.",
+        function: |args| match args {
+            [Value::List(items), Value::Integer(i)] => Ok(items[index(items, *i)?].clone()),
+            [Value::Map(entries), key] => match entries.iter().find(|(k, _)| k == key) {
+                Some((_, value)) => Ok(value.clone()),
+                None => Err(format!("Map has no key {}", key.quoted())),
+            },
+            _ => Err(unsupported("get", args)),
+        },
+    },
+    Builtin {
+        name: "set",
+        signatures: &["set(list<T> items, integer index, T item) -> list<T>"],
+        doc: "The list with the element at `index` replaced by `item`.",
+        function: |args| match args {
+            [Value::List(items), Value::Integer(i), item] => {
+                let mut items = items.clone();
+                let i = index(&items, *i)?;
+                items[i] = item.clone();
+                Ok(Value::List(items))
+            }
+            _ => Err(unsupported("set", args)),
+        },
+    },
+    Builtin {
+        name: "slice",
+        signatures: &["slice(list<T> items, integer from, integer to) -> list<T>"],
+        doc: "The elements from index `from` up to but excluding `to`.",
+        function: |args| match args {
+            [Value::List(items), Value::Integer(from), Value::Integer(to)] => match (usize::try_from(*from), usize::try_from(*to)) {
+                (Ok(start), Ok(end)) if start <= end && end <= items.len() => Ok(Value::List(items[start..end].to_vec())),
+                _ => Err(format!("Slice {} to {} is out of bounds for a list of length {}", from, to, items.len())),
+            },
+            _ => Err(unsupported("slice", args)),
+        },
+    },
+    Builtin {
+        name: "reverse",
+        signatures: &["reverse(list<T> items) -> list<T>"],
+        doc: "The elements in reverse order.",
+        function: |args| match args {
+            [Value::List(items)] => Ok(Value::List(items.iter().rev().cloned().collect())),
+            _ => Err(unsupported("reverse", args)),
+        },
+    },
+    Builtin {
+        name: "sort",
+        signatures: &["sort(list<T> items) -> list<T>"],
+        doc: "The elements in ascending order, which must be integers, runes or strings.",
+        function: |args| match args {
+            [Value::List(items)] => {
+                if let Some(item) = items.iter().find(|item| item.compare(item).is_none()) {
+                    return Err(format!("Builtin sort can't order values of type {}", item.type_name()));
+                }
+                let mut items = items.clone();
+                items.sort_by(|a, b| a.compare(b).expect("comparable elements"));
+                Ok(Value::List(items))
+            }
+            _ => Err(unsupported("sort", args)),
+        },
+    },
+    Builtin {
+        name: "index_of",
+        signatures: &["index_of(list<T> items, T item) -> integer"],
+        doc: "Index of the first element equal to `item`, -1 if there is none.",
+        function: |args| match args {
+            [Value::List(items), item] => Ok(items.iter().position(|i| i == item).map_or(Value::Integer(-1), integer)),
+            _ => Err(unsupported("index_of", args)),
+        },
+    },
+    Builtin {
+        name: "put",
+        signatures: &["put(map<K, V> entries, K key, V value) -> map<K, V>"],
+        doc: "The map with `key` set to `value`, in place of its previous value or as a new last entry.",
+        function: |args| match args {
+            [Value::Map(entries), key, value] => {
+                let mut entries = entries.clone();
+                value::put(&mut entries, key.clone(), value.clone());
+                Ok(Value::Map(entries))
+            }
+            _ => Err(unsupported("put", args)),
+        },
+    },
+    Builtin {
+        name: "remove",
+        signatures: &["remove(map<K, V> entries, K key) -> map<K, V>"],
+        doc: "The map without the entry for `key`, unchanged if there is none.",
+        function: |args| match args {
+            [Value::Map(entries), key] => Ok(Value::Map(entries.iter().filter(|(k, _)| k != key).cloned().collect())),
+            _ => Err(unsupported("remove", args)),
+        },
+    },
+    Builtin {
+        name: "keys",
+        signatures: &["keys(map<K, V> entries) -> list<K>"],
+        doc: "The keys of the map, in insertion order.",
+        function: |args| match args {
+            [Value::Map(entries)] => Ok(Value::List(entries.iter().map(|(key, _)| key.clone()).collect())),
+            _ => Err(unsupported("keys", args)),
+        },
+    },
+    Builtin {
+        name: "values",
+        signatures: &["values(map<K, V> entries) -> list<V>"],
+        doc: "The values of the map, in the insertion order of their keys.",
+        function: |args| match args {
+            [Value::Map(entries)] => Ok(Value::List(entries.iter().map(|(_, value)| value.clone()).collect())),
+            _ => Err(unsupported("values", args)),
+        },
+    },
+];
+
+fn single<I: Iterator<Item = char>>(mut chars: I) -> Option<char> {
+    let first = chars.next()?;
+    match chars.next() {
+        Some(_) => None,
+        None => Some(first),
+    }
+}
+
+fn check_base(base: i64) -> Result<u32, String> {
+    match base {
+        2..=36 => Ok(base as u32),
+        _ => Err(format!("Base must be between 2 and 36 but got {}", base)),
+    }
+}
+
+fn parse_int(text: &str, base: i64) -> Result<Value, String> {
+    let base = check_base(base)?;
+    i64::from_str_radix(text, base).map(Value::Integer).map_err(|_| format!("Can't read \"{}\" as an integer in base {}", text, base))
+}
+
+fn format_int(value: i64, base: i64) -> Result<Value, String> {
+    let base = check_base(base)?;
+    let mut magnitude = value.unsigned_abs();
+    let mut digits = vec![];
+    loop {
+        digits.push(std::char::from_digit((magnitude % base as u64) as u32, base).expect("digit below the base"));
+        magnitude /= base as u64;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if value < 0 {
+        digits.push('-');
+    }
+    Ok(Value::String(digits.iter().rev().collect()))
+}
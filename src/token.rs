@@ -4,6 +4,7 @@ pub const STRING_LITERAL: &str = "stringLiteral";
 pub const INTEGER_LITERAL: &str = "integerLiteral";
 pub const BOOLEAN_LITERAL: &str = "booleanLiteral";
 pub const IDENTIFIER: &str = "identifier";
+pub const COMMENT: &str = "comment";
 pub const EOF: &str = "eof";
 pub const UNKNOWN: &str = "unknown";
 
@@ -35,6 +36,9 @@ pub const POST_DECREMENT: &str = "--";
  pub const CI_CONFIG: &str = "ciConfig";
  pub const DEPLOYMENT: &str = "deployment";
  pub const MANIFEST: &str = "manifest";
+ pub const SECRET: &str = "secret";
+ pub const LIST: &str = "list";
+ pub const MAP: &str = "map";
  pub const IF: &str = "if";
  pub const ELSE: &str = "else";
  pub const WHILE: &str = "while";
@@ -44,17 +48,22 @@ pub const POST_DECREMENT: &str = "--";
  pub const FUNC: &str = "func";
  pub const COMPLEX: &str = "complex";
  pub const RETURN: &str = "return";
+ pub const IMPORT: &str = "import";
+ pub const PUB: &str = "pub";
 
  pub const L_PARENT: &str = "(";
  pub const R_PARENT: &str = ")";
  pub const L_BRACE: &str = "{";
  pub const R_BRACE: &str = "}";
+ pub const L_BRACKET: &str = "[";
+ pub const R_BRACKET: &str = "]";
  pub const SEMI: &str = ";";
  pub const COMMA: &str = ",";
+ pub const COLON: &str = ":";
  pub const ASSIGN: &str = "=";
  pub const DOT: &str = ".";
 
- #[derive(Debug)]
+ #[derive(Debug, Clone, PartialEq)]
  pub struct Token {
      pub token_type: String,
      pub value: String,
@@ -0,0 +1,253 @@
+//! Values computed while running a program, and what operators do with them.
+//!
+//! Both backends and constant folding apply operators through `Value::binary` and `Value::unary`, and the checker's
+//! `binary_type` and `unary_type` accept exactly the operand types these succeed on. Any operator applied to operands
+//! not listed below fails with "Operator <op> can't be applied to <type> and <type>".
+//!
+//! Arithmetic, `+`, `-`, `*`, `/`, prefix `-` and postfix `++` and `--`, applies to integers only:
+//! - integers are signed 64-bit, overflow is checked and never wraps: `9223372036854775807 + 1`,
+//!   `-(-9223372036854775808)` and `-9223372036854775808 / -1` fail with "... overflows integer",
+//! - division truncates toward zero, `-7 / 2` being `-3`, and dividing by zero fails with "Division by zero".
+//!
+//! Concatenation, `+` with a string on either side:
+//! - `string + string`, `string + rune` and `rune + string` give a string, `rune + rune` is an error since runes
+//!   aren't numbers nor strings,
+//! - a secret added to a string, a rune or another secret gives a secret, which remembers the secret values it holds.
+//!
+//! Logic and bits:
+//! - `&&` and `||` apply to booleans, the backends skip the right operand once the left one decides,
+//! - `&` and `|` are bitwise on integers, in two's complement, and logical on booleans, always evaluating both sides,
+//! - `!` applies to booleans.
+//!
+//! Ordering, `<`, `>`, `<=` and `>=`, compares two integers by value, two runes by code point, or two strings
+//! lexicographically by code point, so `"Z" < "a"` and `"ab" < "b"`. Booleans, lists, maps and complex values have no
+//! order.
+//!
+//! Equality, `==` and `!=`, compares two values of the same type other than void:
+//! - values of different types are an error rather than unequal: `1 == '1'`, `'a' == "a"`, and complex values of
+//!   different types,
+//! - complex values are equal when their fields are, lists when they have equal elements in the same order, and maps
+//!   when they hold the same keys with equal values, whatever the order they were inserted in.
+//!
+//! Conversions:
+//! - the only implicit ones are a rune becoming a one-character string when added to a string, and a string or rune
+//!   becoming a secret when added to a secret. Integers and runes never convert to each other,
+//! - the builtins `to_string` turns an integer, rune or boolean into its text, `parse_int` and `format_int` convert
+//!   between integers and their text in a base.
+//!
+//! Display, when `run` prints a value: integers in decimal, strings and runes as their text, booleans as `true` or
+//! `false`, void as `void`, secrets masked as `****`, complex values as `name { field: value }` or `name {}` without
+//! fields, lists as `[a, b]` and maps as `[key: value]` in insertion order, `[:]` when empty. Strings and runes nested
+//! in another value are quoted as in source.
+
+use core::fmt::Display;
+use std::cmp::Ordering;
+
+use crate::ast::{BinaryOp, UnaryOp};
+use crate::secret::{self, Secret};
+
+/// A value computed while running a program.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Integer(i64),
+    String(String),
+    Rune(char),
+    Boolean(bool),
+    /// Result of calling a `void` function
+    Void,
+    /// Value of a `complex` type or a domain declaration, fields in declaration order
+    Complex { name: String, fields: Vec<(String, Value)> },
+    Secret(Secret),
+    List(Vec<Value>),
+    /// Entries in insertion order, each key once
+    Map(Vec<(Value, Value)>),
+}
+
+impl Value {
+    /// Name of the type of the value, as written in source.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::String(_) => "string",
+            Value::Rune(_) => "rune",
+            Value::Boolean(_) => "boolean",
+            Value::Void => "void",
+            Value::Complex { name, .. } => name,
+            Value::Secret(_) => "secret",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+
+    /// The value as written in source when it's nested in another.
+    pub fn quoted(&self) -> String {
+        match self {
+            Value::String(text) => format!("\"{}\"", text),
+            Value::Rune(rune) => format!("'{}'", rune),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Order of integers, runes and strings, `None` for other values or values of different types.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::Rune(a), Value::Rune(b)) => Some(a.cmp(b)),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    /// Bytes of memory the value holds outside of itself, counting nested values in full.
+    pub fn heap_size(&self) -> usize {
+        let size = std::mem::size_of::<Value>();
+        match self {
+            Value::Integer(_) | Value::Rune(_) | Value::Boolean(_) | Value::Void => 0,
+            Value::String(text) => text.len(),
+            Value::Secret(secret) => secret.text.len() + secret.values.iter().map(|value| std::mem::size_of::<String>() + value.len()).sum::<usize>(),
+            Value::Complex { name, fields } => name.len() + fields.iter().map(|(field, value)| std::mem::size_of::<String>() + field.len() + size + value.heap_size()).sum::<usize>(),
+            Value::List(elements) => elements.iter().map(|element| size + element.heap_size()).sum(),
+            Value::Map(entries) => entries.iter().map(|(key, value)| 2 * size + key.heap_size() + value.heap_size()).sum(),
+        }
+    }
+
+    /// Text of a string, rune or secret with the secret values it holds, for concatenation.
+    fn text(&self) -> Option<(std::borrow::Cow<'_, str>, &[String])> {
+        match self {
+            Value::String(text) => Some((text.as_str().into(), &[])),
+            Value::Rune(rune) => Some((rune.to_string().into(), &[])),
+            Value::Secret(secret) => Some((secret.text.as_str().into(), &secret.values)),
+            _ => None,
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Complex { fields, .. } => fields.iter().find(|(field, _)| field == name).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut Value> {
+        match self {
+            Value::Complex { fields, .. } => fields.iter_mut().find(|(field, _)| field == name).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Result of `self op other` as described in the module documentation, or a message explaining why it can't be
+    /// computed. `&&` and `||` are evaluated here on both operands, short-circuiting is up to the caller.
+    pub fn binary(&self, op: BinaryOp, other: &Value) -> Result<Value, String> {
+        use Value::*;
+
+        let value = match (op, self, other) {
+            (BinaryOp::Plus, Integer(a), Integer(b)) => Integer(checked(a.checked_add(*b), op, *a, *b)?),
+            (BinaryOp::Minus, Integer(a), Integer(b)) => Integer(checked(a.checked_sub(*b), op, *a, *b)?),
+            (BinaryOp::Times, Integer(a), Integer(b)) => Integer(checked(a.checked_mul(*b), op, *a, *b)?),
+            (BinaryOp::Div, Integer(_), Integer(0)) => return Err("Division by zero".to_string()),
+            (BinaryOp::Div, Integer(a), Integer(b)) => Integer(checked(a.checked_div(*b), op, *a, *b)?),
+            (BinaryOp::Plus, String(a), String(b)) => String(format!("{}{}", a, b)),
+            (BinaryOp::Plus, String(a), Rune(b)) => String(format!("{}{}", a, b)),
+            (BinaryOp::Plus, Rune(a), String(b)) => String(format!("{}{}", a, b)),
+            (BinaryOp::Plus, Secret(_), _) | (BinaryOp::Plus, _, Secret(_)) => match (self.text(), other.text()) {
+                (Some((left, left_values)), Some((right, right_values))) => Secret(secret::Secret::concat((&left, left_values), (&right, right_values))),
+                _ => return Err(mismatch(op, self, other)),
+            },
+            (BinaryOp::BitAnd, Integer(a), Integer(b)) => Integer(a & b),
+            (BinaryOp::BitOr, Integer(a), Integer(b)) => Integer(a | b),
+            (BinaryOp::BitAnd, Boolean(a), Boolean(b)) | (BinaryOp::And, Boolean(a), Boolean(b)) => Boolean(*a && *b),
+            (BinaryOp::BitOr, Boolean(a), Boolean(b)) | (BinaryOp::Or, Boolean(a), Boolean(b)) => Boolean(*a || *b),
+            (BinaryOp::Eq, _, _) | (BinaryOp::Neq, _, _) if self.type_name() == other.type_name() && *self != Void => {
+                Boolean((self == other) == (op == BinaryOp::Eq))
+            }
+            (BinaryOp::Lt, _, _) | (BinaryOp::Rt, _, _) | (BinaryOp::LtEq, _, _) | (BinaryOp::RtEq, _, _) => {
+                let ordering = self.compare(other).ok_or_else(|| mismatch(op, self, other))?;
+                Boolean(match op {
+                    BinaryOp::Lt => ordering.is_lt(),
+                    BinaryOp::Rt => ordering.is_gt(),
+                    BinaryOp::LtEq => ordering.is_le(),
+                    _ => ordering.is_ge(),
+                })
+            }
+            _ => return Err(mismatch(op, self, other)),
+        };
+
+        Ok(value)
+    }
+
+    /// Result of `op self`, or a message explaining why it can't be computed.
+    pub fn unary(&self, op: UnaryOp) -> Result<Value, String> {
+        match (op, self) {
+            (UnaryOp::Not, Value::Boolean(value)) => Ok(Value::Boolean(!value)),
+            (UnaryOp::Negate, Value::Integer(value)) => value.checked_neg().map(Value::Integer).ok_or_else(|| format!("-({}) overflows integer", value)),
+            _ => Err(format!("Operator {} can't be applied to {}", op.as_str(), self.type_name())),
+        }
+    }
+}
+
+/// Maps are equal when they hold the same entries, whatever their order. Other values are equal when they're of the same
+/// kind with equal contents.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        use Value::*;
+
+        match (self, other) {
+            (Integer(a), Integer(b)) => a == b,
+            (String(a), String(b)) => a == b,
+            (Rune(a), Rune(b)) => a == b,
+            (Boolean(a), Boolean(b)) => a == b,
+            (Void, Void) => true,
+            (Complex { name: a, fields: a_fields }, Complex { name: b, fields: b_fields }) => a == b && a_fields == b_fields,
+            (Secret(a), Secret(b)) => a == b,
+            (List(a), List(b)) => a == b,
+            // Keys are unique, so entries found in the other map and as many of them make the same map
+            (Map(a), Map(b)) => a.len() == b.len() && a.iter().all(|(key, value)| b.iter().any(|(k, v)| k == key && v == value)),
+            _ => false,
+        }
+    }
+}
+
+/// Sets `key` to `value` in the entries of a map, in place of its previous value or as a new last entry.
+pub fn put(entries: &mut Vec<(Value, Value)>, key: Value, value: Value) {
+    match entries.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, v)) => *v = value,
+        None => entries.push((key, value)),
+    }
+}
+
+fn checked(result: Option<i64>, op: BinaryOp, left: i64, right: i64) -> Result<i64, String> {
+    result.ok_or_else(|| format!("{} {} {} overflows integer", left, op.as_str(), right))
+}
+
+fn mismatch(op: BinaryOp, left: &Value, right: &Value) -> String {
+    format!("Operator {} can't be applied to {} and {}", op.as_str(), left.type_name(), right.type_name())
+}
+
+/// Strings and runes display as their text, inside complex values, lists and maps they're quoted as in source.
+/// Secrets are masked.
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Rune(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Void => write!(f, "void"),
+            Value::Secret(_) => write!(f, "{}", secret::MASK),
+            Value::Complex { name, fields } if fields.is_empty() => write!(f, "{} {{}}", name),
+            Value::Complex { name, fields } => {
+                let fields: Vec<String> = fields.iter().map(|(field, value)| format!("{}: {}", field, value.quoted())).collect();
+                write!(f, "{} {{ {} }}", name, fields.join(", "))
+            }
+            Value::List(values) => {
+                let values: Vec<String> = values.iter().map(Value::quoted).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            Value::Map(entries) if entries.is_empty() => write!(f, "[:]"),
+            Value::Map(entries) => {
+                let entries: Vec<String> = entries.iter().map(|(key, value)| format!("{}: {}", key.quoted(), value.quoted())).collect();
+                write!(f, "[{}]", entries.join(", "))
+            }
+        }
+    }
+}
@@ -0,0 +1,433 @@
+//! Traversal of the AST.
+//!
+//! `Visitor` walks a tree by shared reference and `VisitorMut` by mutable reference to rewrite it in place.
+//! Every `visit_*` method defaults to the matching `walk_*` function, which visits the children of the node,
+//! so a pass only overrides the nodes it cares about and calls `walk_*` itself to keep descending.
+
+use crate::ast::{
+    Block, CommandDecl, ComplexDecl, DomainDecl, DomainField, Expr, ExprKind, FieldDecl, FieldInit, FuncDecl, ImportDecl, Item, Param, Program, Stmt,
+    StmtKind,
+};
+
+pub trait Visitor<'ast>: Sized {
+    fn visit_program(&mut self, program: &'ast Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) {
+        walk_item(self, item)
+    }
+
+    fn visit_import(&mut self, _import: &'ast ImportDecl) {}
+
+    fn visit_func(&mut self, func: &'ast FuncDecl) {
+        walk_func(self, func)
+    }
+
+    fn visit_param(&mut self, _param: &'ast Param) {}
+
+    fn visit_complex(&mut self, complex: &'ast ComplexDecl) {
+        walk_complex(self, complex)
+    }
+
+    fn visit_field_decl(&mut self, _field: &'ast FieldDecl) {}
+
+    fn visit_domain(&mut self, domain: &'ast DomainDecl) {
+        walk_domain(self, domain)
+    }
+
+    fn visit_domain_field(&mut self, field: &'ast DomainField) {
+        walk_domain_field(self, field)
+    }
+
+    fn visit_command(&mut self, command: &'ast CommandDecl) {
+        walk_command(self, command)
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_field_init(&mut self, field: &'ast FieldInit) {
+        walk_field_init(self, field)
+    }
+}
+
+pub fn walk_program<'ast, V: Visitor<'ast>>(visitor: &mut V, program: &'ast Program) {
+    for item in &program.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Item) {
+    match item {
+        Item::Import(import) => visitor.visit_import(import),
+        Item::Func(func) => visitor.visit_func(func),
+        Item::Complex(complex) => visitor.visit_complex(complex),
+        Item::Domain(domain) => visitor.visit_domain(domain),
+        Item::Command(command) => visitor.visit_command(command),
+        Item::Stmt(stmt) => visitor.visit_stmt(stmt),
+    }
+}
+
+pub fn walk_func<'ast, V: Visitor<'ast>>(visitor: &mut V, func: &'ast FuncDecl) {
+    for param in &func.params {
+        visitor.visit_param(param);
+    }
+    visitor.visit_block(&func.body);
+}
+
+pub fn walk_complex<'ast, V: Visitor<'ast>>(visitor: &mut V, complex: &'ast ComplexDecl) {
+    for field in &complex.fields {
+        visitor.visit_field_decl(field);
+    }
+}
+
+pub fn walk_domain<'ast, V: Visitor<'ast>>(visitor: &mut V, domain: &'ast DomainDecl) {
+    for field in &domain.fields {
+        visitor.visit_domain_field(field);
+    }
+}
+
+pub fn walk_domain_field<'ast, V: Visitor<'ast>>(visitor: &mut V, field: &'ast DomainField) {
+    visitor.visit_expr(&field.value);
+}
+
+pub fn walk_command<'ast, V: Visitor<'ast>>(visitor: &mut V, command: &'ast CommandDecl) {
+    for param in &command.params {
+        visitor.visit_param(param);
+    }
+    for field in &command.fields {
+        visitor.visit_domain_field(field);
+    }
+}
+
+pub fn walk_block<'ast, V: Visitor<'ast>>(visitor: &mut V, block: &'ast Block) {
+    for stmt in &block.stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<'ast, V: Visitor<'ast>>(visitor: &mut V, stmt: &'ast Stmt) {
+    match &stmt.kind {
+        StmtKind::VarDecl { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        StmtKind::If { condition, then_branch, else_branch } => {
+            visitor.visit_expr(condition);
+            visitor.visit_block(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_stmt(else_branch);
+            }
+        }
+        StmtKind::While { condition, body } => {
+            visitor.visit_expr(condition);
+            visitor.visit_block(body);
+        }
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        StmtKind::Block(block) => visitor.visit_block(block),
+        StmtKind::Expr(expr) => visitor.visit_expr(expr),
+    }
+}
+
+pub fn walk_expr<'ast, V: Visitor<'ast>>(visitor: &mut V, expr: &'ast Expr) {
+    match &expr.kind {
+        ExprKind::Integer(_) | ExprKind::String(_) | ExprKind::Rune(_) | ExprKind::Boolean(_) | ExprKind::Identifier(_) => {}
+        ExprKind::Unary { operand, .. } | ExprKind::Postfix { operand, .. } => visitor.visit_expr(operand),
+        ExprKind::Binary { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        ExprKind::Assign { target, value } => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(value);
+        }
+        ExprKind::Call { callee, args } => {
+            visitor.visit_expr(callee);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::Field { object, .. } => visitor.visit_expr(object),
+        ExprKind::ComplexLiteral { fields, .. } => {
+            for field in fields {
+                visitor.visit_field_init(field);
+            }
+        }
+        ExprKind::List(values) => {
+            for value in values {
+                visitor.visit_expr(value);
+            }
+        }
+        ExprKind::Map(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+    }
+}
+
+pub fn walk_field_init<'ast, V: Visitor<'ast>>(visitor: &mut V, field: &'ast FieldInit) {
+    visitor.visit_expr(&field.value);
+}
+
+pub trait VisitorMut: Sized {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        walk_item_mut(self, item)
+    }
+
+    fn visit_import_mut(&mut self, _import: &mut ImportDecl) {}
+
+    fn visit_func_mut(&mut self, func: &mut FuncDecl) {
+        walk_func_mut(self, func)
+    }
+
+    fn visit_param_mut(&mut self, _param: &mut Param) {}
+
+    fn visit_complex_mut(&mut self, complex: &mut ComplexDecl) {
+        walk_complex_mut(self, complex)
+    }
+
+    fn visit_field_decl_mut(&mut self, _field: &mut FieldDecl) {}
+
+    fn visit_domain_mut(&mut self, domain: &mut DomainDecl) {
+        walk_domain_mut(self, domain)
+    }
+
+    fn visit_domain_field_mut(&mut self, field: &mut DomainField) {
+        walk_domain_field_mut(self, field)
+    }
+
+    fn visit_command_mut(&mut self, command: &mut CommandDecl) {
+        walk_command_mut(self, command)
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_field_init_mut(&mut self, field: &mut FieldInit) {
+        walk_field_init_mut(self, field)
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut>(visitor: &mut V, program: &mut Program) {
+    for item in &mut program.items {
+        visitor.visit_item_mut(item);
+    }
+}
+
+pub fn walk_item_mut<V: VisitorMut>(visitor: &mut V, item: &mut Item) {
+    match item {
+        Item::Import(import) => visitor.visit_import_mut(import),
+        Item::Func(func) => visitor.visit_func_mut(func),
+        Item::Complex(complex) => visitor.visit_complex_mut(complex),
+        Item::Domain(domain) => visitor.visit_domain_mut(domain),
+        Item::Command(command) => visitor.visit_command_mut(command),
+        Item::Stmt(stmt) => visitor.visit_stmt_mut(stmt),
+    }
+}
+
+pub fn walk_func_mut<V: VisitorMut>(visitor: &mut V, func: &mut FuncDecl) {
+    for param in &mut func.params {
+        visitor.visit_param_mut(param);
+    }
+    visitor.visit_block_mut(&mut func.body);
+}
+
+pub fn walk_complex_mut<V: VisitorMut>(visitor: &mut V, complex: &mut ComplexDecl) {
+    for field in &mut complex.fields {
+        visitor.visit_field_decl_mut(field);
+    }
+}
+
+pub fn walk_domain_mut<V: VisitorMut>(visitor: &mut V, domain: &mut DomainDecl) {
+    for field in &mut domain.fields {
+        visitor.visit_domain_field_mut(field);
+    }
+}
+
+pub fn walk_domain_field_mut<V: VisitorMut>(visitor: &mut V, field: &mut DomainField) {
+    visitor.visit_expr_mut(&mut field.value);
+}
+
+pub fn walk_command_mut<V: VisitorMut>(visitor: &mut V, command: &mut CommandDecl) {
+    for param in &mut command.params {
+        visitor.visit_param_mut(param);
+    }
+    for field in &mut command.fields {
+        visitor.visit_domain_field_mut(field);
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut>(visitor: &mut V, block: &mut Block) {
+    for stmt in &mut block.stmts {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut>(visitor: &mut V, stmt: &mut Stmt) {
+    match &mut stmt.kind {
+        StmtKind::VarDecl { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        StmtKind::If { condition, then_branch, else_branch } => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_block_mut(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_stmt_mut(else_branch);
+            }
+        }
+        StmtKind::While { condition, body } => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_block_mut(body);
+        }
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        StmtKind::Block(block) => visitor.visit_block_mut(block),
+        StmtKind::Expr(expr) => visitor.visit_expr_mut(expr),
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut>(visitor: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Integer(_) | ExprKind::String(_) | ExprKind::Rune(_) | ExprKind::Boolean(_) | ExprKind::Identifier(_) => {}
+        ExprKind::Unary { operand, .. } | ExprKind::Postfix { operand, .. } => visitor.visit_expr_mut(operand),
+        ExprKind::Binary { left, right, .. } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        ExprKind::Assign { target, value } => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_expr_mut(value);
+        }
+        ExprKind::Call { callee, args } => {
+            visitor.visit_expr_mut(callee);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        ExprKind::Field { object, .. } => visitor.visit_expr_mut(object),
+        ExprKind::ComplexLiteral { fields, .. } => {
+            for field in fields {
+                visitor.visit_field_init_mut(field);
+            }
+        }
+        ExprKind::List(values) => {
+            for value in values {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        ExprKind::Map(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expr_mut(key);
+                visitor.visit_expr_mut(value);
+            }
+        }
+    }
+}
+
+pub fn walk_field_init_mut<V: VisitorMut>(visitor: &mut V, field: &mut FieldInit) {
+    visitor.visit_expr_mut(&mut field.value);
+}
+
+/// Counts the calls to the function `name`.
+pub struct CallCounter<'a> {
+    pub name: &'a str,
+    pub count: usize,
+}
+
+impl<'ast> Visitor<'ast> for CallCounter<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let ExprKind::Call { callee, .. } = &expr.kind {
+            if matches!(&callee.kind, ExprKind::Identifier(name) if name == self.name) {
+                self.count += 1;
+            }
+        }
+
+        walk_expr(self, expr)
+    }
+}
+
+/// Renames every declaration and use of the identifier `from` to `to`, regardless of scopes.
+pub struct Rename<'a> {
+    pub from: &'a str,
+    pub to: &'a str,
+}
+
+impl Rename<'_> {
+    fn rename(&self, name: &mut String) {
+        if name == self.from {
+            *name = self.to.to_string();
+        }
+    }
+}
+
+impl VisitorMut for Rename<'_> {
+    fn visit_func_mut(&mut self, func: &mut FuncDecl) {
+        self.rename(&mut func.name);
+        walk_func_mut(self, func)
+    }
+
+    fn visit_param_mut(&mut self, param: &mut Param) {
+        self.rename(&mut param.name);
+    }
+
+    fn visit_domain_mut(&mut self, domain: &mut DomainDecl) {
+        self.rename(&mut domain.name);
+        walk_domain_mut(self, domain)
+    }
+
+    fn visit_command_mut(&mut self, command: &mut CommandDecl) {
+        self.rename(&mut command.name);
+        walk_command_mut(self, command)
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        if let StmtKind::VarDecl { name, .. } = &mut stmt.kind {
+            self.rename(name);
+        }
+
+        walk_stmt_mut(self, stmt)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let ExprKind::Identifier(name) = &mut expr.kind {
+            self.rename(name);
+        }
+
+        walk_expr_mut(self, expr)
+    }
+}
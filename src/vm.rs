@@ -0,0 +1,338 @@
+use crate::ast::Span;
+use crate::bytecode::{Bytecode, Op};
+use crate::command::{self, Invocation, Runner};
+use crate::interpreter::{self, RuntimeError};
+use crate::limits::{Budget, Limits};
+use crate::secret;
+use crate::stdlib;
+use crate::value::{self, Value};
+
+/// Runs compiled bytecode: the top-level statements, then `main` if there is one.
+/// Returns the same value, or fails with the same error, as `interpreter::run` on the source.
+pub fn run(bytecode: &Bytecode) -> Result<Value, RuntimeError> {
+    run_with(bytecode, &mut command::Local::default())
+}
+
+/// Like `run`, with commands carried out by `runner`.
+pub fn run_with(bytecode: &Bytecode, runner: &mut dyn Runner) -> Result<Value, RuntimeError> {
+    run_limited(bytecode, runner, Limits::default())
+}
+
+/// Like `run_with`, failing once the program goes over one of `limits`, with the same error as
+/// `interpreter::run_limited`.
+pub fn run_limited(bytecode: &Bytecode, runner: &mut dyn Runner, limits: Limits) -> Result<Value, RuntimeError> {
+    let mut vm = Vm::new(bytecode, runner, limits);
+    vm.call(bytecode.script, vec![], None)?;
+    match bytecode.main {
+        Some(main) => vm.call(main, vec![], None),
+        None => Ok(Value::Void),
+    }
+}
+
+/// State of a global variable.
+#[derive(Debug, Clone)]
+enum Global {
+    Undeclared,
+    Declared(Option<Value>),
+}
+
+#[derive(Debug, Clone)]
+enum DomainState {
+    Pending,
+    Evaluating,
+    Done(Value),
+}
+
+struct Frame {
+    function: usize,
+    ip: usize,
+    /// `None` until the local is given a value
+    locals: Vec<Option<Value>>,
+    /// Height of the stack when the frame started
+    base: usize,
+    /// Span of the call expression, `None` for the script, `main` and domains
+    call: Option<Span>,
+    /// Domain evaluated by the frame, cached when it returns
+    domain: Option<usize>,
+    /// The frame evaluates the settings of a command, run when it returns
+    exec: bool,
+}
+
+struct Vm<'b, 'r> {
+    bytecode: &'b Bytecode,
+    runner: &'r mut dyn Runner,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    globals: Vec<Global>,
+    domains: Vec<DomainState>,
+    budget: Budget,
+}
+
+impl<'b, 'r> Vm<'b, 'r> {
+    fn new(bytecode: &'b Bytecode, runner: &'r mut dyn Runner, limits: Limits) -> Self {
+        Vm {
+            bytecode,
+            runner,
+            stack: vec![],
+            frames: vec![],
+            globals: vec![Global::Undeclared; bytecode.globals.len()],
+            domains: vec![DomainState::Pending; bytecode.domains.len()],
+            budget: Budget::new(limits),
+        }
+    }
+
+    /// Calls function `index` and runs until it returns.
+    fn call(&mut self, index: usize, args: Vec<Value>, call: Option<Span>) -> Result<Value, RuntimeError> {
+        let depth = self.frames.len();
+        self.push_frame(index, args, call, None);
+        let result = self.execute(depth);
+        if result.is_err() {
+            self.frames.truncate(depth);
+        }
+        result
+    }
+
+    fn push_frame(&mut self, function: usize, args: Vec<Value>, call: Option<Span>, domain: Option<usize>) {
+        let mut locals: Vec<Option<Value>> = args.into_iter().map(Some).collect();
+        locals.resize(self.bytecode.functions[function].locals.len(), None);
+        self.frames.push(Frame { function, ip: 0, locals, base: self.stack.len(), call, domain, exec: false });
+    }
+
+    fn error(&self, message: &str, span: Span) -> RuntimeError {
+        let calls = self.frames.iter().rev().filter_map(|frame| frame.call).collect();
+        RuntimeError { message: message.to_string(), span, calls }
+    }
+
+    /// Charges the heap budget for `value`, just built by the instruction at `span`, and pushes it.
+    fn push_allocated(&mut self, value: Value, span: Span) -> Result<(), RuntimeError> {
+        self.budget.allocate(&value).map_err(|message| self.error(&message, span))?;
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("operand stack underflow")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("operand stack underflow")
+    }
+
+    /// Runs instructions until the frame stack is back to `depth` frames, returning the value of the last return.
+    fn execute(&mut self, depth: usize) -> Result<Value, RuntimeError> {
+        loop {
+            let bytecode = self.bytecode;
+            let frame = self.frames.last().expect("frame");
+            let function = &bytecode.functions[frame.function];
+            let chunk = &function.chunk;
+            let offset = frame.ip;
+            let op = Op::from_byte(chunk.code[offset]).expect("valid opcode");
+            let operand = |i: usize| {
+                let position = offset + 1 + op.operands()[..i].iter().sum::<usize>();
+                chunk.operand(position, op.operands()[i])
+            };
+            let span = chunk.span_at(offset);
+            let mut next = offset + op.size();
+
+            match op {
+                Op::Constant => self.stack.push(chunk.constants[operand(0)].clone()),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::Dup => self.stack.push(self.peek().clone()),
+                Op::GetLocal => {
+                    let slot = operand(0);
+                    match &self.frames.last().expect("frame").locals[slot] {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(self.error(&format!("{} is read before it's given a value", function.locals[slot]), span)),
+                    }
+                }
+                Op::SetLocal => {
+                    let value = self.peek().clone();
+                    self.frames.last_mut().expect("frame").locals[operand(0)] = Some(value);
+                }
+                Op::UnsetLocal => self.frames.last_mut().expect("frame").locals[operand(0)] = None,
+                Op::SetLocalField => {
+                    let (slot, path) = (operand(0), operand(1));
+                    let value = self.peek().clone();
+                    let path = self.path(path);
+                    let stored = interpreter::store(&mut self.frames.last_mut().expect("frame").locals[slot], &function.locals[slot], &path, span, value);
+                    stored.map_err(|(message, span)| self.error(&message, span))?;
+                }
+                Op::GetGlobal => {
+                    let slot = operand(0);
+                    let name = &bytecode.globals[slot];
+                    match &self.globals[slot] {
+                        Global::Declared(Some(value)) => self.stack.push(value.clone()),
+                        Global::Declared(None) => return Err(self.error(&format!("{} is read before it's given a value", name), span)),
+                        Global::Undeclared => return Err(self.error(&format!("{} is read before it's declared", name), span)),
+                    }
+                }
+                Op::SetGlobal | Op::SetGlobalField => {
+                    let slot = operand(0);
+                    let name = &bytecode.globals[slot];
+                    let value = self.peek().clone();
+                    let path = if op == Op::SetGlobalField { self.path(operand(1)) } else { vec![] };
+                    let stored = match &mut self.globals[slot] {
+                        Global::Declared(slot) => interpreter::store(slot, name, &path, span, value),
+                        Global::Undeclared => Err((format!("{} is assigned before it's declared", name), span)),
+                    };
+                    stored.map_err(|(message, span)| self.error(&message, span))?;
+                }
+                Op::DefineGlobal => {
+                    let value = self.pop();
+                    self.globals[operand(0)] = Global::Declared(Some(value));
+                }
+                Op::DeclareGlobal => self.globals[operand(0)] = Global::Declared(None),
+                Op::GetDomain => {
+                    let index = operand(0);
+                    match &self.domains[index] {
+                        DomainState::Done(value) => self.stack.push(value.clone()),
+                        DomainState::Evaluating => return Err(self.error(&format!("{} depends on itself", self.bytecode.domains[index].name), span)),
+                        DomainState::Pending => {
+                            self.domains[index] = DomainState::Evaluating;
+                            self.frames.last_mut().expect("frame").ip = next;
+                            self.push_frame(self.bytecode.domains[index].function, vec![], None, Some(index));
+                            continue;
+                        }
+                    }
+                }
+                Op::GetField => {
+                    let object = self.pop();
+                    let field = match &chunk.constants[operand(0)] {
+                        Value::String(field) => field,
+                        other => unreachable!("field name {:?}", other),
+                    };
+                    match object.field(field) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(self.error(&format!("{} has no field {}", object.type_name(), field), span)),
+                    }
+                }
+                Op::Complex => {
+                    let layout = &bytecode.layouts[operand(0)];
+                    let values = self.stack.split_off(self.stack.len() - layout.order.len());
+                    let mut fields: Vec<(usize, (String, Value))> =
+                        layout.order.iter().zip(values).map(|(&position, value)| (position, (layout.fields[position].clone(), value))).collect();
+                    fields.sort_by_key(|&(position, _)| position);
+                    self.push_allocated(Value::Complex { name: layout.name.clone(), fields: fields.into_iter().map(|(_, field)| field).collect() }, span)?;
+                }
+                Op::Negate | Op::Not => {
+                    let operand = self.pop();
+                    let op = if op == Op::Negate { crate::ast::UnaryOp::Negate } else { crate::ast::UnaryOp::Not };
+                    let value = operand.unary(op).map_err(|message| self.error(&message, span))?;
+                    self.stack.push(value);
+                }
+                Op::Increment | Op::Decrement => {
+                    let operand = self.pop();
+                    let (op, name) = if op == Op::Increment { (crate::ast::BinaryOp::Plus, "++") } else { (crate::ast::BinaryOp::Minus, "--") };
+                    let value = match operand {
+                        Value::Integer(_) => operand.binary(op, &Value::Integer(1)).map_err(|message| self.error(&message, span))?,
+                        _ => return Err(self.error(&format!("Operator {} can't be applied to {}", name, operand.type_name()), span)),
+                    };
+                    self.stack.push(value);
+                }
+                Op::Jump => {
+                    next = operand(0);
+                    // Jumping back is the end of a loop iteration
+                    if next < offset {
+                        self.budget.step().map_err(|message| self.error(&message, span))?;
+                    }
+                }
+                Op::JumpIfFalse => match self.pop() {
+                    Value::Boolean(true) => {}
+                    Value::Boolean(false) => next = operand(0),
+                    other => return Err(self.error(&format!("Condition expects boolean but got {}", other.type_name()), span)),
+                },
+                Op::JumpIfFalseKeep => {
+                    if *self.peek() == Value::Boolean(false) {
+                        next = operand(0);
+                    }
+                }
+                Op::JumpIfTrueKeep => {
+                    if *self.peek() == Value::Boolean(true) {
+                        next = operand(0);
+                    }
+                }
+                Op::Call | Op::Exec => {
+                    let (index, count) = (operand(0), operand(1));
+                    let callee = &bytecode.functions[index];
+                    if callee.arity != count {
+                        let kind = if op == Op::Exec { "Command" } else { "Function" };
+                        return Err(self.error(&format!("{} {} expects {} arguments but got {}", kind, callee.name, callee.arity, count), span));
+                    }
+                    self.budget.enter().map_err(|message| self.error(&message, span))?;
+                    let args = self.stack.split_off(self.stack.len() - count);
+                    self.frames.last_mut().expect("frame").ip = next;
+                    self.push_frame(index, args, Some(span), None);
+                    self.frames.last_mut().expect("frame").exec = op == Op::Exec;
+                    continue;
+                }
+                Op::Return => {
+                    let mut value = self.pop();
+                    let frame = self.frames.pop().expect("frame");
+                    self.stack.truncate(frame.base);
+                    if frame.call.is_some() {
+                        self.budget.leave();
+                    }
+                    if let Some(domain) = frame.domain {
+                        self.domains[domain] = DomainState::Done(value.clone());
+                    }
+                    if frame.exec {
+                        let call = frame.call.expect("command call");
+                        let name = &bytecode.functions[frame.function].name;
+                        let invocation = Invocation::from_settings(name, &value).map_err(|message| self.error(&message, call))?;
+                        let outcome = self.runner.run(&invocation).map_err(|message| self.error(&message, call))?;
+                        value = outcome.to_value();
+                        self.budget.allocate(&value).map_err(|message| self.error(&message, call))?;
+                    }
+                    if self.frames.len() == depth {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                    continue;
+                }
+                Op::LoadSecret => {
+                    let settings = self.pop();
+                    let name = chunk.constants[operand(0)].to_string();
+                    let value = secret::load(&name, &settings, self.runner).map_err(|message| self.error(&message, span))?;
+                    self.stack.push(value);
+                }
+                Op::List => {
+                    let elements = self.stack.split_off(self.stack.len() - operand(0));
+                    self.push_allocated(Value::List(elements), span)?;
+                }
+                Op::Map => {
+                    let values = self.stack.split_off(self.stack.len() - 2 * operand(0));
+                    let mut entries = Vec::with_capacity(operand(0));
+                    let mut values = values.into_iter();
+                    while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                        value::put(&mut entries, key, value);
+                    }
+                    self.push_allocated(Value::Map(entries), span)?;
+                }
+                Op::Builtin => {
+                    let args = self.stack.split_off(self.stack.len() - operand(1));
+                    let value = stdlib::BUILTINS[operand(0)].call(&args).map_err(|message| self.error(&message, span))?;
+                    self.push_allocated(value, span)?;
+                }
+                Op::Fail => {
+                    let message = chunk.constants[operand(0)].to_string();
+                    return Err(self.error(&message, span));
+                }
+                _ => {
+                    let op = op.binary().expect("binary operator");
+                    let right = self.pop();
+                    let left = self.pop();
+                    let value = left.binary(op, &right).map_err(|message| self.error(&message, span))?;
+                    self.push_allocated(value, span)?;
+                }
+            }
+
+            self.frames.last_mut().expect("frame").ip = next;
+        }
+    }
+
+    fn path(&self, index: usize) -> Vec<(&'b str, Span)> {
+        self.bytecode.paths[index].iter().map(|(field, span)| (field.as_str(), *span)).collect()
+    }
+}
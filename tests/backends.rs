@@ -0,0 +1,354 @@
+use std::time::Duration;
+
+use shanty::command;
+use shanty::compiler;
+use shanty::interpreter::{self, RuntimeError};
+use shanty::limits::Limits;
+use shanty::parser;
+use shanty::resolve;
+use shanty::value::Value;
+use shanty::vm;
+
+/// Runs `source` with the interpreter and the bytecode VM, checking that both agree before returning the result.
+fn run(source: &str) -> Result<Value, RuntimeError> {
+    run_limited(source, Limits::default())
+}
+
+fn run_limited(source: &str, limits: Limits) -> Result<Value, RuntimeError> {
+    let program = parser::parse(source).unwrap();
+    let resolution = resolve::resolve(&program);
+    assert!(resolution.errors.is_empty(), "{:?}", resolution.errors);
+
+    let interpreted = interpreter::run_limited(&program, &resolution, &mut command::Local::default(), limits);
+    let bytecode = compiler::compile(&program, &resolution).unwrap();
+    let executed = vm::run_limited(&bytecode, &mut command::Local::default(), limits);
+    assert_eq!(interpreted, executed, "backends disagree on:\n{}", source);
+    interpreted
+}
+
+fn value(source: &str) -> Value {
+    run(source).unwrap()
+}
+
+fn failure(source: &str) -> RuntimeError {
+    run(source).unwrap_err()
+}
+
+#[test]
+fn arithmetic_and_recursion() {
+    let source = "func integer fib(integer n) {
+    if (n < 2) {
+        return n;
+    }
+    return fib(n - 1) + fib(n - 2);
+}
+
+func integer main() {
+    return fib(15) * 2 - 10 / 3 + (7 & 3 | 8);
+}
+";
+    assert_eq!(value(source), Value::Integer(610 * 2 - 3 + 11));
+}
+
+#[test]
+fn loops_and_postfix_operators() {
+    let source = "func integer main() {
+    integer i = 0;
+    integer total = 0;
+    while (i < 10) {
+        integer before = total++;
+        total = before + i;
+        i++;
+    }
+    integer j = 5;
+    integer old = j--;
+    return total * 100 + old * 10 + j;
+}
+";
+    assert_eq!(value(source), Value::Integer(45 * 100 + 50 + 4));
+}
+
+#[test]
+fn strings_runes_and_comparisons() {
+    let source = "func string greet(string name) {
+    return \"hello \" + name + '!';
+}
+
+func boolean main() {
+    string greeting = greet(\"bob\");
+    return greeting == \"hello bob!\" && 'a' < 'b' && \"abc\" <= \"abd\" && !(1 >= 2) && 3 != 4;
+}
+";
+    assert_eq!(value(source), Value::Boolean(true));
+}
+
+#[test]
+fn short_circuit_skips_the_right_operand() {
+    let source = "integer calls = 0;
+
+func boolean touch() {
+    calls++;
+    return true;
+}
+
+func integer main() {
+    boolean a = false && touch();
+    boolean b = true || touch();
+    boolean c = true && touch();
+    return calls;
+}
+";
+    assert_eq!(value(source), Value::Integer(1));
+}
+
+#[test]
+fn complex_values_and_field_assignment() {
+    let source = "complex Point {
+    x: integer;
+    y: integer;
+}
+
+complex Line {
+    from: Point;
+    to: Point;
+}
+
+Line line = Line { to: Point { y: 4, x: 3 }, from: Point { x: 0, y: 0 } };
+
+func Line main() {
+    line.to.x = 10;
+    line.from.y++;
+    Point copy = line.to;
+    copy.y = 0;
+    return line;
+}
+";
+    let point = |x, y| Value::Complex { name: "Point".to_string(), fields: vec![("x".to_string(), Value::Integer(x)), ("y".to_string(), Value::Integer(y))] };
+    let expected = Value::Complex { name: "Line".to_string(), fields: vec![("from".to_string(), point(0, 1)), ("to".to_string(), point(10, 4))] };
+    assert_eq!(value(source), expected);
+}
+
+#[test]
+fn empty_and_nested_complex_values() {
+    let source = "complex Empty {}
+
+complex Box {
+    inner: Empty;
+    size: integer;
+}
+
+func boolean main() {
+    Box a = Box { inner: Empty {}, size: 1 };
+    Box b = Box { size: 1, inner: Empty {} };
+    return a == b && a.inner == Empty {};
+}
+";
+    assert_eq!(value(source), Value::Boolean(true));
+}
+
+#[test]
+fn domain_blocks_refer_to_each_other_by_name() {
+    let source = "repo backend {
+    url = \"git@example.com:backend.git\";
+}
+
+manifest app {
+    repo = backend;
+    path = \"k8s/\" + \"app.yaml\";
+}
+
+deployment production {
+    manifest = app;
+    repo = backend;
+    environment = \"prod\";
+}
+
+func deployment main() {
+    return production;
+}
+";
+    let complex = |name: &str, fields: Vec<(&str, Value)>| Value::Complex { name: name.to_string(), fields: fields.into_iter().map(|(field, value)| (field.to_string(), value)).collect() };
+    let backend = complex("repo", vec![("url", Value::String("git@example.com:backend.git".to_string()))]);
+    let app = complex("manifest", vec![("repo", backend.clone()), ("path", Value::String("k8s/app.yaml".to_string()))]);
+    let production = complex("deployment", vec![("manifest", app), ("repo", backend), ("environment", Value::String("prod".to_string()))]);
+    assert_eq!(value(source), production);
+}
+
+#[test]
+fn domains_are_evaluated_once_with_their_references() {
+    let source = "integer evaluated = 0;
+
+func string branch() {
+    evaluated++;
+    return \"main\";
+}
+
+repo backend {
+    url = \"git@example.com:backend.git\";
+    branch = branch();
+}
+
+deployment production {
+    repo = backend;
+    replicas = 3;
+}
+
+func integer main() {
+    repo r = backend;
+    deployment d = production;
+    return evaluated;
+}
+";
+    assert_eq!(value(source), Value::Integer(1));
+}
+
+#[test]
+fn top_level_statements_run_before_main() {
+    let source = "integer x = 1;
+if (x == 1) {
+    integer y = 2;
+    x = x + y;
+}
+x++;
+
+func integer main() {
+    return x;
+}
+";
+    assert_eq!(value(source), Value::Integer(4));
+    assert_eq!(value("integer x = 1;\nx = x + 1;\n"), Value::Void);
+}
+
+#[test]
+fn division_by_zero_points_at_the_expression_and_its_callers() {
+    let source = "func integer divide(integer a, integer b) {
+    return a / b;
+}
+
+func integer main() {
+    return divide(1, 0);
+}
+";
+    let error = failure(source);
+    assert_eq!(error.message, "Division by zero");
+    assert_eq!((error.span.line, error.span.column), (2, 12));
+    assert_eq!(error.calls.iter().map(|span| (span.line, span.column)).collect::<Vec<_>>(), vec![(6, 12)]);
+}
+
+#[test]
+fn overflow_and_type_errors() {
+    let error = failure("integer big = 9223372036854775807;\nbig++;\n");
+    assert_eq!(error.message, "9223372036854775807 + 1 overflows integer");
+    assert_eq!(error.span.line, 2);
+
+    let error = failure("integer x = -(1 + 1) * 4611686018427387904 - 1;\n");
+    assert_eq!(error.message, "-9223372036854775808 - 1 overflows integer");
+
+    let error = failure("boolean b = 1 < \"x\";\n");
+    assert_eq!(error.message, "Operator < can't be applied to integer and string");
+
+    let error = failure("if (1) {\n}\n");
+    assert_eq!(error.message, "Condition expects boolean but got integer");
+}
+
+#[test]
+fn reads_of_variables_without_a_value() {
+    let error = failure("integer x;\ninteger y = x + 1;\n");
+    assert_eq!(error.message, "x is read before it's given a value");
+    assert_eq!((error.span.line, error.span.column), (2, 13));
+
+    let source = "func integer early() {
+    return late;
+}
+
+integer value = early();
+integer late = 1;
+";
+    assert_eq!(failure(source).message, "late is read before it's declared");
+}
+
+#[test]
+fn wrong_argument_counts_and_self_referencing_domains() {
+    let error = failure("func integer one(integer a) {\n    return a;\n}\n\ninteger x = one(1, 2);\n");
+    assert_eq!(error.message, "Function one expects 1 arguments but got 2");
+
+    let source = "repo a {
+    url = b;
+}
+
+repo b {
+    url = a;
+}
+";
+    assert_eq!(failure(source).message, "repo a depends on itself");
+}
+
+#[test]
+fn builtins_lists_and_maps() {
+    let source = "map<string, integer> ages = [\"bob\": 3, \"al\": 5, \"bob\": 4];
+
+func string main() {
+    list<string> words = sort(split(\"b,a,c\", \",\"));
+    ages = remove(put(ages, \"cy\", 1), \"al\");
+    integer total = get(ages, \"bob\") + len(keys(ages)) + max(2, abs(-7)) + parse_int(\"ff\", 16);
+    return join(words, \"-\") + format_int(total, 2) + to_upper('x') + to_string(index_of(words, \"z\"));
+}
+";
+    assert_eq!(value(source), Value::String("a-b-c100001100X-1".to_string()));
+
+    let error = failure("list<integer> xs = [1];\ninteger x = get(xs, 2);\n");
+    assert_eq!(error.message, "Index 2 is out of bounds for a list of length 1");
+    assert_eq!((error.span.line, error.span.column), (2, 13));
+}
+
+#[test]
+fn resource_limits_stop_the_program_where_they_are_reached() {
+    let endless = "func integer main() {
+    integer i = 0;
+    while (i >= 0) {
+        i = i + 1;
+    }
+    return i;
+}
+";
+    let error = run_limited(endless, Limits { steps: Some(100), ..Limits::none() }).unwrap_err();
+    assert_eq!(error.message, "Step limit of 100 exceeded");
+    assert_eq!((error.span.line, error.span.column), (3, 5));
+
+    let recursive = "func integer depth(integer n) {
+    return depth(n + 1);
+}
+
+func integer main() {
+    return depth(0);
+}
+";
+    let error = run_limited(recursive, Limits { depth: Some(50), ..Limits::none() }).unwrap_err();
+    assert_eq!(error.message, "Call depth limit of 50 exceeded");
+    assert_eq!((error.span.line, error.span.column), (2, 12));
+    assert_eq!(error.calls.len(), 50);
+    // Calls are steps too
+    let error = run_limited(recursive, Limits { steps: Some(10), ..Limits::none() }).unwrap_err();
+    assert_eq!(error.message, "Step limit of 10 exceeded");
+
+    let growing = "list<string> parts = [];
+string part = \"ab\";
+while (len(parts) < 100) {
+    part = part + part;
+    parts = append(parts, part);
+}
+";
+    let error = run_limited(growing, Limits { heap: Some(10_000), ..Limits::none() }).unwrap_err();
+    assert_eq!(error.message, "Heap limit of 10000 bytes exceeded");
+    assert_eq!(error.span.line, 4);
+    assert!(run_limited(growing.replace("100", "3").as_str(), Limits { heap: Some(10_000), ..Limits::none() }).is_ok());
+
+    // Where time runs out differs between runs, only the message is checked
+    let program = parser::parse(endless).unwrap();
+    let resolution = resolve::resolve(&program);
+    let limits = Limits { time: Some(Duration::from_millis(50)), ..Limits::none() };
+    let error = interpreter::run_limited(&program, &resolution, &mut command::Local::default(), limits).unwrap_err();
+    assert_eq!(error.message, "Time limit of 50ms exceeded");
+    let error = vm::run_limited(&compiler::compile(&program, &resolution).unwrap(), &mut command::Local::default(), limits).unwrap_err();
+    assert_eq!(error.message, "Time limit of 50ms exceeded");
+}
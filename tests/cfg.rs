@@ -0,0 +1,118 @@
+use shanty::ast::Item;
+use shanty::cfg::{self, Cfg};
+use shanty::parser;
+
+/// Flow errors of `source`, with their line and column, warnings marked as such.
+fn errors(source: &str) -> Vec<String> {
+    let program = parser::parse(source).unwrap();
+    cfg::check(&program)
+        .iter()
+        .map(|error| format!("{}:{} {}{}", error.span.line, error.span.column, if error.warning { "warning: " } else { "" }, error.message))
+        .collect()
+}
+
+#[test]
+fn code_after_a_return_is_unreachable() {
+    let source = "func integer f() {
+    return 1;
+    integer a = 2;
+    a = 3;
+}
+";
+    // Only the first statement of the dead code is reported
+    assert_eq!(errors(source), vec!["3:5 warning: Unreachable code"]);
+}
+
+#[test]
+fn code_after_an_if_returning_on_both_branches_is_unreachable() {
+    let source = "func integer f(boolean b) {
+    if (b) {
+        return 1;
+    } else {
+        return 2;
+    }
+    return 3;
+}
+";
+    assert_eq!(errors(source), vec!["7:5 warning: Unreachable code"]);
+}
+
+#[test]
+fn functions_with_a_value_need_a_return_on_every_path() {
+    let source = "func integer f(boolean b) {
+    if (b) {
+        return 1;
+    }
+}
+";
+    assert_eq!(errors(source), vec!["5:1 Function f returns integer but can reach its end without a return"]);
+
+    let source = "func integer f(integer n) {
+    while (n > 0) {
+        return n;
+    }
+}
+";
+    assert_eq!(errors(source), vec!["5:1 Function f returns integer but can reach its end without a return"]);
+}
+
+#[test]
+fn returning_on_every_path_is_enough() {
+    let source = "func integer f(boolean b) {
+    if (b) {
+        return 1;
+    } else {
+        if (!b) {
+            return 2;
+        } else {
+            return 3;
+        }
+    }
+}
+func integer g() {
+    while (true) {
+        return 1;
+    }
+}
+func void h(boolean b) {
+    if (b) {
+        return;
+    }
+}
+";
+    assert_eq!(errors(source), Vec::<String>::new());
+}
+
+#[test]
+fn endless_loops_need_a_return() {
+    let source = "func void f() {
+    integer i = 0;
+    while (true) {
+        i = i + 1;
+    }
+}
+";
+    assert_eq!(errors(source), vec!["3:5 while (true) never ends, its body has no return"]);
+}
+
+#[test]
+fn the_graph_of_a_body_reaches_its_end_unless_every_path_returns() {
+    let program = parser::parse("func integer f(boolean b) { if (b) { return 1; } return 2; }\nfunc integer g(boolean b) { if (b) { return 1; } }\n").unwrap();
+    let funcs: Vec<_> = program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Func(func) => Some(func),
+            _ => None,
+        })
+        .collect();
+
+    let returning = Cfg::build(funcs[0]);
+    let reachable = returning.reachable();
+    assert!(reachable[returning.entry] && reachable[returning.exit]);
+    assert!(!reachable[returning.end]);
+
+    let falling_off = Cfg::build(funcs[1]);
+    assert!(falling_off.reachable()[falling_off.end]);
+    assert!(falling_off.predecessors()[falling_off.exit].iter().all(|&block| block != falling_off.end));
+}
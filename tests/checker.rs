@@ -0,0 +1,248 @@
+use shanty::checker;
+use shanty::parser;
+
+/// Messages of the type errors of `source`, with their line and column.
+fn errors(source: &str) -> Vec<String> {
+    let program = parser::parse(source).unwrap();
+    checker::check(&program).iter().map(|error| format!("{}:{} {}", error.span.line, error.span.column, error.message)).collect()
+}
+
+#[test]
+fn well_typed_programs_have_no_errors() {
+    let source = "complex Point { x: integer; y: integer; }
+func integer norm(Point p) {
+    return p.x * p.x + p.y * p.y;
+}
+func integer main() {
+    string name = \"a\" + 'b';
+    boolean ok = 1 < 2 && name == \"ab\";
+    if (ok) {
+        return norm(Point { x: 3, y: 4 });
+    }
+    return 0;
+}
+";
+    assert_eq!(errors(source), Vec::<String>::new());
+}
+
+#[test]
+fn operators_need_operands_they_support() {
+    let source = "integer a = 1 + \"one\";\nboolean b = !1;\nboolean c = true < false;\ninteger d = -\"x\";\n";
+    assert_eq!(
+        errors(source),
+        vec![
+            "1:13 Operator + can't be applied to integer and string",
+            "2:13 Operator ! can't be applied to integer",
+            "3:13 Operator < can't be applied to boolean and boolean",
+            "4:13 Operator - can't be applied to string",
+        ]
+    );
+}
+
+#[test]
+fn calls_match_the_signature() {
+    let source = "func integer twice(integer n) {
+    return 2 * n;
+}
+integer a = twice();
+integer b = twice(\"2\");
+string c = twice(1);
+integer d = a(1);
+";
+    assert_eq!(
+        errors(source),
+        vec![
+            "4:13 Function twice expects 1 arguments but got 0",
+            "5:19 Argument 1 of twice expects integer but got string",
+            "6:12 Variable c expects string but got integer",
+            "7:13 a is a variable of type integer, not a function",
+        ]
+    );
+}
+
+#[test]
+fn returns_and_conditions_are_checked() {
+    let source = "func integer f() {
+    return \"no\";
+}
+func void g() {
+    return 1;
+}
+func integer h() {
+    if (1) {
+        return;
+    }
+    while (\"x\") {}
+    return 0;
+}
+";
+    assert_eq!(
+        errors(source),
+        vec![
+            "2:12 Function f expects integer but got string",
+            "5:12 Function g returns void but return has a value",
+            "8:9 Condition of if expects boolean but got integer",
+            "9:9 Function h returns integer but return has no value",
+            "11:12 Condition of while expects boolean but got string",
+        ]
+    );
+}
+
+#[test]
+fn complex_literals_set_every_field_with_its_type() {
+    let source = "complex Point { x: integer; y: integer; }
+var a = Point { x: 1 };
+var b = Point { x: 1, y: \"2\" };
+var c = Point { x: 1, y: 2, z: 3 };
+integer d = a.z;
+";
+    assert_eq!(
+        errors(source),
+        vec![
+            "2:9 Missing fields y in Point literal",
+            "3:26 Field y expects integer but got string",
+            "4:29 Complex type Point has no field z",
+            "5:15 Complex type Point has no field z",
+        ]
+    );
+}
+
+#[test]
+fn duplicate_declarations_are_reported() {
+    let source = "func integer f() {
+    return 1;
+}
+func integer f() {
+    return 2;
+}
+command c() {
+    run = \"true\";
+}
+command c() {
+    run = \"false\";
+}
+complex P {}
+complex P {}
+integer main = f();
+";
+    assert_eq!(
+        errors(source),
+        vec!["4:14 Function f is declared more than once", "10:9 Command c is declared more than once", "14:9 Complex type P is declared more than once"]
+    );
+}
+
+#[test]
+fn secrets_are_only_accepted_by_commands() {
+    let source = "secret token {
+    env = \"TOKEN\";
+}
+string shown = token;
+command deploy() {
+    run = \"deploy \" + token;
+}
+";
+    assert_eq!(errors(source), vec!["4:16 Variable shown expects string but got secret, which only commands accept"]);
+}
+
+#[test]
+fn complex_types_are_checked() {
+    let source = "complex Point {
+    x: integer;
+    x: string;
+    next: Missing;
+}
+
+Point a = Point { x: 1, next: 2 };
+integer b = a.y;
+integer n = 1;
+integer c = n.x;
+";
+    assert_eq!(
+        errors(source),
+        vec!["3:5 Field x is declared more than once in Point", "4:5 Unknown type Missing", "8:15 Complex type Point has no field y", "10:15 Type integer has no fields"]
+    );
+}
+
+#[test]
+fn domain_blocks_refer_to_each_other_by_name() {
+    let source = "repo backend {
+    url = \"git@example.com:backend.git\";
+}
+
+manifest app {
+    repo = backend;
+    path = \"k8s/\" + \"app.yaml\";
+}
+
+deployment production {
+    manifest = app;
+    repo = backend;
+    environment = \"prod\";
+}
+
+func deployment main() {
+    return production;
+}
+";
+    assert_eq!(errors(source), Vec::<String>::new());
+    let mixed = "repo backend {\n    url = \"x\";\n}\n\ndeployment d = backend;\n";
+    assert_eq!(errors(mixed), vec!["5:16 Variable d expects deployment but got repo"]);
+}
+
+/// Types inferred for the bindings of `source`, by name, in source order.
+fn inferred(source: &str) -> Vec<(String, String)> {
+    let program = parser::parse(source).unwrap();
+    let types = checker::infer(&program);
+    assert_eq!(types.errors, vec![]);
+
+    let mut bindings: Vec<_> = types.bindings.iter().collect();
+    bindings.sort_by_key(|(span, _)| (span.line, span.column));
+    let lines: Vec<&str> = source.lines().collect();
+    bindings.iter().map(|(span, ty)| (lines[span.line - 1][span.column - 1..span.end_column - 1].to_string(), ty.to_string())).collect()
+}
+
+#[test]
+fn var_declarations_take_the_type_of_their_value() {
+    let source = "complex Point { x: integer; }
+var count = 1 + 2;
+var name = \"a\" + 'b';
+var ok = count > 1;
+var point = Point { x: 1 };
+var x = point.x;
+var later;
+later = name;
+";
+    let expected = [("count", "integer"), ("name", "string"), ("ok", "boolean"), ("point", "Point"), ("x", "integer"), ("later", "string")];
+    let expected: Vec<(String, String)> = expected.iter().map(|(name, ty)| (name.to_string(), ty.to_string())).collect();
+    assert_eq!(inferred(source), expected);
+}
+
+#[test]
+fn list_and_map_elements_are_inferred_from_the_first_one() {
+    let source = "var words = [\"a\", \"b\"];\nvar nested = [[1], [2, 3]];\nvar ages = [\"bob\": 3];\nvar flags = [1: [true]];\n";
+    let types: Vec<String> = inferred(source).into_iter().map(|(_, ty)| ty).collect();
+    assert_eq!(types, ["list<string>", "list<list<integer>>", "map<string, integer>", "map<integer, list<boolean>>"]);
+
+    let source = "var mixed = [1, \"two\", 3];\nvar keys = [\"a\": 1, 2: 2];\nvar values = [\"a\": 1, \"b\": false];\n";
+    assert_eq!(
+        errors(source),
+        vec![
+            "1:17 List element expects integer but got string",
+            "2:21 Map key expects string but got integer",
+            "3:28 Map value expects integer but got boolean",
+        ]
+    );
+}
+
+#[test]
+fn inferred_types_are_enforced_afterwards() {
+    let source = "var count = 1;\ncount = \"two\";\nvar later;\nlater = true;\nlater = 1;\nvar never;\n";
+    assert_eq!(
+        errors(source),
+        vec![
+            "2:9 Variable count expects integer but got string",
+            "5:9 Variable later expects boolean but got integer",
+            "6:5 Can't infer the type of never, it's never given a value",
+        ]
+    );
+}
@@ -0,0 +1,147 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Runs `shanty` with `args`, returning its exit code, stdout and stderr.
+fn shanty(args: &[&str]) -> (i32, String, String) {
+    shanty_with(args, &[])
+}
+
+/// Runs `shanty` with `args` and the environment variables `env` added.
+fn shanty_with(args: &[&str], env: &[(&str, &str)]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_shanty")).args(args).envs(env.iter().copied()).output().unwrap();
+    (output.status.code().unwrap_or(-1), String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+}
+
+/// Writes `source` to a file of its own.
+fn program(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("shanty-cli-{}-{}.sh", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+const ENDLESS_RECURSION: &str = "func integer deeper(integer n) {
+    return deeper(n + 1);
+}
+
+func integer main() {
+    return deeper(0);
+}
+";
+
+#[test]
+fn call_depth_stays_within_what_the_stack_holds() {
+    let path = program("depth", ENDLESS_RECURSION);
+    let file = path.to_str().unwrap();
+
+    let (code, _, stderr) = shanty(&["run", "--max-depth", "100000000", file]);
+    assert_eq!(code, 2);
+    assert!(stderr.contains("--max-depth can't be above 10000"), "{}", stderr);
+
+    for backend in [&["run"][..], &["run", "--vm"][..]] {
+        let args: Vec<&str> = backend.iter().copied().chain(["--max-depth", "10000", file]).collect();
+        let (code, _, stderr) = shanty(&args);
+        assert_eq!(code, 1, "{}", stderr);
+        assert!(stderr.contains("Call depth limit of 10000 exceeded"), "{}", stderr);
+    }
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn fmt_check_reports_unformatted_files_with_a_diff() {
+    let path = program("fmt", "func integer main() { return 1; }\n");
+    let file = path.to_str().unwrap();
+
+    let (code, stdout, _) = shanty(&["fmt", "--check", file]);
+    assert_eq!(code, 1);
+    let diff = format!("--- {0}\n+++ {0}\n@@ -1,1 +1,3 @@\n-func integer main() {{ return 1; }}\n+func integer main() {{\n+    return 1;\n+}}\n", file);
+    assert_eq!(stdout, diff);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "func integer main() { return 1; }\n");
+
+    assert_eq!(shanty(&["fmt", file]).0, 0);
+    assert_eq!(shanty(&["fmt", "--check", file]), (0, String::new(), String::new()));
+
+    fs::write(&path, "func integer main( {\n").unwrap();
+    assert_eq!(shanty(&["fmt", "--check", file]).0, 2);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn json_diagnostics_are_printed_one_object_per_line() {
+    let path = program("json", "integer a = \"one\";\nstring b = 2;\n");
+    let file = path.to_str().unwrap();
+
+    let (code, stdout, stderr) = shanty(&["check", "--format", "json", file]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "");
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", stderr);
+    for (line, number) in lines.iter().zip(["1", "2"]) {
+        assert!(line.starts_with("{\"severity\":\"error\",\"code\":\"E0200\""), "{}", line);
+        assert!(line.contains(&format!("\"line\":{},", number)), "{}", line);
+        assert!(line.ends_with('}'), "{}", line);
+    }
+}
+
+const BUILD: &str = "command build(string target) {
+    run = \"make \" + target;
+    timeout = 60;
+}
+
+func integer main() {
+    var result = build(\"all\");
+    return 10 / result.status;
+}
+";
+
+#[test]
+fn dry_runs_print_the_plan_then_the_value_or_failure_of_main() {
+    let path = program("dry-run", BUILD);
+    let stubs = program("stubs", "# build fails once\nbuild = 2 \"\" \"no rule\"\n");
+    let (file, stubs_file) = (path.to_str().unwrap(), stubs.to_str().unwrap());
+
+    let (code, stdout, _) = shanty(&["run", "--dry-run", "--stubs", stubs_file, file]);
+    assert_eq!((code, stdout.as_str()), (0, "1. build\n    run: make all\n    timeout: 60s\n5\n"));
+
+    // Without a stub the command succeeds with status 0, which main divides by
+    let (code, stdout, stderr) = shanty(&["run", "--dry-run", file]);
+    assert_eq!((code, stdout.as_str()), (1, "1. build\n    run: make all\n    timeout: 60s\n"));
+    assert!(stderr.contains("Division by zero"), "{}", stderr);
+
+    let (code, stdout, _) = shanty(&["run", "--dry-run", "--format", "json", "--stubs", stubs_file, file]);
+    assert_eq!(code, 0);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines, vec!["[{\"command\":\"build\",\"argv\":[\"make\",\"all\"],\"cwd\":null,\"env\":{},\"timeout\":60}]", "5"]);
+
+    fs::write(&stubs, "build = two\n").unwrap();
+    let (code, _, stderr) = shanty(&["run", "--dry-run", "--stubs", stubs_file, file]);
+    assert_eq!(code, 2);
+    assert!(stderr.contains("Expected an integer status for build at line 1"), "{}", stderr);
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(stubs).unwrap();
+}
+
+const PRINTENV: &str = "secret token {
+    env = \"SHANTY_TOKEN\";
+}
+
+command show() {
+    run = \"printenv SHANTY_TOKEN\";
+}
+
+func string main() {
+    secret loaded = token;
+    return show().stdout;
+}
+";
+
+#[test]
+fn loaded_secrets_are_masked_in_streamed_output_and_the_value() {
+    let path = program("printenv", PRINTENV);
+    let (code, stdout, stderr) = shanty_with(&["run", path.to_str().unwrap()], &[("SHANTY_TOKEN", "hunter2\nsecond line")]);
+
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(stdout, "[show] ****\n****\n\n");
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use shanty::command::{DryRun, Invocation, Local, Outcome, Runner};
+use shanty::secret::Source;
+
+fn invocation(command: &str) -> Invocation {
+    Invocation { command: command.to_string(), argv: vec!["true".to_string()], cwd: None, env: vec![], timeout: None, secrets: vec![] }
+}
+
+fn outcome(status: i64, stdout: &str, stderr: &str) -> Outcome {
+    Outcome { status, stdout: stdout.to_string(), stderr: stderr.to_string(), timed_out: false }
+}
+
+#[test]
+fn stubs_answer_successive_calls_then_repeat() {
+    let mut dry_run = DryRun::parse_stubs("# deploy retries once\ndeploy = 1 \"\" 'no route'\ndeploy = 0 done\n\nbuild = 0 # comment\n").unwrap();
+
+    assert_eq!(dry_run.run(&invocation("deploy")), Ok(outcome(1, "", "no route")));
+    assert_eq!(dry_run.run(&invocation("deploy")), Ok(outcome(0, "done", "")));
+    assert_eq!(dry_run.run(&invocation("deploy")), Ok(outcome(0, "done", "")));
+    assert_eq!(dry_run.run(&invocation("build")), Ok(outcome(0, "", "")));
+    assert_eq!(dry_run.plan.len(), 4);
+}
+
+#[test]
+fn commands_without_stub_succeed_without_output() {
+    let mut dry_run = DryRun::parse_stubs("build = 2\n").unwrap();
+    assert_eq!(dry_run.run(&invocation("test")), Ok(Outcome::default()));
+    assert_eq!(dry_run.plan, vec![invocation("test")]);
+}
+
+#[test]
+fn malformed_stub_lines_are_reported_with_their_line() {
+    let error = |text: &str| DryRun::parse_stubs(text).unwrap_err();
+
+    assert_eq!(error("build = 0\nbuild 0\n"), "Expected command = status [stdout [stderr]] at line 2");
+    assert_eq!(error(" = 0"), "Expected command = status [stdout [stderr]] at line 1");
+    assert_eq!(error("build ="), "Expected command = status [stdout [stderr]] at line 1");
+    assert_eq!(error("build = 0 out err extra"), "Expected command = status [stdout [stderr]] at line 1");
+    assert_eq!(error("build = ok"), "Expected an integer status for build at line 1");
+    assert!(error("build = 0 \"out").ends_with(" at line 1"));
+}
+
+fn local(argv: &[&str], timeout: u64) -> Invocation {
+    Invocation { argv: argv.iter().map(|word| word.to_string()).collect(), timeout: Some(timeout), ..invocation("slow") }
+}
+
+#[test]
+fn timed_out_commands_are_killed_with_their_children() {
+    let start = Instant::now();
+    let outcome = Local::quiet().run(&local(&["sh", "-c", "echo started; sleep 30 & sleep 30"], 1)).unwrap();
+
+    assert!(outcome.timed_out);
+    assert_eq!((outcome.status, outcome.stdout.as_str()), (137, "started\n"));
+    assert!(start.elapsed() < Duration::from_secs(10), "{:?}", start.elapsed());
+}
+
+#[test]
+fn output_of_processes_escaping_the_timeout_is_given_up_on() {
+    // setsid moves sleep out of the process group killed on timeout, it keeps stdout open for 30 seconds
+    let start = Instant::now();
+    let outcome = Local::quiet().run(&local(&["sh", "-c", "echo started; setsid sleep 30 & sleep 30"], 1)).unwrap();
+
+    assert!(outcome.timed_out);
+    assert_eq!(outcome.stdout, "started\n");
+    assert!(start.elapsed() < Duration::from_secs(10), "{:?}", start.elapsed());
+}
+
+#[test]
+fn commands_finishing_in_time_are_not_timed_out() {
+    let outcome = Local::quiet().run(&local(&["sh", "-c", "sleep 0.1; echo done"], 5)).unwrap();
+    assert_eq!(outcome, Outcome { status: 0, stdout: "done\n".to_string(), ..Outcome::default() });
+}
+
+/// A secret loaded from an environment variable of its own.
+fn secret(runner: &mut dyn Runner, variable: &str, value: &str) -> String {
+    std::env::set_var(variable, value);
+    runner.secret("token", &Source::Env(variable.to_string())).unwrap()
+}
+
+#[test]
+fn loaded_secrets_are_masked_in_the_output_of_any_command() {
+    let mut runner = Local::quiet();
+    secret(&mut runner, "SHANTY_TEST_PRINTED", "hunter2");
+
+    let outcome = runner.run(&local(&["sh", "-c", "printenv SHANTY_TEST_PRINTED; printenv SHANTY_TEST_PRINTED >&2"], 5)).unwrap();
+    assert_eq!((outcome.stdout.as_str(), outcome.stderr.as_str()), ("****\n", "****\n"));
+}
+
+#[test]
+fn secrets_spanning_lines_are_masked_whole() {
+    let mut runner = Local::quiet();
+    secret(&mut runner, "SHANTY_TEST_KEY", "-----BEGIN KEY-----\nabc\n-----END KEY-----");
+
+    let outcome = runner.run(&local(&["sh", "-c", "echo start; printenv SHANTY_TEST_KEY; echo end"], 5)).unwrap();
+    assert_eq!(outcome.stdout, "start\n****\nend\n");
+}
+
+#[test]
+fn loaded_secrets_are_masked_in_errors_and_plans() {
+    let mut runner = Local::quiet();
+    let value = secret(&mut runner, "SHANTY_TEST_PROGRAM", "no-such-program-hunter2");
+    let error = runner.run(&local(&[&value], 5)).unwrap_err();
+    assert!(error.starts_with("Can't run ****: "), "{}", error);
+
+    let mut dry_run = DryRun::default();
+    let value = secret(&mut dry_run, "SHANTY_TEST_PLANNED", "hunter2");
+    dry_run.run(&local(&["deploy", "--token", &value], 5)).unwrap();
+    assert_eq!(dry_run.plan[0].argv, vec!["deploy", "--token", "****"]);
+}
@@ -0,0 +1,97 @@
+use shanty::ast::{BinaryOp, Item, Program, StmtKind};
+use shanty::constant::{self, Constant, Constants};
+use shanty::parser;
+use shanty::resolve;
+
+fn fold(source: &str) -> (Program, Constants) {
+    let program = parser::parse(source).unwrap();
+    let constants = constant::fold(&program, &resolve::resolve(&program));
+    (program, constants)
+}
+
+/// Folded value of the initializer of each top-level variable, in order.
+fn initializers(source: &str) -> Vec<Option<Constant>> {
+    let (program, constants) = fold(source);
+    program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Stmt(stmt) => match &stmt.kind {
+                StmtKind::VarDecl { value: Some(value), .. } => Some(constants.value(value).cloned()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Messages of the folding errors of `source`, with their line and column.
+fn errors(source: &str) -> Vec<String> {
+    fold(source).1.errors.iter().map(|error| format!("{}:{} {}", error.span.line, error.span.column, error.message)).collect()
+}
+
+#[test]
+fn literals_and_operators_fold() {
+    let source = "integer a = 1 + 2 * 3;
+string b = \"ab\" + 'c';
+boolean c = !(1 < 2) || 3 == 3;
+integer d = -(7 / 2);
+rune e = 'x';
+";
+    assert_eq!(
+        initializers(source),
+        vec![
+            Some(Constant::Integer(7)),
+            Some(Constant::String("abc".to_string())),
+            Some(Constant::Boolean(true)),
+            Some(Constant::Integer(-3)),
+            Some(Constant::Rune('x')),
+        ]
+    );
+}
+
+#[test]
+fn variables_never_assigned_to_are_constants() {
+    let source = "integer a = 2;
+integer b = a * 10;
+integer c = 1;
+c = 5;
+integer d = c + 1;
+";
+    assert_eq!(initializers(source), vec![Some(Constant::Integer(2)), Some(Constant::Integer(20)), Some(Constant::Integer(1)), None]);
+
+    let source = "integer n = 1;\nn++;\ninteger m = n;\n";
+    assert_eq!(initializers(source), vec![Some(Constant::Integer(1)), None]);
+}
+
+#[test]
+fn boolean_operators_short_circuit() {
+    let source = "func boolean f() { return true; }
+boolean a = false && f();
+boolean b = true || f();
+boolean c = true && f();
+";
+    assert_eq!(initializers(source), vec![Some(Constant::Boolean(false)), Some(Constant::Boolean(true)), None]);
+}
+
+#[test]
+fn division_by_zero_is_an_error() {
+    let source = "integer a = 1 / 0;\nfunc integer f(integer n) {\n    return n / (2 - 2);\n}\n";
+    assert_eq!(errors(source), vec!["1:13 Division by zero", "3:12 Division by zero"]);
+}
+
+#[test]
+fn overflow_is_an_error() {
+    let source = "integer a = 9223372036854775807 + 1;\ninteger b = 3037000500 * 3037000500;\n";
+    assert_eq!(
+        errors(source),
+        vec!["1:13 9223372036854775807 + 1 overflows integer", "2:13 3037000500 * 3037000500 overflows integer"]
+    );
+    assert_eq!(initializers(source), vec![None, None]);
+}
+
+#[test]
+fn constants_of_mismatched_types_are_left_to_the_checker() {
+    assert_eq!(constant::binary(BinaryOp::Plus, &Constant::Integer(1), &Constant::Boolean(true)), Ok(None));
+    assert_eq!(errors("integer a = 1 + true;\n"), Vec::<String>::new());
+}
@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use shanty::json::{self, Json};
+
+const PROGRAM: &str = "integer total = 0;
+
+func integer add(integer a, integer b) {
+    integer sum = a + b;
+    return sum;
+}
+
+func integer main() {
+    list<integer> xs = [1, 2];
+    integer x = add(1, 2);
+    total = x * 2;
+    return total;
+}
+";
+
+/// Talks to `shanty dap` over its stdin and stdout, one request at a time.
+struct Client {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    seq: i64,
+    /// Events read while waiting for a response
+    events: VecDeque<Json>,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_shanty")).arg("dap").stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().unwrap();
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        Client { child, input, output, seq: 0, events: VecDeque::new() }
+    }
+
+    fn read(&mut self) -> Json {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            assert!(self.output.read_line(&mut header).unwrap() > 0, "adapter closed its output");
+            match header.trim_end().strip_prefix("Content-Length: ") {
+                Some(value) => length = value.parse().unwrap(),
+                None if header.trim_end().is_empty() => break,
+                None => panic!("unexpected header {:?}", header),
+            }
+        }
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+        json::parse(&String::from_utf8(body).unwrap()).unwrap()
+    }
+
+    /// Sends a request and returns its response.
+    fn request(&mut self, command: &str, arguments: &str) -> Json {
+        self.seq += 1;
+        let body = format!("{{\"seq\":{},\"type\":\"request\",\"command\":\"{}\",\"arguments\":{}}}", self.seq, command, arguments);
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+
+        loop {
+            let message = self.read();
+            match message.get("type").and_then(Json::as_str) {
+                Some("response") if message.get("request_seq").and_then(Json::as_i64) == Some(self.seq) => return message,
+                Some("event") => self.events.push_back(message),
+                _ => panic!("unexpected message {:?}", message),
+            }
+        }
+    }
+
+    /// Sends a request that must succeed and returns the body of its response.
+    fn ok(&mut self, command: &str, arguments: &str) -> Json {
+        let response = self.request(command, arguments);
+        assert_eq!(response.get("success"), Some(&Json::Bool(true)), "{} failed: {:?}", command, response);
+        response.get("body").cloned().unwrap_or(Json::Null)
+    }
+
+    /// Waits for the next event named `name`, returning its body.
+    fn event(&mut self, name: &str) -> Json {
+        loop {
+            let message = match self.events.pop_front() {
+                Some(message) => message,
+                None => self.read(),
+            };
+            if message.get("event").and_then(Json::as_str) == Some(name) {
+                return message.get("body").cloned().unwrap_or(Json::Null);
+            }
+        }
+    }
+
+    /// Waits for the program to stop, returning the reason and the line and function of each frame.
+    fn stopped(&mut self) -> (String, Vec<(i64, String)>) {
+        let reason = self.event("stopped").get("reason").and_then(Json::as_str).unwrap().to_string();
+        let trace = self.ok("stackTrace", "{\"threadId\":1}");
+        let frames = trace.get("stackFrames").and_then(Json::as_array).unwrap();
+        let frames = frames.iter().map(|frame| (frame.get("line").and_then(Json::as_i64).unwrap(), frame.get("name").and_then(Json::as_str).unwrap().to_string())).collect();
+        (reason, frames)
+    }
+
+    /// Names and values of the variables under `reference`, with the reference of their children.
+    fn variables(&mut self, reference: i64) -> Vec<(String, String, i64)> {
+        let body = self.ok("variables", &format!("{{\"variablesReference\":{}}}", reference));
+        let variables = body.get("variables").and_then(Json::as_array).unwrap();
+        variables
+            .iter()
+            .map(|variable| {
+                let text = |key: &str| variable.get(key).and_then(Json::as_str).unwrap().to_string();
+                (text("name"), text("value"), variable.get("variablesReference").and_then(Json::as_i64).unwrap())
+            })
+            .collect()
+    }
+
+    /// Reference of the variables of scope `name` in frame `frame`.
+    fn scope(&mut self, frame: i64, name: &str) -> i64 {
+        let body = self.ok("scopes", &format!("{{\"frameId\":{}}}", frame));
+        let scopes = body.get("scopes").and_then(Json::as_array).unwrap();
+        let scope = scopes.iter().find(|scope| scope.get("name").and_then(Json::as_str) == Some(name)).unwrap();
+        scope.get("variablesReference").and_then(Json::as_i64).unwrap()
+    }
+
+    fn finish(mut self) {
+        self.ok("disconnect", "{}");
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+/// Writes `source` to a file of its own for the adapter to launch.
+fn program(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("shanty-dap-{}-{}.sh", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn named(variables: &[(String, String, i64)]) -> Vec<(&str, &str)> {
+    variables.iter().map(|(name, value, _)| (name.as_str(), value.as_str())).collect()
+}
+
+#[test]
+fn breakpoints_steps_and_variables() {
+    let path = program("steps", PROGRAM);
+    let source = json::string(&path.display().to_string());
+    let mut client = Client::start();
+
+    let capabilities = client.ok("initialize", "{\"adapterID\":\"shanty\"}");
+    assert_eq!(capabilities.get("supportsConfigurationDoneRequest"), Some(&Json::Bool(true)));
+    client.event("initialized");
+    client.ok("launch", &format!("{{\"program\":{}}}", source));
+
+    // Line 7 is blank, its breakpoint moves to the next statement
+    let breakpoints = client.ok("setBreakpoints", &format!("{{\"source\":{{\"path\":{}}},\"breakpoints\":[{{\"line\":4}},{{\"line\":7}}]}}", source));
+    let lines: Vec<i64> = breakpoints.get("breakpoints").and_then(Json::as_array).unwrap().iter().map(|b| b.get("line").and_then(Json::as_i64).unwrap()).collect();
+    assert_eq!(lines, vec![4, 9]);
+    client.ok("configurationDone", "{}");
+
+    let main = |line: i64| (line, "main".to_string());
+    assert_eq!(client.stopped(), ("breakpoint".to_string(), vec![main(9), (1, "<top level>".to_string())]));
+    client.ok("continue", "{\"threadId\":1}");
+
+    assert_eq!(client.stopped().1, vec![(4, "add".to_string()), main(10), (1, "<top level>".to_string())]);
+    let locals = client.scope(0, "Locals");
+    assert_eq!(named(&client.variables(locals)), vec![("a", "1"), ("b", "2")]);
+    let globals = client.scope(0, "Globals");
+    assert_eq!(named(&client.variables(globals)), vec![("total", "0")]);
+
+    client.ok("next", "{\"threadId\":1}");
+    assert_eq!(client.stopped(), ("step".to_string(), vec![(5, "add".to_string()), main(10), (1, "<top level>".to_string())]));
+    let locals = client.scope(0, "Locals");
+    assert_eq!(named(&client.variables(locals)), vec![("a", "1"), ("b", "2"), ("sum", "3")]);
+
+    client.ok("stepOut", "{\"threadId\":1}");
+    assert_eq!(client.stopped().1, vec![main(11), (1, "<top level>".to_string())]);
+    let locals = client.scope(0, "Locals");
+    let variables = client.variables(locals);
+    assert_eq!(named(&variables), vec![("xs", "[1, 2]"), ("x", "3")]);
+    assert_eq!(named(&client.variables(variables[0].2)), vec![("[0]", "1"), ("[1]", "2")]);
+
+    client.ok("continue", "{\"threadId\":1}");
+    assert_eq!(client.event("output").get("output").and_then(Json::as_str), Some("6\n"));
+    assert_eq!(client.event("exited").get("exitCode").and_then(Json::as_i64), Some(0));
+    client.event("terminated");
+    client.finish();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn stop_on_entry_step_in_and_failures() {
+    let path = program("entry", &PROGRAM.replace("return total;", "return total / (x - 3);"));
+    let source = json::string(&path.display().to_string());
+    let mut client = Client::start();
+    client.ok("initialize", "{}");
+    client.ok("launch", &format!("{{\"program\":{},\"stopOnEntry\":true}}", source));
+    client.ok("configurationDone", "{}");
+
+    assert_eq!(client.stopped(), ("entry".to_string(), vec![(1, "<top level>".to_string())]));
+    client.ok("stepIn", "{\"threadId\":1}");
+    assert_eq!(client.stopped().1, vec![(9, "main".to_string()), (1, "<top level>".to_string())]);
+    client.ok("next", "{\"threadId\":1}");
+    assert_eq!(client.stopped().1[0], (10, "main".to_string()));
+    client.ok("stepIn", "{\"threadId\":1}");
+    assert_eq!(client.stopped().1[0], (4, "add".to_string()));
+
+    client.ok("continue", "{\"threadId\":1}");
+    let output = client.event("output");
+    assert_eq!(output.get("category").and_then(Json::as_str), Some("stderr"));
+    assert!(output.get("output").and_then(Json::as_str).unwrap().contains("Division by zero"));
+    assert_eq!(client.event("exited").get("exitCode").and_then(Json::as_i64), Some(1));
+    client.finish();
+
+    let mut client = Client::start();
+    client.ok("initialize", "{}");
+    let bad = program("bad", "integer x = \"a\";\n");
+    let response = client.request("launch", &format!("{{\"program\":{}}}", json::string(&bad.display().to_string())));
+    assert_eq!(response.get("success"), Some(&Json::Bool(false)));
+    assert!(response.get("message").and_then(Json::as_str).unwrap().contains("Variable x expects integer but got string"));
+    client.finish();
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(bad).unwrap();
+}
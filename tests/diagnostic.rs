@@ -0,0 +1,173 @@
+use shanty::ast::{FileId, Span};
+use shanty::command;
+use shanty::diagnostic::{Diagnostic, Source, Sources};
+use shanty::interpreter;
+use shanty::limits::Limits;
+use shanty::parser;
+use shanty::resolve;
+
+/// Runs `source` with the interpreter, returning its failure as a diagnostic.
+fn runtime_failure(source: &str, limits: Limits) -> Diagnostic {
+    let program = parser::parse(source).unwrap();
+    let resolution = resolve::resolve(&program);
+    let error = interpreter::run_limited(&program, &resolution, &mut command::Local::default(), limits).unwrap_err();
+    Diagnostic::from(&error)
+}
+
+#[test]
+fn recursive_calls_get_one_label_per_place() {
+    let source = "func integer ping(integer n) {
+    return pong(n);
+}
+
+func integer pong(integer n) {
+    return ping(n);
+}
+
+func integer main() {
+    return ping(0);
+}
+";
+    let diagnostic = runtime_failure(source, Limits { depth: Some(30), ..Limits::none() });
+    let labels: Vec<&str> = diagnostic.labels.iter().map(|label| label.message.as_str()).collect();
+    assert_eq!(labels, vec!["", "called from here (15 times)", "called from here (14 times)", "called from here"]);
+
+    let rendered = diagnostic.render(&Source { name: "ping.sh", text: source }, false);
+    assert_eq!(
+        rendered,
+        "error[E0800]: Call depth limit of 30 exceeded
+  --> ping.sh:6:12
+   |
+ 2 |     return pong(n);
+   |            ------- called from here (15 times)
+...
+ 6 |     return ping(n);
+   |            ^^^^^^^
+   |            ------- called from here (14 times)
+...
+10 |     return ping(0);
+   |            ------- called from here
+   |
+"
+    );
+}
+
+/// Files of their own, by file id.
+struct Files(Vec<(&'static str, &'static str)>);
+
+impl Sources for Files {
+    fn name(&self, file: FileId) -> String {
+        self.0[file].0.to_string()
+    }
+
+    fn text(&self, file: FileId) -> Option<&str> {
+        Some(self.0[file].1)
+    }
+}
+
+const SOURCE: &str = "integer total = 1;
+string total = \"a\";
+integer sum = total + 1;
+";
+
+#[test]
+fn excerpts_mark_primary_labels_and_secondary_ones() {
+    let diagnostic = Diagnostic::error("E0100", "total is already declared in this scope")
+        .with_label(Span::new(2, 8, 2, 13), "declared again here")
+        .with_secondary(Span::new(1, 9, 1, 14), "first declared here")
+        .with_note("every name is declared once per scope")
+        .with_help("rename one of them");
+
+    assert_eq!(
+        diagnostic.render(&Source { name: "total.sh", text: SOURCE }, false),
+        "error[E0100]: total is already declared in this scope
+ --> total.sh:2:8
+  |
+1 | integer total = 1;
+  |         ----- first declared here
+2 | string total = \"a\";
+  |        ^^^^^ declared again here
+  |
+  = note: every name is declared once per scope
+  = help: rename one of them
+"
+    );
+}
+
+#[test]
+fn labels_on_distant_lines_and_other_files_are_set_apart() {
+    let diagnostic = Diagnostic::warning("W0100", "total shadows a declaration")
+        .with_label(Span::new(3, 15, 3, 20), "")
+        .with_secondary(Span::new(1, 9, 1, 14), "")
+        .with_secondary(Span::new(1, 8, 1, 12).in_file(1), "imported here");
+    let files = Files(vec![("main.sh", SOURCE), ("lib.sh", "pub func integer f() { return 1; }\n")]);
+
+    assert_eq!(
+        diagnostic.render(&files, false),
+        "warning[W0100]: total shadows a declaration
+ --> main.sh:3:15
+  |
+1 | integer total = 1;
+  |         -----
+...
+3 | integer sum = total + 1;
+  |               ^^^^^
+ ::: lib.sh:1:8
+1 | pub func integer f() { return 1; }
+  |        ---- imported here
+  |
+"
+    );
+}
+
+#[test]
+fn labels_of_earlier_files_come_after_the_primary_one() {
+    let diagnostic = Diagnostic::error("E0100", "Ambiguous import of f")
+        .with_label(Span::new(1, 18, 1, 19).in_file(1), "")
+        .with_secondary(Span::new(1, 18, 1, 19), "declared here");
+    let files = Files(vec![("a.sh", "pub func integer f() { return 1; }\n"), ("b.sh", "pub func integer f() { return 2; }\n")]);
+
+    assert_eq!(
+        diagnostic.render(&files, false),
+        "error[E0100]: Ambiguous import of f
+ --> b.sh:1:18
+  |
+1 | pub func integer f() { return 2; }
+  |                  ^
+ ::: a.sh:1:18
+1 | pub func integer f() { return 1; }
+  |                  - declared here
+  |
+"
+    );
+}
+
+#[test]
+fn colors_are_only_added_when_asked_for() {
+    let diagnostic = Diagnostic::error("E0200", "mismatch").with_label(Span::new(1, 1, 1, 8), "");
+    let source = Source { name: "a.sh", text: SOURCE };
+
+    assert!(!diagnostic.render(&source, false).contains('\x1b'));
+    let colored = diagnostic.render(&source, true);
+    assert!(colored.starts_with("\x1b[1;31merror[E0200]\x1b[0m"));
+    assert!(colored.contains("\x1b[1;31m^^^^^^^\x1b[0m"));
+}
+
+#[test]
+fn json_is_one_object_on_a_single_line() {
+    let diagnostic = Diagnostic::error("E0200", "Variable x expects \"string\"")
+        .with_label(Span::new(2, 8, 2, 13), "")
+        .with_secondary(Span::new(1, 1, 1, 8).in_file(1), "from here")
+        .with_note("a note");
+    let files = Files(vec![("main.sh", SOURCE), ("lib.sh", "")]);
+
+    assert_eq!(
+        diagnostic.to_json(&files),
+        concat!(
+            r#"{"severity":"error","code":"E0200","message":"Variable x expects \"string\"","labels":["#,
+            r#"{"file":"main.sh","line":2,"column":8,"end_line":2,"end_column":13,"message":"","primary":true},"#,
+            r#"{"file":"lib.sh","line":1,"column":1,"end_line":1,"end_column":8,"message":"from here","primary":false}"#,
+            r#"],"notes":["a note"],"help":[]}"#
+        )
+    );
+}
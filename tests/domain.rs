@@ -0,0 +1,144 @@
+use shanty::ast::{DomainKind, Type};
+use shanty::checker;
+use shanty::constant;
+use shanty::domain::{self, FieldSchema, Schemas};
+use shanty::parser;
+use shanty::resolve;
+
+/// Schema errors of `source` against `schemas`, with their line and column.
+fn errors_with(source: &str, schemas: &Schemas) -> Vec<String> {
+    let program = parser::parse(source).unwrap();
+    let types = checker::infer(&program);
+    let constants = constant::fold(&program, &resolve::resolve(&program));
+    domain::validate(&program, schemas, &types, &constants).iter().map(|error| format!("{}:{} {}", error.span.line, error.span.column, error.message)).collect()
+}
+
+fn errors(source: &str) -> Vec<String> {
+    errors_with(source, &Schemas::builtin())
+}
+
+#[test]
+fn declarations_setting_their_fields_are_valid() {
+    let source = "repo backend {
+    url = \"git@example.com:backend\";
+    branch = \"main\";
+}
+manifest app {
+    repo = backend;
+    path = \"k8s/app.yaml\";
+    replicas = 2 * 3;
+}
+deployment production {
+    manifest = app;
+    repo = backend;
+    environment = \"prod\";
+}
+";
+    assert_eq!(errors(source), Vec::<String>::new());
+}
+
+#[test]
+fn required_fields_must_be_set() {
+    let source = "repo backend {\n    branch = \"main\";\n}\nmanifest app {\n}\n";
+    assert_eq!(errors(source), vec!["1:6 Missing fields url in repo backend", "4:10 Missing fields repo, path in manifest app"]);
+}
+
+#[test]
+fn unknown_and_repeated_fields_are_errors() {
+    let source = "repo backend {
+    url = \"a\";
+    url = \"b\";
+    brnch = \"main\";
+    color = \"red\";
+}
+";
+    assert_eq!(
+        errors(source),
+        vec!["3:5 Field url is set more than once", "4:5 repo has no field brnch, did you mean branch?", "5:5 repo has no field color"]
+    );
+
+    // A misspelled required field is suggested first
+    let source = "user me {\n    nme = \"me\";\n}\n";
+    assert_eq!(errors(source), vec!["2:5 user has no field nme, did you mean name?", "1:6 Missing fields name in user me"]);
+}
+
+#[test]
+fn field_values_must_have_the_field_type() {
+    let source = "repo backend {\n    url = 1;\n}\n";
+    assert_eq!(errors(source), vec!["2:11 Field url expects string but got integer"]);
+}
+
+#[test]
+fn enum_fields_only_take_their_values() {
+    let source = "repo backend {
+    url = \"a\";
+}
+manifest app {
+    repo = backend;
+    path = \"app.yaml\";
+}
+deployment production {
+    manifest = app;
+    repo = backend;
+    environment = \"prd\";
+}
+deployment test {
+    manifest = app;
+    repo = backend;
+    environment = \"qa\";
+}
+";
+    assert_eq!(
+        errors(source),
+        vec![
+            "11:19 Field environment must be one of dev, staging, prod but got \"prd\", did you mean \"prod\"?",
+            "16:19 Field environment must be one of dev, staging, prod but got \"qa\"",
+        ]
+    );
+}
+
+#[test]
+fn range_fields_only_take_values_within_their_bounds() {
+    let source = "repo backend {
+    url = \"a\";
+}
+manifest small {
+    repo = backend;
+    path = \"a.yaml\";
+    replicas = 1 - 1;
+}
+manifest large {
+    repo = backend;
+    path = \"b.yaml\";
+    replicas = 100;
+}
+manifest huge {
+    repo = backend;
+    path = \"c.yaml\";
+    replicas = 101;
+}
+";
+    assert_eq!(
+        errors(source),
+        vec!["7:16 Field replicas must be between 1 and 100 but got 0", "17:16 Field replicas must be between 1 and 100 but got 101"]
+    );
+}
+
+#[test]
+fn embedders_can_add_and_remove_fields() {
+    let mut schemas = Schemas::builtin();
+    schemas
+        .add_field(DomainKind::Repo, FieldSchema::required("team", Type::String).one_of(&["web", "infra"]))
+        .add_field(DomainKind::Repo, FieldSchema::optional("url", Type::String))
+        .remove_field(DomainKind::Repo, "branch");
+
+    assert_eq!(schemas.fields(DomainKind::Repo).len(), 2);
+    assert_eq!(schemas.field(DomainKind::Repo, "url"), Some(&FieldSchema::optional("url", Type::String)));
+    assert_eq!(schemas.field(DomainKind::Repo, "branch"), None);
+
+    assert_eq!(errors_with("repo a {\n}\n", &schemas), vec!["1:6 Missing fields team in repo a"]);
+    assert_eq!(
+        errors_with("repo a {\n    team = \"ops\";\n    branch = \"main\";\n}\n", &schemas),
+        vec!["2:12 Field team must be one of web, infra but got \"ops\"", "3:5 repo has no field branch"]
+    );
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::PathBuf;
+
+use shanty::editor::{Editor, Input};
+
+/// Edits a line with `keys`, returning what was read and what was drawn.
+fn edit(editor: &mut Editor, keys: &str) -> (Input, String) {
+    let mut screen = vec![];
+    let input = editor.edit("> ", &mut keys.as_bytes(), &mut screen).unwrap();
+    (input, String::from_utf8(screen).unwrap())
+}
+
+fn line(text: &str) -> Input {
+    Input::Line(text.to_string())
+}
+
+fn history_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("shanty-history-{}-{}", std::process::id(), name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn keys_edit_the_line() {
+    let mut editor = Editor::new(None);
+
+    assert_eq!(edit(&mut editor, "abc\r").0, line("abc"));
+    // Left arrow, Ctrl-B and Home move back before inserting
+    assert_eq!(edit(&mut editor, "ac\x1b[Db\r").0, line("abc"));
+    assert_eq!(edit(&mut editor, "bc\x01a\x05d\r").0, line("abcd"));
+    assert_eq!(edit(&mut editor, "abd\x7fc\x1b[H\x1b[3~\r").0, line("bc"));
+    // Ctrl-W deletes a word, Ctrl-U the start of the line, Ctrl-K its end
+    assert_eq!(edit(&mut editor, "one two \x17three\r").0, line("one three"));
+    assert_eq!(edit(&mut editor, "one two\x02\x02\x02\x15\r").0, line("two"));
+    assert_eq!(edit(&mut editor, "one two\x01\x06\x06\x06\x0b\r").0, line("one"));
+    assert_eq!(edit(&mut editor, "é\x1b[Dà\r").0, line("àé"));
+}
+
+#[test]
+fn control_keys_end_the_line() {
+    let mut editor = Editor::new(None);
+
+    let (input, screen) = edit(&mut editor, "half\x03");
+    assert_eq!((input, screen.ends_with("^C\r\n")), (Input::Interrupted, true));
+    assert_eq!(edit(&mut editor, "\x04").0, Input::Eof);
+    // Ctrl-D deletes under the cursor on a line that isn't empty
+    assert_eq!(edit(&mut editor, "ab\x01\x04\r").0, line("b"));
+    assert_eq!(edit(&mut editor, "unfinished").0, Input::Eof);
+}
+
+#[test]
+fn the_line_is_redrawn_after_each_key() {
+    let (input, screen) = edit(&mut Editor::new(None), "ab\x1b[D\r");
+    assert_eq!(input, line("ab"));
+    assert_eq!(screen, "\r> \x1b[K\r> a\x1b[K\r> ab\x1b[K\r> ab\x1b[K\x1b[1D\r\n");
+}
+
+#[test]
+fn arrows_browse_the_history_and_keep_the_draft() {
+    let mut editor = Editor::new(None);
+    editor.add_history("first");
+    editor.add_history("second");
+
+    assert_eq!(edit(&mut editor, "\x1b[A\r").0, line("second"));
+    assert_eq!(edit(&mut editor, "\x1b[A\x1b[A\x1b[A\r").0, line("first"));
+    assert_eq!(edit(&mut editor, "draft\x10\x10\x0e\x0e\r").0, line("draft"));
+    assert_eq!(edit(&mut editor, "\x1b[A!\r").0, line("second!"));
+}
+
+#[test]
+fn history_is_saved_without_blanks_or_repeats() {
+    let path = history_file("saved");
+    let mut editor = Editor::new(Some(path.clone()));
+    for entry in ["integer x = 1;", "", "  ", "x", "x", "integer x = 1;"] {
+        editor.add_history(entry);
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), "integer x = 1;\nx\ninteger x = 1;\n");
+
+    let mut editor = Editor::new(Some(path.clone()));
+    assert_eq!(edit(&mut editor, "\x1b[A\x1b[A\r").0, line("x"));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn history_file_is_trimmed_once_twice_too_long() {
+    let path = history_file("trimmed");
+    let mut editor = Editor::new(Some(path.clone()));
+    for i in 0..2001 {
+        editor.add_history(&i.to_string());
+    }
+
+    let saved = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = saved.lines().collect();
+    assert_eq!((lines.len(), lines[0], lines[999]), (1000, "1001", "2000"));
+    fs::remove_file(path).unwrap();
+}
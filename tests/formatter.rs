@@ -0,0 +1,106 @@
+use shanty::formatter::format_source;
+
+fn fmt(source: &str) -> String {
+    format_source(source).unwrap()
+}
+
+/// Sources covering every kind of item, statement and comment placement.
+const SOURCES: [&str; 6] = [
+    "import \"lib.sh\";\npub complex Point { x: integer; y: integer; }\nfunc integer main() { integer x = 1; while (x < 10) { x++; } return x; }\n",
+    "// leading\n\n\n// second\nfunc integer main() { // why\n    integer x = 1; // one\n    if (x == 1) {\n        x = 2;\n    } // note\n    else if (x == 2) { x = 3; }\n    else {}\n    // before return\n    return x;\n} // after\n// last\n",
+    "command deploy(string env) { // settings\n    run = \"deploy \" + env; // the program\n    timeout = 60;\n}\nrepo backend { url = \"git@example.com:backend.git\"; }\n",
+    "func boolean long(integer alpha, integer beta) {\n    return alpha > 100000000 && beta < 2000000000 && alpha + beta != 300000000 && alpha * beta != 4000000000000 || false;\n}\n",
+    "func integer one() { return 1; } // trailing\nfunc void nothing() {} // empty\nvar values = [1, 2, 3]; var counts = [\"a\": 1];\n",
+    "string greeting = \"héllo wörld ✓\"; // café\nrune r = 'é';\nstring naïve = greeting;\n",
+];
+
+#[test]
+fn formatting_is_idempotent() {
+    for source in SOURCES {
+        let formatted = fmt(source);
+        assert_eq!(fmt(&formatted), formatted, "{}", formatted);
+    }
+}
+
+#[test]
+fn comments_are_all_kept() {
+    for source in SOURCES {
+        let count = |text: &str| text.matches("//").count();
+        assert_eq!(count(&fmt(source)), count(source), "{}", source);
+    }
+}
+
+#[test]
+fn trailing_comments_stay_after_the_token_they_follow() {
+    let source = "func integer main() { // why
+    integer x = 1;
+    if (x == 1) {
+        x = 2;
+    } // note
+    else {
+        x = 3;
+    }
+    return x;
+}
+
+func integer one() { return 1; } // trailing
+";
+    assert_eq!(
+        fmt(source),
+        "func integer main() { // why
+    integer x = 1;
+    if (x == 1) {
+        x = 2;
+    } // note
+    else {
+        x = 3;
+    }
+    return x;
+}
+
+func integer one() {
+    return 1;
+} // trailing
+"
+    );
+}
+
+#[test]
+fn comments_on_their_own_lines_stay_in_front() {
+    let source = "func integer main() {\n    // first\n    integer x = 1;\n\n    // second\n    return x;\n    // last\n}\n";
+    assert_eq!(fmt(source), source);
+    assert_eq!(fmt("command c() { // settings\n    run = \"true\"; // program\n}\n"), "command c() { // settings\n    run = \"true\"; // program\n}\n");
+}
+
+#[test]
+fn long_binary_conditions_wrap_before_each_operator() {
+    let source = "func boolean long(integer alpha, integer beta) {
+    if (alpha > 100000000 && beta < 2000000000 && alpha + beta != 300000000 && alpha * beta != 4000000000000) {
+        return true;
+    }
+    return false;
+}
+";
+    assert_eq!(
+        fmt(source),
+        "func boolean long(integer alpha, integer beta) {
+    if (alpha > 100000000
+        && beta < 2000000000
+        && alpha + beta != 300000000
+        && alpha * beta != 4000000000000) {
+        return true;
+    }
+    return false;
+}
+"
+    );
+    // Short ones stay on one line
+    assert_eq!(fmt("var ok = 1 < 2 && 3 < 4;\n"), "var ok = 1 < 2 && 3 < 4;\n");
+}
+
+#[test]
+fn non_ascii_strings_runes_and_names_are_kept() {
+    assert_eq!(fmt("x   =   \"é\";"), "x = \"é\";\n");
+    assert_eq!(fmt("rune r='é';"), "rune r = 'é';\n");
+    assert_eq!(fmt("string  naïve = \"日本\" + \"✓\";  // ünïcode"), "string naïve = \"日本\" + \"✓\"; // ünïcode\n");
+}
@@ -0,0 +1,82 @@
+use shanty::graph::{self, DependencyGraph};
+use shanty::parser;
+use shanty::resolve;
+
+fn build(source: &str) -> DependencyGraph {
+    let program = parser::parse(source).unwrap();
+    graph::build(&program, &resolve::resolve(&program))
+}
+
+/// Names of the declarations in topological order.
+fn order(graph: &DependencyGraph) -> Vec<&str> {
+    graph.topological_order().unwrap().iter().map(|&id| graph.nodes[id].name.as_str()).collect()
+}
+
+const DEPLOYMENT: &str = "deployment production {
+    manifest = app;
+    repo = backend;
+    environment = \"prod\";
+}
+manifest app {
+    repo = backend;
+    path = \"app.yaml\";
+}
+repo backend {
+    url = \"a\";
+}
+";
+
+#[test]
+fn declarations_come_after_what_they_depend_on() {
+    let graph = build(DEPLOYMENT);
+    assert_eq!(graph.dependencies, vec![vec![1, 2], vec![2], vec![]]);
+    assert!(graph.cycles().is_empty());
+    assert_eq!(order(&graph), vec!["backend", "app", "production"]);
+}
+
+#[test]
+fn independent_declarations_keep_declaration_order() {
+    let graph = build("repo b {\n    url = \"b\";\n}\nrepo a {\n    url = \"a\";\n}\nuser c {\n    name = \"c\";\n}\n");
+    assert_eq!(order(&graph), vec!["b", "a", "c"]);
+}
+
+#[test]
+fn cycles_are_reported_at_the_reference_closing_them() {
+    let source = "manifest a {
+    repo = b;
+}
+manifest b {
+    repo = c;
+}
+manifest c {
+    repo = a;
+}
+manifest d {
+    repo = d;
+}
+";
+    let graph = build(source);
+    let cycles = graph.topological_order().unwrap_err();
+    let reported: Vec<String> = cycles.iter().map(|cycle| format!("{}:{} {}", cycle.span.line, cycle.span.column, cycle.message)).collect();
+    assert_eq!(
+        reported,
+        vec!["8:12 Dependency cycle: manifest a -> manifest b -> manifest c -> manifest a", "11:12 Dependency cycle: manifest d -> manifest d"]
+    );
+    assert_eq!(cycles[0].path, vec![0, 1, 2, 0]);
+}
+
+#[test]
+fn dot_output_has_a_node_per_declaration_and_an_edge_per_dependency() {
+    assert_eq!(
+        build(DEPLOYMENT).to_dot(),
+        "digraph dependencies {
+    n0 [label=\"deployment production\"];
+    n1 [label=\"manifest app\"];
+    n2 [label=\"repo backend\"];
+    n0 -> n1;
+    n0 -> n2;
+    n1 -> n2;
+}
+"
+    );
+}
@@ -0,0 +1,112 @@
+use shanty::init;
+use shanty::parser;
+use shanty::resolve;
+
+/// Reads of unassigned variables in `source`, with their line and column.
+fn errors(source: &str) -> Vec<String> {
+    let program = parser::parse(source).unwrap();
+    init::check(&program, &resolve::resolve(&program)).iter().map(|error| format!("{}:{} {}", error.span.line, error.span.column, error.message)).collect()
+}
+
+#[test]
+fn reads_before_any_assignment_are_reported_once() {
+    let source = "integer a;\ninteger b = a + a;\na = 1;\ninteger c = a;\n";
+    assert_eq!(errors(source), vec!["2:13 a might not be assigned when it's read here"]);
+}
+
+#[test]
+fn both_branches_of_an_if_must_assign() {
+    let source = "func integer f(boolean b) {
+    integer x;
+    if (b) {
+        x = 1;
+    } else {
+        x = 2;
+    }
+    return x;
+}
+func integer g(boolean b) {
+    integer x;
+    if (b) {
+        x = 1;
+    }
+    return x;
+}
+func integer h(boolean b) {
+    integer x;
+    if (b) {
+        x = 1;
+    } else if (!b) {
+        x = 2;
+    }
+    return x;
+}
+";
+    assert_eq!(errors(source), vec!["15:12 x might not be assigned when it's read here", "24:12 x might not be assigned when it's read here"]);
+}
+
+#[test]
+fn a_branch_that_returns_assigns_everything() {
+    let source = "func integer f(boolean b) {
+    integer x;
+    if (b) {
+        x = 1;
+    } else {
+        return 0;
+    }
+    return x;
+}
+";
+    assert_eq!(errors(source), Vec::<String>::new());
+}
+
+#[test]
+fn loop_bodies_might_not_run() {
+    let source = "func integer f(integer n) {
+    integer x;
+    while (n > 0) {
+        x = n;
+        n = n - 1;
+    }
+    return x;
+}
+func integer g() {
+    integer x;
+    while (true) {
+        x = 1;
+        return x;
+    }
+    return x;
+}
+";
+    // Nothing runs after `while (true)` unless its body breaks out with a return
+    assert_eq!(errors(source), vec!["7:12 x might not be assigned when it's read here"]);
+}
+
+#[test]
+fn right_operands_of_boolean_operators_might_not_run() {
+    let source = "func boolean f(boolean b) {
+    boolean x;
+    boolean y = b && (x = true);
+    return x;
+}
+";
+    assert_eq!(errors(source), vec!["4:12 x might not be assigned when it's read here"]);
+}
+
+#[test]
+fn functions_read_top_level_variables_as_the_statements_leave_them() {
+    let source = "integer g;
+integer h;
+integer i;
+h = 1;
+func integer main() {
+    i = 2;
+    return g + h + i + g;
+}
+func integer other() {
+    return g;
+}
+";
+    assert_eq!(errors(source), vec!["7:12 g might not be assigned when it's read here", "10:12 g might not be assigned when it's read here"]);
+}
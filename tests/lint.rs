@@ -0,0 +1,134 @@
+use shanty::diagnostic::Severity;
+use shanty::lint::{self, Config};
+use shanty::parser;
+use shanty::resolve;
+
+fn findings_with(source: &str, config: &Config) -> Vec<lint::Finding> {
+    let program = parser::parse(source).unwrap();
+    lint::lint(source, &program, &resolve::resolve(&program), config)
+}
+
+/// Rule, line and column of each finding of `source` with the default severities.
+fn findings(source: &str) -> Vec<String> {
+    findings_with(source, &Config::default()).iter().map(|f| format!("{} {}:{} {}", f.rule, f.span.line, f.span.column, f.message)).collect()
+}
+
+/// Findings of a single rule, leaving out the unused variables most sources declare.
+fn findings_of(rule: &str, source: &str) -> Vec<String> {
+    findings(source).into_iter().filter(|finding| finding.starts_with(rule)).collect()
+}
+
+/// `source` with the fixes of its findings applied.
+fn fix(source: &str) -> String {
+    lint::apply_fixes(source, &findings_with(source, &Config::default()))
+}
+
+#[test]
+fn unused_variables_and_functions() {
+    let source = "func integer helper() { return 1; }
+func integer used() { return 2; }
+pub func integer exported() { return 3; }
+func integer main() {
+    integer unused = 4;
+    return used();
+}
+";
+    assert_eq!(findings(source), vec!["unused-function 1:14 Function helper is never called", "unused-variable 5:13 Variable unused is never used"]);
+}
+
+#[test]
+fn empty_while_loops() {
+    let source = "boolean b = false;\nwhile (b) {\n}\nwhile (b) {\n    b = false;\n}\n";
+    assert_eq!(findings(source), vec!["empty-while 2:1 while loop has an empty body"]);
+}
+
+#[test]
+fn comparisons_with_boolean_literals() {
+    let source = "boolean b = false;\nboolean c = b == true;\nboolean d = false != b;\n";
+    assert_eq!(
+        findings_of("boolean-comparison", source),
+        vec!["boolean-comparison 2:13 Comparing with true is redundant", "boolean-comparison 3:13 Comparing with false is redundant"]
+    );
+}
+
+#[test]
+fn hard_coded_secrets() {
+    let source = "string api_token = \"hunter2\";
+string greeting = \"ghp_0123456789\";
+string password = \"\";
+";
+    assert_eq!(
+        findings_of("hardcoded-secret", source),
+        vec![
+            "hardcoded-secret 1:20 api_token is set to a hard-coded secret, load it as a secret instead",
+            "hardcoded-secret 2:19 String looks like a hard-coded token, load it as a secret instead",
+        ]
+    );
+}
+
+#[test]
+fn commands_deleting_the_root_directory() {
+    let source = "string cleanup = \"rm -rf /\";\nstring other = \"rm -rf /tmp/build\";\n";
+    assert_eq!(findings_of("dangerous-command", source), vec!["dangerous-command 1:18 Command deletes the root directory"]);
+
+    for command in ["rm -Rf /", "rm -r -f /", "rm --recursive --force /", "rm -rf --no-preserve-root /", "sudo rm -fr /*"] {
+        let source = format!("string cleanup = \"{}\";\n", command);
+        assert_eq!(findings_of("dangerous-command", &source).len(), 1, "{}", command);
+    }
+    for command in ["rm -r /", "rm -f /", "rm -rf ./", "rm -rf -- /tmp", "rm -rf build; ls /", "echo rm -rf"] {
+        let source = format!("string cleanup = \"{}\";\n", command);
+        assert_eq!(findings_of("dangerous-command", &source), Vec::<String>::new(), "{}", command);
+    }
+}
+
+#[test]
+fn config_changes_severities_and_turns_rules_off() {
+    let config = Config::parse("# project settings\nunused-variable = error\nempty-while = off  # loops wait on purpose\n\n").unwrap();
+    let source = "boolean b = false;\nwhile (b) {\n}\ninteger x = 1;\n";
+    let found: Vec<(&str, Severity)> = findings_with(source, &config).iter().map(|f| (f.rule, f.severity)).collect();
+    assert_eq!(found, vec![("unused-variable", Severity::Error)]);
+
+    assert_eq!(config.severity(lint::rule("unused-variable").unwrap()), Some(Severity::Error));
+    assert_eq!(config.severity(lint::rule("empty-while").unwrap()), None);
+    assert_eq!(config.severity(lint::rule("hardcoded-secret").unwrap()), Some(Severity::Error));
+}
+
+#[test]
+fn config_errors_name_their_line() {
+    assert_eq!(Config::parse("unused-variable = error\nunused-variable\n").unwrap_err(), "Expected rule-id = error|warning|off at line 2");
+    assert_eq!(Config::parse("no-such-rule = off\n").unwrap_err(), "Unknown lint rule no-such-rule at line 1");
+    assert_eq!(Config::parse("\nempty-while = loud\n").unwrap_err(), "Expected error, warning or off for empty-while at line 2");
+}
+
+#[test]
+fn disable_comments_cover_their_line_and_the_next() {
+    let source = "// lint:disable unused-variable, hardcoded-secret
+string password = \"hunter2\";
+integer a = 1; // lint:disable unused-variable
+integer b = 2;
+integer c = 3; // lint:disable empty-while
+";
+    assert_eq!(findings(source), vec!["unused-variable 5:9 Variable c is never used"]);
+}
+
+#[test]
+fn fixes_rewrite_boolean_comparisons() {
+    assert_eq!(fix("boolean b = false;\nboolean c = b == true;\n"), "boolean b = false;\nboolean c = b;\n");
+    assert_eq!(fix("boolean b = false;\nboolean c = b == false;\n"), "boolean b = false;\nboolean c = !b;\n");
+    assert_eq!(fix("boolean b = false;\nboolean c = true != b;\n"), "boolean b = false;\nboolean c = !b;\n");
+}
+
+#[test]
+fn negated_fixes_keep_operators_grouped() {
+    assert_eq!(fix("integer n = 1;\nboolean c = n < 2 == false;\n"), "integer n = 1;\nboolean c = !(n < 2);\n");
+    assert_eq!(fix("integer n = 1;\nboolean c = (n < 2) == false;\n"), "integer n = 1;\nboolean c = !(n < 2);\n");
+    assert_eq!(fix("integer n = 1;\nboolean c = (n) < (2) == false;\n"), "integer n = 1;\nboolean c = !((n) < (2));\n");
+}
+
+#[test]
+fn overlapping_fixes_keep_the_first() {
+    // The inner comparison is fixed on a later run
+    let source = "boolean b = false;\nboolean c = (b == true) == false;\n";
+    assert_eq!(fix(source), "boolean b = false;\nboolean c = !(b == true);\n");
+    assert_eq!(fix(&fix(source)), "boolean b = false;\nboolean c = !b;\n");
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use shanty::checker;
+use shanty::compiler;
+use shanty::interpreter;
+use shanty::module::{self, FileResolver, FsResolver, ModuleError, Modules};
+use shanty::resolve;
+use shanty::value::Value;
+use shanty::vm;
+
+/// Files held in memory, resolved like the file system does.
+struct Files(HashMap<PathBuf, &'static str>);
+
+impl FileResolver for Files {
+    fn resolve(&self, importer: &Path, import_path: &str) -> PathBuf {
+        FsResolver.resolve(importer, import_path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.0.get(path).map(|source| source.to_string()).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found"))
+    }
+}
+
+fn load(files: &[(&str, &'static str)]) -> Result<Modules, (Modules, Vec<ModuleError>)> {
+    let files = Files(files.iter().map(|&(path, source)| (PathBuf::from(path), source)).collect());
+    module::load_with(Path::new("app/main.sh"), &files)
+}
+
+fn paths(modules: &Modules, ids: &[usize]) -> Vec<String> {
+    ids.iter().map(|&id| modules.path(id).display().to_string()).collect()
+}
+
+fn errors(files: &[(&str, &'static str)]) -> Vec<String> {
+    load(files).unwrap_err().1.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn imports_resolve_relative_to_the_importing_file() {
+    let modules = load(&[
+        ("app/main.sh", "import \"lib/strings.sh\";\nimport \"../shared/log.sh\";\n"),
+        ("app/lib/strings.sh", "import \"../../shared/log.sh\";\nimport \"./util.sh\";\n"),
+        ("app/lib/util.sh", ""),
+        ("shared/log.sh", ""),
+    ])
+    .unwrap();
+
+    assert_eq!(paths(&modules, &[0, 1, 2, 3]), ["app/main.sh", "app/lib/strings.sh", "shared/log.sh", "app/lib/util.sh"]);
+    // Files come after their imports, a file reached twice is loaded once
+    assert_eq!(paths(&modules, &modules.order), ["shared/log.sh", "app/lib/util.sh", "app/lib/strings.sh", "app/main.sh"]);
+    assert_eq!(modules.files[0].imports, vec![1, 2]);
+    assert_eq!(modules.files[1].imports, vec![2, 3]);
+}
+
+#[test]
+fn missing_and_unparsable_files_are_reported_at_their_import() {
+    let failed = load(&[("app/main.sh", "import \"missing.sh\";\nimport \"broken.sh\";\n"), ("app/broken.sh", "func (")]).unwrap_err();
+    let (modules, errors) = failed;
+
+    assert_eq!(errors[0].message, "Can't read app/missing.sh: not found");
+    assert_eq!(errors[0].span.map(|span| (span.file, span.line, span.column)), Some((0, 1, 8)));
+    assert!(errors[1].syntax);
+    assert_eq!(errors[1].span.map(|span| (span.file, span.line)), Some((1, 1)));
+    assert_eq!(modules.path(1), Path::new("app/broken.sh"));
+
+    let root = module::load_with(Path::new("nowhere.sh"), &Files(HashMap::new())).unwrap_err().1;
+    assert_eq!((root[0].message.as_str(), root[0].span), ("Can't read nowhere.sh: not found", None));
+}
+
+#[test]
+fn import_cycles_are_reported_with_their_path() {
+    let cycle = errors(&[("app/main.sh", "import \"a.sh\";\n"), ("app/a.sh", "import \"b.sh\";\n"), ("app/b.sh", "import \"a.sh\";\n")]);
+    assert_eq!(cycle, ["Import cycle: app/a.sh -> app/b.sh -> app/a.sh at 1:8"]);
+
+    let itself = errors(&[("app/main.sh", "import \"main.sh\";\n")]);
+    assert_eq!(itself, ["Import cycle: app/main.sh -> app/main.sh at 1:8"]);
+}
+
+#[test]
+fn only_pub_declarations_are_visible_to_importers() {
+    let files = [
+        ("app/main.sh", "import \"lib.sh\";\n\nfunc integer main() {\n    return twice(limit()) + hidden();\n}\n"),
+        ("app/lib.sh", "pub func integer limit() {\n    return 20;\n}\n\npub func integer twice(integer n) {\n    return 2 * n;\n}\n\nfunc integer hidden() {\n    return 1;\n}\n"),
+    ];
+    let modules = load(&files).unwrap();
+    let messages: Vec<String> = resolve::resolve_modules(&modules).errors.into_iter().map(|error| error.to_string()).collect();
+    assert_eq!(messages, ["hidden is not pub in the file that declares it at 4:29"]);
+
+    let files = [("app/main.sh", "import \"lib.sh\";\n\nfunc integer main() {\n    return twice(limit());\n}\n"), files[1]];
+    let modules = load(&files).unwrap();
+    let resolution = resolve::resolve_modules(&modules);
+    assert!(resolution.errors.is_empty(), "{:?}", resolution.errors);
+    assert_eq!(checker::check_modules(&modules), vec![]);
+    assert_eq!(interpreter::run_modules(&modules, &resolution), Ok(Value::Integer(40)));
+
+    let modules = load(&[("app/main.sh", "import \"lib.sh\";\n\nPoint point = Point {};\n"), ("app/lib.sh", "complex Point {}\n")]).unwrap();
+    let messages: Vec<String> = checker::check_modules(&modules).into_iter().map(|error| error.to_string()).collect();
+    assert_eq!(messages, ["type Point is not pub in the file that declares it at 3:7", "type Point is not pub in the file that declares it at 3:15"]);
+}
+
+#[test]
+fn declarations_of_a_file_shadow_imported_ones() {
+    let files = [
+        ("app/main.sh", "import \"lib.sh\";\n\nfunc string helper() {\n    return \"local\";\n}\n\nfunc string main() {\n    return helper() + \"!\";\n}\n"),
+        ("app/lib.sh", "pub func integer helper() {\n    return 1;\n}\n"),
+    ];
+    let modules = load(&files).unwrap();
+    let resolution = resolve::resolve_modules(&modules);
+    assert!(resolution.errors.is_empty(), "{:?}", resolution.errors);
+    assert_eq!(checker::check_modules(&modules), vec![]);
+    let expected = Value::String("local!".to_string());
+    assert_eq!(interpreter::run_modules(&modules, &resolution), Ok(expected.clone()));
+    assert_eq!(vm::run(&compiler::compile_modules(&modules, &resolution).unwrap()), Ok(expected));
+}
+
+#[test]
+fn names_declared_by_two_imported_files_are_ambiguous() {
+    let files = [
+        ("app/main.sh", "import \"a.sh\";\nimport \"b.sh\";\n\nfunc integer main() {\n    return h();\n}\n"),
+        ("app/a.sh", "pub func integer h() {\n    return 1;\n}\n"),
+        ("app/b.sh", "\npub func integer h() {\n    return 2;\n}\n"),
+    ];
+    let modules = load(&files).unwrap();
+    let errors = resolve::resolve_modules(&modules).errors;
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].message, "Ambiguous import of h");
+    assert_eq!((errors[0].span.file, errors[0].span.line, errors[0].span.column), (2, 2, 18));
+    assert_eq!(errors[0].previous.map(|span| (span.file, span.line, span.column)), Some((1, 1, 18)));
+}
@@ -0,0 +1,45 @@
+use shanty::ast::{DomainKind, ExprKind, Item, Type};
+use shanty::parser;
+
+#[test]
+fn complex_declarations_and_literals_parse() {
+    let program = parser::parse("complex Point {\n    x: integer;\n    label: string;\n}\n\ninteger p = Point { label: \"a\", x: 1 }.x;\n").unwrap();
+
+    let complex = match &program.items[0] {
+        Item::Complex(complex) => complex,
+        other => panic!("{:?}", other),
+    };
+    let fields: Vec<(&str, &Type)> = complex.fields.iter().map(|field| (field.name.as_str(), &field.ty)).collect();
+    assert_eq!(complex.name, "Point");
+    assert_eq!(fields, vec![("x", &Type::Integer), ("label", &Type::String)]);
+
+    let access = match &program.items[1] {
+        Item::Stmt(stmt) => format!("{:?}", stmt.kind),
+        other => panic!("{:?}", other),
+    };
+    assert!(access.contains("Field") && access.contains("ComplexLiteral"), "{}", access);
+    assert!(parser::parse("complex Point { x integer; }").is_err());
+    assert!(parser::parse("integer p = Point { x: 1, x };").is_err());
+}
+
+#[test]
+fn domain_blocks_parse_with_their_kind_and_fields() {
+    let program = parser::parse("deployment production {\n    replicas = 1 + 2;\n    repo = backend;\n}\n").unwrap();
+    let domain = match &program.items[0] {
+        Item::Domain(domain) => domain,
+        other => panic!("{:?}", other),
+    };
+    let fields: Vec<&str> = domain.fields.iter().map(|field| field.name.as_str()).collect();
+    assert_eq!((domain.kind, domain.name.as_str(), fields), (DomainKind::Deployment, "production", vec!["replicas", "repo"]));
+    assert!(matches!(domain.fields[0].value.kind, ExprKind::Binary { .. }));
+
+    assert!(parser::parse("repo backend { url: \"x\"; }").is_err());
+    assert!(parser::parse("repo { url = \"x\"; }").is_err());
+}
+
+#[test]
+fn a_quote_ending_the_input_is_an_unterminated_string() {
+    let error = parser::parse("string s = \"").unwrap_err();
+    assert_eq!(error.to_string(), "Unterminated string literal at 1:12");
+    assert_eq!(parser::parse("\"").unwrap_err().to_string(), "Unterminated string literal at 1:1");
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Runs `shanty repl` on `input`, returning its stdout and stderr. The history goes to a home directory of its own.
+fn repl(name: &str, input: &str) -> (String, String, PathBuf) {
+    let home = std::env::temp_dir().join(format!("shanty-repl-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&home).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_shanty"))
+        .arg("repl")
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap(), home)
+}
+
+#[test]
+fn inputs_run_once_on_top_of_the_earlier_ones() {
+    let input = "command greet() {
+    run = \"echo hello\";
+}
+var greeting = greet();
+integer count = 1;
+count = count + 1;
+count
+greeting.stdout
+";
+    let (stdout, stderr, home) = repl("once", input);
+    assert_eq!(stderr, "");
+    // The command ran for its own input only, not again with the later ones
+    assert_eq!(stdout, "[greet] hello\n2\n2\nhello\n\n");
+    fs::remove_dir_all(home).unwrap();
+}
+
+#[test]
+fn failed_inputs_keep_their_assignments_but_not_their_declarations() {
+    let input = "integer n = 1;
+func integer fail(integer d) {
+    return 1 / d;
+}
+n = 5; integer lost = fail(n - 5);
+n
+lost
+";
+    let (stdout, stderr, home) = repl("failed", input);
+    assert_eq!(stdout, "5\n");
+    assert!(stderr.contains("Division by zero"), "{}", stderr);
+    assert!(stderr.contains("Undefined name lost"), "{}", stderr);
+    fs::remove_dir_all(home).unwrap();
+}
+
+#[test]
+fn meta_commands() {
+    let home = std::env::temp_dir().join(format!("shanty-repl-{}-load.sh", std::process::id()));
+    fs::write(&home, "integer loaded = 40;\n").unwrap();
+    let input = format!(":tokens x + 1\n:type 1 < 2\n:load {}\nloaded + 2\n:type loaded\n:ast x;\n:help\n:nope\n:quit\n3\n", home.display());
+
+    let (stdout, stderr, dir) = repl("meta", &input);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[..7], ["1:1 identifier \"x\"", "1:3 + \"+\"", "1:5 integerLiteral \"1\"", "1:6 eof \"\"", "boolean", "42", "integer"]);
+    assert!(stdout.contains("Expr("), "{}", stdout);
+    assert!(stdout.contains(":load <file>"), "{}", stdout);
+    // Nothing is read after :quit
+    assert!(!stdout.ends_with("3\n"), "{}", stdout);
+    assert_eq!(stderr, "Unknown command :nope, :help lists them\n");
+    fs::remove_file(home).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn open_brackets_continue_the_input_and_lines_go_to_the_history() {
+    let input = "func integer twice(integer n) {\n    return 2 * n;\n}\ntwice(\n  21)\n\n";
+    let (stdout, _, home) = repl("history", input);
+    assert_eq!(stdout, "42\n");
+
+    let history = fs::read_to_string(home.join(".shanty_history")).unwrap();
+    assert_eq!(history, "func integer twice(integer n) {\n    return 2 * n;\n}\ntwice(\n  21)\n");
+    fs::remove_dir_all(home).unwrap();
+}
+
+#[test]
+fn a_lone_quote_is_reported_and_the_session_goes_on() {
+    let (stdout, stderr, home) = repl("quote", "\"\n1 + 1\n");
+    assert!(stderr.contains("Unterminated string literal"), "{}", stderr);
+    assert_eq!(stdout, "2\n");
+    fs::remove_dir_all(home).unwrap();
+}
+
+#[test]
+fn type_without_an_expression_is_refused() {
+    let (stdout, stderr, home) = repl("type", "integer count = 1;\ncount\n:type\n:type   \n");
+    assert_eq!(stdout, "1\n");
+    assert_eq!(stderr, ":type expects an expression\n:type expects an expression\n");
+    fs::remove_dir_all(home).unwrap();
+}
@@ -0,0 +1,115 @@
+use shanty::parser;
+use shanty::resolve::{self, Resolution, SymbolKind};
+use shanty::suggest;
+
+fn resolve(source: &str) -> Resolution {
+    resolve::resolve(&parser::parse(source).unwrap())
+}
+
+/// Errors and warnings of `source`, with their position.
+fn errors(source: &str) -> Vec<String> {
+    resolve(source).errors.iter().map(|error| format!("{}{}", if error.warning { "warning: " } else { "" }, error)).collect()
+}
+
+/// Line and column of the declaration the identifier at `line` and `column` refers to.
+fn definition(resolution: &Resolution, line: usize, column: usize) -> Option<(usize, usize)> {
+    let span = *resolution.uses.keys().find(|span| (span.line, span.column) == (line, column))?;
+    resolution.definition(span).map(|symbol| (symbol.span.line, symbol.span.column))
+}
+
+#[test]
+fn uses_refer_to_the_innermost_visible_declaration() {
+    let source = "integer x = 1;
+func integer f(integer n) {
+    integer y = n + x;
+    if (y > 0) {
+        integer z = y;
+        return z;
+    }
+    return g();
+}
+func integer g() {
+    return x;
+}
+";
+    let resolution = resolve(source);
+    assert_eq!(resolution.errors, vec![]);
+    assert_eq!(definition(&resolution, 3, 17), Some((2, 24)));
+    assert_eq!(definition(&resolution, 3, 21), Some((1, 9)));
+    assert_eq!(definition(&resolution, 6, 16), Some((5, 17)));
+    // Functions are visible before their declaration
+    assert_eq!(definition(&resolution, 8, 12), Some((10, 14)));
+
+    let kinds: Vec<(&str, SymbolKind)> = resolution.symbols.iter().map(|symbol| (symbol.name.as_str(), symbol.kind)).collect();
+    assert!(kinds.contains(&("f", SymbolKind::Function)) && kinds.contains(&("n", SymbolKind::Parameter)) && kinds.contains(&("z", SymbolKind::Variable)));
+    let x = resolution.symbols.iter().position(|symbol| symbol.name == "x").unwrap();
+    let references: Vec<(usize, usize)> = resolution.references(x).iter().map(|span| (span.line, span.column)).collect();
+    assert_eq!(references, [(3, 21), (11, 12)]);
+}
+
+#[test]
+fn names_are_gone_once_their_block_ends() {
+    let source = "func integer f() {
+    if (true) {
+        integer inner = 1;
+    }
+    return inner;
+}
+integer early = late;
+integer late = 1;
+";
+    assert_eq!(errors(source), ["Undefined name inner at 5:12", "Undefined name late at 7:17"]);
+}
+
+#[test]
+fn shadowing_warns_and_redeclaring_fails() {
+    let source = "integer x = 1;
+func integer f(integer n) {
+    integer n = 2;
+    if (true) {
+        integer x = 3;
+    }
+    return n;
+}
+integer x = 4;
+";
+    assert_eq!(
+        errors(source),
+        [
+            "n is already declared in this scope at 3:13",
+            "warning: x shadows a declaration from an enclosing scope at 5:17",
+            "x is already declared in this scope at 9:9",
+        ]
+    );
+    let resolution = resolve(source);
+    assert_eq!(resolution.errors[0].previous.map(|span| (span.line, span.column)), Some((2, 24)));
+}
+
+#[test]
+fn undefined_names_suggest_close_visible_ones() {
+    let source = "integer count = 1;
+func integer total(integer amount) {
+    return amout + cuont + totl() + lenn([]) + completely_different;
+}
+";
+    assert_eq!(
+        errors(source),
+        [
+            "Undefined name amout, did you mean amount? at 3:12",
+            "Undefined name cuont, did you mean count? at 3:20",
+            "Undefined name totl, did you mean total? at 3:28",
+            "Undefined name lenn, did you mean len? at 3:37",
+            "Undefined name completely_different at 3:48",
+        ]
+    );
+}
+
+#[test]
+fn suggestions_are_close_enough_to_be_typos() {
+    assert_eq!(suggest::edit_distance("kitten", "sitting"), 3);
+    assert_eq!(suggest::edit_distance("form", "from"), 1);
+    assert_eq!(suggest::closest("lenght", ["length", "len"]), Some("length"));
+    assert_eq!(suggest::closest("ab", ["xy"]), None);
+    // Ties go to the first name alphabetically
+    assert_eq!(suggest::closest("cat", ["bat", "rat", "cat"]), Some("bat"));
+}
@@ -0,0 +1,280 @@
+//! Conformance of runtime values to the rules written down in `shanty::value`: every operator on every pair of types,
+//! overflow, concatenation, comparisons, equality, conversions and display. The checker and constant folding must
+//! agree with the runtime on all of them.
+
+use shanty::ast::{BinaryOp, Type, UnaryOp};
+use shanty::checker;
+use shanty::constant::{self, Constant};
+use shanty::secret::Secret;
+use shanty::stdlib;
+use shanty::token;
+use shanty::value::Value;
+
+const BINARY: [&str; 14] = [
+    token::OR,
+    token::AND,
+    token::PIPE,
+    token::AMP,
+    token::EQ,
+    token::NEQ,
+    token::LT,
+    token::RT,
+    token::LT_EQ,
+    token::RT_EQ,
+    token::PLUS,
+    token::MINUS,
+    token::TIMES,
+    token::DIV,
+];
+
+fn binary_ops() -> Vec<BinaryOp> {
+    BINARY.iter().map(|token| BinaryOp::from_token(token).unwrap()).collect()
+}
+
+fn point(x: i64) -> Value {
+    Value::Complex { name: "point".to_string(), fields: vec![("x".to_string(), Value::Integer(x))] }
+}
+
+fn string(text: &str) -> Value {
+    Value::String(text.to_string())
+}
+
+/// A value of each type, with its type.
+fn samples() -> Vec<(Value, Type)> {
+    vec![
+        (Value::Integer(7), Type::Integer),
+        (Value::Rune('r'), Type::Rune),
+        (string("text"), Type::String),
+        (Value::Boolean(true), Type::Boolean),
+        (Value::Void, Type::Void),
+        (point(1), Type::Complex("point".to_string())),
+        (Value::Complex { name: "size".to_string(), fields: vec![] }, Type::Complex("size".to_string())),
+        (Value::Secret(Secret::new("hunter2".to_string())), Type::Secret),
+        (Value::List(vec![Value::Integer(1)]), Type::List(Box::new(Type::Integer))),
+        (Value::Map(vec![(Value::Integer(1), string("one"))]), Type::Map(Box::new(Type::Integer), Box::new(Type::String))),
+    ]
+}
+
+fn binary(left: Value, op: &str, right: Value) -> Result<Value, String> {
+    left.binary(BinaryOp::from_token(op).unwrap(), &right)
+}
+
+fn boolean(left: Value, op: &str, right: Value) -> bool {
+    match binary(left, op, right) {
+        Ok(Value::Boolean(value)) => value,
+        other => panic!("expected a boolean, got {:?}", other),
+    }
+}
+
+#[test]
+fn operators_apply_to_exactly_the_types_the_checker_accepts() {
+    for op in binary_ops() {
+        for (left, left_type) in samples() {
+            for (right, right_type) in samples() {
+                let expected = checker::binary_type(op, &left_type, &right_type);
+                match (left.binary(op, &right), expected) {
+                    (Ok(value), Some(ty)) => {
+                        let actual = samples().into_iter().find(|(sample, _)| sample.type_name() == value.type_name()).unwrap().1;
+                        assert_eq!(actual, ty, "{} {} {}", left_type, op.as_str(), right_type);
+                    }
+                    (Err(message), None) => {
+                        assert_eq!(message, format!("Operator {} can't be applied to {} and {}", op.as_str(), left.type_name(), right.type_name()));
+                    }
+                    (result, expected) => panic!("{} {} {}: runtime gives {:?}, checker {:?}", left_type, op.as_str(), right_type, result, expected),
+                }
+            }
+        }
+    }
+
+    for op in [UnaryOp::Not, UnaryOp::Negate] {
+        for (operand, ty) in samples() {
+            match (operand.unary(op), checker::unary_type(op, &ty)) {
+                (Ok(value), Some(expected)) => assert_eq!(value.type_name(), expected.to_string()),
+                (Err(message), None) => assert_eq!(message, format!("Operator {} can't be applied to {}", op.as_str(), operand.type_name())),
+                (result, expected) => panic!("{} {}: runtime gives {:?}, checker {:?}", op.as_str(), ty, result, expected),
+            }
+        }
+    }
+}
+
+#[test]
+fn integer_arithmetic_is_checked_and_never_wraps() {
+    let max = Value::Integer(i64::MAX);
+    let min = Value::Integer(i64::MIN);
+    assert_eq!(binary(max.clone(), token::PLUS, Value::Integer(1)), Err("9223372036854775807 + 1 overflows integer".to_string()));
+    assert_eq!(binary(min.clone(), token::MINUS, Value::Integer(1)), Err("-9223372036854775808 - 1 overflows integer".to_string()));
+    assert_eq!(binary(max.clone(), token::TIMES, Value::Integer(2)), Err("9223372036854775807 * 2 overflows integer".to_string()));
+    assert_eq!(binary(min.clone(), token::DIV, Value::Integer(-1)), Err("-9223372036854775808 / -1 overflows integer".to_string()));
+    assert_eq!(min.unary(UnaryOp::Negate), Err("-(-9223372036854775808) overflows integer".to_string()));
+    assert_eq!(binary(Value::Integer(1), token::DIV, Value::Integer(0)), Err("Division by zero".to_string()));
+
+    // Right up to the bounds is fine
+    assert_eq!(binary(Value::Integer(i64::MAX - 1), token::PLUS, Value::Integer(1)), Ok(max.clone()));
+    assert_eq!(binary(min.clone(), token::PLUS, max.clone()), Ok(Value::Integer(-1)));
+    assert_eq!(max.unary(UnaryOp::Negate), Ok(Value::Integer(-i64::MAX)));
+
+    // Division truncates toward zero
+    assert_eq!(binary(Value::Integer(-7), token::DIV, Value::Integer(2)), Ok(Value::Integer(-3)));
+    assert_eq!(binary(Value::Integer(7), token::DIV, Value::Integer(-2)), Ok(Value::Integer(-3)));
+
+    // Bitwise operators work on two's complement and can't overflow
+    assert_eq!(binary(Value::Integer(-6), token::AMP, Value::Integer(3)), Ok(Value::Integer(2)));
+    assert_eq!(binary(Value::Integer(-8), token::PIPE, Value::Integer(1)), Ok(Value::Integer(-7)));
+    assert_eq!(binary(min, token::PIPE, max), Ok(Value::Integer(-1)));
+}
+
+#[test]
+fn strings_concatenate_with_strings_and_runes_but_runes_alone_do_not() {
+    assert_eq!(binary(string("ab"), token::PLUS, string("cd")), Ok(string("abcd")));
+    assert_eq!(binary(string("ab"), token::PLUS, Value::Rune('é')), Ok(string("abé")));
+    assert_eq!(binary(Value::Rune('😀'), token::PLUS, string("")), Ok(string("😀")));
+    assert_eq!(binary(Value::Rune('a'), token::PLUS, Value::Rune('b')), Err("Operator + can't be applied to rune and rune".to_string()));
+    assert_eq!(binary(string("1"), token::PLUS, Value::Integer(1)), Err("Operator + can't be applied to string and integer".to_string()));
+    assert_eq!(binary(Value::Rune('a'), token::PLUS, Value::Integer(1)), Err("Operator + can't be applied to rune and integer".to_string()));
+
+    // Secrets spread to what they're added to, and remember their values
+    let secret = Value::Secret(Secret::new("hunter2".to_string()));
+    match binary(string("pw="), token::PLUS, secret.clone()) {
+        Ok(Value::Secret(secret)) => assert_eq!((secret.text.as_str(), secret.values), ("pw=hunter2", vec!["hunter2".to_string()])),
+        other => panic!("expected a secret, got {:?}", other),
+    }
+    assert!(matches!(binary(secret.clone(), token::PLUS, Value::Rune('!')), Ok(Value::Secret(_))));
+    assert_eq!(binary(secret, token::PLUS, Value::Integer(1)), Err("Operator + can't be applied to secret and integer".to_string()));
+}
+
+#[test]
+fn ordering_compares_integers_runes_and_strings_by_code_point() {
+    assert!(boolean(Value::Integer(-1), token::LT, Value::Integer(0)));
+    assert!(boolean(Value::Integer(2), token::RT_EQ, Value::Integer(2)));
+    assert!(!boolean(Value::Integer(2), token::RT, Value::Integer(2)));
+    assert!(boolean(Value::Rune('a'), token::LT, Value::Rune('b')));
+    assert!(boolean(Value::Rune('é'), token::RT, Value::Rune('z')));
+    assert!(boolean(string("Z"), token::LT, string("a")));
+    assert!(boolean(string("ab"), token::LT, string("b")));
+    assert!(boolean(string(""), token::LT, string("a")));
+    assert!(boolean(string("abc"), token::LT_EQ, string("abc")));
+
+    // No order across types nor on other types
+    assert_eq!(binary(Value::Integer(97), token::LT, Value::Rune('b')), Err("Operator < can't be applied to integer and rune".to_string()));
+    assert_eq!(binary(string("a"), token::RT, Value::Rune('a')), Err("Operator > can't be applied to string and rune".to_string()));
+    assert_eq!(binary(Value::Boolean(false), token::LT, Value::Boolean(true)), Err("Operator < can't be applied to boolean and boolean".to_string()));
+    assert_eq!(binary(Value::List(vec![]), token::LT_EQ, Value::List(vec![])), Err("Operator <= can't be applied to list and list".to_string()));
+}
+
+#[test]
+fn equality_needs_values_of_the_same_type() {
+    assert!(boolean(Value::Integer(1), token::EQ, Value::Integer(1)));
+    assert!(boolean(string("a"), token::NEQ, string("b")));
+    assert!(boolean(Value::Boolean(true), token::EQ, Value::Boolean(true)));
+    assert!(boolean(point(1), token::EQ, point(1)));
+    assert!(boolean(point(1), token::NEQ, point(2)));
+
+    assert_eq!(binary(Value::Integer(1), token::EQ, Value::Rune('1')), Err("Operator == can't be applied to integer and rune".to_string()));
+    assert_eq!(binary(Value::Rune('a'), token::NEQ, string("a")), Err("Operator != can't be applied to rune and string".to_string()));
+    assert_eq!(binary(Value::Void, token::EQ, Value::Void), Err("Operator == can't be applied to void and void".to_string()));
+    let size = Value::Complex { name: "size".to_string(), fields: vec![("x".to_string(), Value::Integer(1))] };
+    assert_eq!(binary(point(1), token::EQ, size), Err("Operator == can't be applied to point and size".to_string()));
+}
+
+#[test]
+fn lists_are_equal_in_order_and_maps_in_any_order() {
+    let list = |values: &[i64]| Value::List(values.iter().map(|&value| Value::Integer(value)).collect());
+    assert!(boolean(list(&[1, 2]), token::EQ, list(&[1, 2])));
+    assert!(boolean(list(&[1, 2]), token::NEQ, list(&[2, 1])));
+    assert!(boolean(list(&[1]), token::NEQ, list(&[1, 1])));
+    assert!(boolean(list(&[]), token::EQ, list(&[])));
+
+    let map = |entries: &[(i64, &str)]| Value::Map(entries.iter().map(|&(key, value)| (Value::Integer(key), string(value))).collect());
+    assert!(boolean(map(&[(1, "a"), (2, "b")]), token::EQ, map(&[(2, "b"), (1, "a")])));
+    assert!(boolean(map(&[(1, "a"), (2, "b")]), token::NEQ, map(&[(1, "b"), (2, "a")])));
+    assert!(boolean(map(&[(1, "a")]), token::NEQ, map(&[(1, "a"), (2, "b")])));
+    assert_eq!(map(&[(1, "a"), (2, "b")]), map(&[(2, "b"), (1, "a")]));
+
+    // Nested values follow the same rules
+    let nested = |first: Value, second: Value| Value::List(vec![first, second]);
+    assert!(boolean(nested(map(&[(1, "a"), (2, "b")]), list(&[3])), token::EQ, nested(map(&[(2, "b"), (1, "a")]), list(&[3]))));
+    assert!(boolean(nested(list(&[1]), list(&[2])), token::NEQ, nested(list(&[2]), list(&[1]))));
+}
+
+#[test]
+fn logic_operators_work_on_booleans() {
+    for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+        let (left, right) = (Value::Boolean(a), Value::Boolean(b));
+        assert_eq!(boolean(left.clone(), token::AND, right.clone()), a && b);
+        assert_eq!(boolean(left.clone(), token::AMP, right.clone()), a && b);
+        assert_eq!(boolean(left.clone(), token::OR, right.clone()), a || b);
+        assert_eq!(boolean(left.clone(), token::PIPE, right), a || b);
+        assert_eq!(left.unary(UnaryOp::Not), Ok(Value::Boolean(!a)));
+    }
+    assert_eq!(binary(Value::Integer(1), token::AND, Value::Integer(1)), Err("Operator && can't be applied to integer and integer".to_string()));
+}
+
+#[test]
+fn constant_folding_computes_like_the_runtime() {
+    let constants = [
+        Constant::Integer(0),
+        Constant::Integer(-3),
+        Constant::Integer(i64::MAX),
+        Constant::Integer(i64::MIN),
+        Constant::Rune('a'),
+        Constant::String("ab".to_string()),
+        Constant::Boolean(false),
+        Constant::Boolean(true),
+    ];
+
+    for op in binary_ops() {
+        for left in &constants {
+            for right in &constants {
+                let folded = constant::binary(op, left, right);
+                let computed = left.to_value().binary(op, &right.to_value());
+                match (folded, computed) {
+                    (Ok(Some(folded)), Ok(value)) => assert_eq!(folded.to_value(), value),
+                    (Ok(None), Err(message)) => assert!(message.starts_with("Operator"), "{}", message),
+                    (Err(folded), Err(message)) => assert_eq!(folded, message),
+                    (folded, computed) => panic!("{} {} {}: folded {:?}, computed {:?}", left, op.as_str(), right, folded, computed),
+                }
+            }
+        }
+    }
+
+    for op in [UnaryOp::Not, UnaryOp::Negate] {
+        for operand in &constants {
+            match (constant::unary(op, operand), operand.to_value().unary(op)) {
+                (Ok(Some(folded)), Ok(value)) => assert_eq!(folded.to_value(), value),
+                (Ok(None), Err(message)) => assert!(message.starts_with("Operator"), "{}", message),
+                (Err(folded), Err(message)) => assert_eq!(folded, message),
+                (folded, computed) => panic!("{} {}: folded {:?}, computed {:?}", op.as_str(), operand, folded, computed),
+            }
+        }
+    }
+}
+
+#[test]
+fn conversions_are_explicit() {
+    let call = |name: &str, args: &[Value]| stdlib::get(name).unwrap().call(args);
+    assert_eq!(call("to_string", &[Value::Integer(-42)]), Ok(string("-42")));
+    assert_eq!(call("to_string", &[Value::Rune('é')]), Ok(string("é")));
+    assert_eq!(call("to_string", &[Value::Boolean(false)]), Ok(string("false")));
+    assert_eq!(call("parse_int", &[string("-42")]), Ok(Value::Integer(-42)));
+    assert_eq!(call("parse_int", &[string("ff"), Value::Integer(16)]), Ok(Value::Integer(255)));
+    assert_eq!(call("parse_int", &[string("9223372036854775808")]), Err("Can't read \"9223372036854775808\" as an integer in base 10".to_string()));
+    assert_eq!(call("format_int", &[Value::Integer(-255), Value::Integer(16)]), Ok(string("-ff")));
+    assert_eq!(call("format_int", &[Value::Integer(i64::MIN), Value::Integer(2)]), Ok(string(&format!("-1{}", "0".repeat(63)))));
+}
+
+#[test]
+fn values_display_as_written_in_source_when_nested() {
+    assert_eq!(Value::Integer(-5).to_string(), "-5");
+    assert_eq!(string("a b").to_string(), "a b");
+    assert_eq!(Value::Rune('x').to_string(), "x");
+    assert_eq!(Value::Boolean(true).to_string(), "true");
+    assert_eq!(Value::Void.to_string(), "void");
+    assert_eq!(Value::Secret(Secret::new("hunter2".to_string())).to_string(), "****");
+
+    let fields = vec![("name".to_string(), string("a")), ("initial".to_string(), Value::Rune('b')), ("age".to_string(), Value::Integer(3))];
+    assert_eq!(Value::Complex { name: "person".to_string(), fields }.to_string(), "person { name: \"a\", initial: 'b', age: 3 }");
+    assert_eq!(Value::Complex { name: "empty".to_string(), fields: vec![] }.to_string(), "empty {}");
+    assert_eq!(Value::List(vec![string("a"), Value::Rune('b'), Value::List(vec![])]).to_string(), "[\"a\", 'b', []]");
+    assert_eq!(Value::Map(vec![(string("k"), Value::Boolean(false)), (Value::Integer(1), string("v"))]).to_string(), "[\"k\": false, 1: \"v\"]");
+    assert_eq!(Value::Map(vec![]).to_string(), "[:]");
+}
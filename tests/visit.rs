@@ -0,0 +1,83 @@
+use shanty::ast::{Expr, ExprKind};
+use shanty::formatter;
+use shanty::parser;
+use shanty::visit::{self, CallCounter, Rename, Visitor, VisitorMut};
+
+const SOURCE: &str = "func integer double(integer n) {
+    return n * 2;
+}
+
+integer total = double(double(1));
+while (total < 100) {
+    total = double(total);
+}
+complex Job {
+    name: string;
+}
+Job job = Job { name: \"build\" };
+repo backend {
+    url = \"git@example.com:backend.git\";
+}
+";
+
+#[test]
+fn counts_calls_in_nested_expressions_and_blocks() {
+    let program = parser::parse(SOURCE).unwrap();
+
+    let mut counter = CallCounter { name: "double", count: 0 };
+    counter.visit_program(&program);
+    assert_eq!(counter.count, 3);
+
+    let mut counter = CallCounter { name: "missing", count: 0 };
+    counter.visit_program(&program);
+    assert_eq!(counter.count, 0);
+}
+
+#[test]
+fn renames_declarations_and_uses() {
+    let mut program = parser::parse(SOURCE).unwrap();
+
+    Rename { from: "double", to: "twice" }.visit_program_mut(&mut program);
+    Rename { from: "total", to: "sum" }.visit_program_mut(&mut program);
+
+    let expected = "func integer twice(integer n) {
+    return n * 2;
+}
+
+integer sum = twice(twice(1));
+while (sum < 100) {
+    sum = twice(sum);
+}
+
+complex Job {
+    name: string;
+}
+
+Job job = Job { name: \"build\" };
+
+repo backend {
+    url = \"git@example.com:backend.git\";
+}
+";
+    assert_eq!(formatter::format_program(&program), expected);
+}
+
+/// Overriding a single node type still reaches every occurrence through the default walkers.
+#[test]
+fn default_walkers_reach_every_expression() {
+    struct Literals(Vec<String>);
+
+    impl<'ast> Visitor<'ast> for Literals {
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            if let ExprKind::String(value) = &expr.kind {
+                self.0.push(value.clone());
+            }
+            visit::walk_expr(self, expr)
+        }
+    }
+
+    let program = parser::parse(SOURCE).unwrap();
+    let mut literals = Literals(vec![]);
+    literals.visit_program(&program);
+    assert_eq!(literals.0, vec!["build", "git@example.com:backend.git"]);
+}